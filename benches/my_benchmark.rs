@@ -3,14 +3,121 @@ extern crate criterion;
 extern crate pathfinder;
 use pathfinder::*;
 
-use criterion::Criterion;
+use criterion::{Benchmark, Criterion};
+use std::collections::LinkedList;
+use std::time::Duration;
+
+fn flyzone() -> Vec<Vec<Location>> {
+    vec![vec![
+        Location::from_degrees(30.276450732764616, -97.74291515350342, 0f32),
+        Location::from_degrees(30.276450732764616, -97.7239465713501, 0f32),
+        Location::from_degrees(30.29294185380876, -97.7239465713501, 0f32),
+        Location::from_degrees(30.29294185380876, -97.74291515350342, 0f32),
+    ]]
+}
+
+fn obstacles() -> Vec<Obstacle> {
+    vec![Obstacle::from_degrees(
+        30.286975723301133,
+        -97.7305555343628,
+        150f32,
+        250f32,
+    )]
+}
+
+// Grid of count small obstacles across the flyzone
+fn field(count: u32) -> Vec<Obstacle> {
+    let columns = (f64::from(count)).sqrt().ceil() as u32;
+    (0..count)
+        .map(|i| {
+            let lat = 30.2775 + 0.0150 * f64::from(i / columns) / f64::from(columns);
+            let lon = -97.7420 + 0.0170 * f64::from(i % columns) / f64::from(columns);
+            Obstacle::from_degrees(lat, lon, 15f32, 100f32)
+        })
+        .collect()
+}
 
 fn criterion_benchmark(c: &mut Criterion) {
-    let j = Point::from_radians(8f64, -2f64, 0f32);
-    let k = Point::from_radians(16f64, 6f64, 0f32);
-    let ob = Obstacle::from_radians(15f64, 0f64, 5f32, 20f32);
-    c.bench_function("perpendicular method", move |b| {
-        b.iter(|| Pathfinder::valid_path_obs(&j, &k, &ob))
+    c.bench_function("build graph", |b| {
+        b.iter(|| Pathfinder::new(Tanstar::new(), TConfig::default(), flyzone(), obstacles()))
+    });
+
+    // Tangents of every pair of nodes are found in parallel with the rayon feature
+    c.bench(
+        "build graph",
+        Benchmark::new("200 obstacles", |b| {
+            b.iter(|| Pathfinder::new(Tanstar::new(), TConfig::default(), flyzone(), field(200)))
+        })
+        .sample_size(10),
+    );
+
+    // Segments are only checked against the obstacles and flyzone edges near them, the time
+    // limit is raised so slower machines still finish the field
+    let config = TConfig {
+        max_process_time: Duration::from_secs(60),
+        ..Default::default()
+    };
+    c.bench(
+        "build graph",
+        Benchmark::new("500 obstacles", move |b| {
+            b.iter(|| Pathfinder::new(Tanstar::new(), config.clone(), flyzone(), field(500)))
+        })
+        .sample_size(10),
+    );
+
+    // Same field with the spatial index turned off, so every segment is checked against every
+    // obstacle and flyzone edge
+    let config = TConfig {
+        max_process_time: Duration::from_secs(60),
+        ..Default::default()
+    };
+    let unindexed = TConfig {
+        spatial_index: false,
+        ..config.clone()
+    };
+    c.bench(
+        "spatial index",
+        Benchmark::new("indexed", move |b| {
+            b.iter(|| Pathfinder::new(Tanstar::new(), config.clone(), flyzone(), field(200)))
+        })
+        .with_function("unindexed", move |b| {
+            b.iter(|| Pathfinder::new(Tanstar::new(), unindexed.clone(), flyzone(), field(200)))
+        })
+        .sample_size(10),
+    );
+
+    let mut pathfinder =
+        Pathfinder::new(Tanstar::new(), TConfig::default(), flyzone(), obstacles());
+    let plane = Plane::from_degrees(30.2881757, -97.7354343, 10.0);
+    let mut waypoints = LinkedList::new();
+    waypoints.push_back(Waypoint::<()>::from_degrees(
+        30.28718185424805,
+        -97.72671508789063,
+        76.1,
+        10f32,
+    ));
+    let mut fieldstar =
+        Pathfinder::new(Fieldstar::new(), FConfig::default(), flyzone(), obstacles());
+    let field_waypoints = waypoints.clone();
+    c.bench_function("adjust path", move |b| {
+        b.iter(|| pathfinder.get_adjust_path(plane, waypoints.clone()))
+    });
+    c.bench_function("field d* adjust path", move |b| {
+        b.iter(|| fieldstar.get_adjust_path(plane, field_waypoints.clone()))
+    });
+
+    // Search pattern of 30 legs, planned in parallel with the rayon feature
+    let mut pathfinder =
+        Pathfinder::new(Tanstar::new(), TConfig::default(), flyzone(), obstacles());
+    let pattern: LinkedList<Waypoint<()>> = (0..30)
+        .map(|i| {
+            let lat = 30.278 + 0.0005 * f64::from(i);
+            let lon = if i % 2 == 0 { -97.741 } else { -97.726 };
+            Waypoint::from_degrees(lat, lon, 76.1, 10f32)
+        })
+        .collect();
+    c.bench_function("adjust search pattern", move |b| {
+        b.iter(|| pathfinder.get_adjust_path(plane, pattern.clone()))
     });
 }
 
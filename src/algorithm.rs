@@ -3,25 +3,101 @@
 
 use super::*;
 
+// Path of a leg and its arrival time, or the reason it failed
+pub type Leg = Result<(LinkedList<Waypoint<()>>, f32), PathError>;
+
 pub trait Algorithm {
     type Config;
 
     fn init(
         &mut self,
         config: Self::Config,
-        flyzones: Vec<Vec<Location>>,
+        flyzones: Vec<Flyzone>,
         obstacles: Vec<Obstacle>,
-    );
-    fn adjust_path<T>(&mut self, start: Location, end: Location)
-        -> Option<LinkedList<Waypoint<T>>>;
+    ) -> Result<(), PathfinderError>;
+    fn adjust_path<T>(
+        &mut self,
+        start: Location,
+        end: Location,
+    ) -> Result<LinkedList<Waypoint<T>>, PathError>;
+    // Adjust path for a leg the plane starts `departure` seconds after planning begins
+    // Returns the path and the arrival time at end, static planners ignore time
+    fn adjust_path_at<T>(
+        &mut self,
+        start: Location,
+        end: Location,
+        departure: f32,
+    ) -> Result<(LinkedList<Waypoint<T>>, f32), PathError> {
+        self.adjust_path(start, end).map(|path| (path, departure))
+    }
+    // Adjust path for the first leg, flown from the plane in its current state
+    // Planners that ignore the heading start from the plane location
+    fn adjust_path_from<T>(
+        &mut self,
+        plane: &Plane,
+        end: Location,
+        departure: f32,
+    ) -> Result<(LinkedList<Waypoint<T>>, f32), PathError> {
+        self.adjust_path_at(plane.location, end, departure)
+    }
+    // Post-process the adjusted path of a whole mission flown from plane
+    fn smooth_path<T>(
+        &self,
+        plane: &Plane,
+        path: LinkedList<Waypoint<T>>,
+    ) -> LinkedList<Waypoint<T>> {
+        path
+    }
+    // Adjust every leg of a mission at once, the first flown from plane and leg i ending at
+    // goals[i], for planners able to search them concurrently; arrival times are leg durations
+    // None when the legs are adjusted one after the other, as when they depend on the arrival
+    // time of the previous leg
+    fn adjust_legs(&self, plane: &Plane, goals: &[Location]) -> Option<Vec<Leg>> {
+        None
+    }
+    // Whether location lies inside the flyzones
+    fn in_flyzone(&self, location: &Location) -> bool;
+    // Lowest and highest altitude allowed by the flyzones containing location, None outside
+    fn altitude_band(&self, location: &Location) -> Option<(f32, f32)>;
 
     // Getters
     fn get_config(&self) -> &Self::Config;
-    fn get_flyzone(&mut self) -> &Vec<Vec<Location>>;
+    fn get_flyzone(&mut self) -> &Vec<Flyzone>;
     fn get_obstacles(&self) -> &Vec<Obstacle>;
 
     // Setters
-    fn set_config(&mut self, config: Self::Config);
-    fn set_flyzone(&mut self, flyzone: Vec<Vec<Location>>);
-    fn set_obstacles(&mut self, obstacles: Vec<Obstacle>);
+    fn set_config(&mut self, config: Self::Config) -> Result<(), PathfinderError>;
+    fn set_flyzone(&mut self, flyzone: Vec<Flyzone>) -> Result<(), PathfinderError>;
+    fn set_obstacles(&mut self, obstacles: Vec<Obstacle>) -> Result<(), PathfinderError>;
+
+    // Single obstacle updates, planners able to update only the affected part override these
+    fn add_obstacle(&mut self, obstacle: Obstacle) -> Result<(), PathfinderError> {
+        let mut obstacles = self.get_obstacles().clone();
+        obstacles.push(obstacle);
+        self.set_obstacles(obstacles)
+    }
+    // Remove and return the obstacle at index
+    fn remove_obstacle(&mut self, index: usize) -> Result<Obstacle, PathfinderError> {
+        let mut obstacles = self.get_obstacles().clone();
+        if index >= obstacles.len() {
+            return Err(PathfinderError::UnknownObstacle { obstacle: index });
+        }
+        let removed = obstacles.remove(index);
+        self.set_obstacles(obstacles)?;
+        Ok(removed)
+    }
+    // Replace and return the obstacle at index
+    fn update_obstacle(
+        &mut self,
+        index: usize,
+        obstacle: Obstacle,
+    ) -> Result<Obstacle, PathfinderError> {
+        let mut obstacles = self.get_obstacles().clone();
+        if index >= obstacles.len() {
+            return Err(PathfinderError::UnknownObstacle { obstacle: index });
+        }
+        let replaced = ::std::mem::replace(&mut obstacles[index], obstacle);
+        self.set_obstacles(obstacles)?;
+        Ok(replaced)
+    }
 }
@@ -0,0 +1,361 @@
+// pathfinder.rs
+// command line interface planning the route of a mission file
+extern crate pathfinder;
+#[macro_use]
+extern crate serde_json;
+
+use pathfinder::*;
+use serde_json::Value;
+use std::env;
+use std::fmt::Write as FmtWrite;
+use std::fs;
+use std::io::{self, Write};
+use std::process;
+use std::time::Duration;
+
+// Every waypoint was reached
+const EXIT_SUCCESS: i32 = 0;
+// A waypoint could not be reached, the route up to it is still written
+const EXIT_PARTIAL: i32 = 1;
+// Arguments, mission file or mission data are invalid
+const EXIT_INVALID: i32 = 2;
+
+const USAGE: &str = "\
+Usage: pathfinder [OPTIONS] <MISSION>
+
+Plan the route of a JSON or TOML mission file and write the adjusted waypoints.
+
+Options:
+    -o, --output <FILE>                 write route to FILE instead of stdout
+    -g, --graph <FILE>                  write visibility graph and route to FILE as GeoJSON
+    -f, --format <json|csv|geojson>     route format, inferred from --output extension
+                                        and json by default
+    -c, --config <FILE>                 read Tan* config from TOML FILE instead of the mission
+        --buffer-size <METERS>          override buffer around obstacles
+        --max-process-time <SECONDS>    override maximum process time
+        --turning-radius <METERS>       override turning radius of the plane
+        --vertex-merge-threshold <METERS>
+                                        override vertex merge threshold
+        --virtualize-flyzone <true|false>
+                                        override flyzone virtualization
+        --anytime                       return best path found on timeout
+        --cruise-speed <METERS/SECOND>  override speed used to time moving obstacles
+        --dubins                        smooth the route into Dubins curves
+        --dubins-spacing <METERS>       override distance between waypoints along curves
+        --wind-speed <METERS/SECOND>    override speed of the wind
+        --wind-direction <DEGREES>      override direction the wind blows from
+        --cost <FUNCTION>               cost of edges: distance, time, energy or turn_penalized
+        --max-climb-angle <DEGREES>     override steepest climb of the plane
+        --max-descent-angle <DEGREES>   override steepest descent of the plane
+    -h, --help                          print this message
+
+Exit status: 0 if every waypoint was reached, 1 if the route is partial,
+2 if the input is invalid.
+
+Config fields are overridden by PATHFINDER_<FIELD> environment variables, such as
+PATHFINDER_BUFFER_SIZE or PATHFINDER_WIND__SPEED, themselves overridden by the options above.";
+
+#[derive(Clone, Copy, Debug, PartialEq)]
+enum Format {
+    Json,
+    Csv,
+    GeoJson,
+}
+
+impl Format {
+    fn parse(s: &str) -> Option<Self> {
+        match s.to_lowercase().as_str() {
+            "json" => Some(Format::Json),
+            "csv" => Some(Format::Csv),
+            "geojson" => Some(Format::GeoJson),
+            _ => None,
+        }
+    }
+
+    fn from_path(path: &str) -> Option<Self> {
+        path.rsplit('.').next().and_then(Self::parse)
+    }
+}
+
+#[derive(Debug, Default)]
+struct Args {
+    mission: String,
+    output: Option<String>,
+    graph: Option<String>,
+    format: Option<Format>,
+    config: Option<String>,
+    buffer_size: Option<f32>,
+    max_process_time: Option<f64>,
+    turning_radius: Option<f32>,
+    vertex_merge_threshold: Option<f32>,
+    virtualize_flyzone: Option<bool>,
+    anytime: bool,
+    cruise_speed: Option<f32>,
+    dubins: bool,
+    dubins_spacing: Option<f32>,
+    wind_speed: Option<f32>,
+    wind_direction: Option<f32>,
+    cost: Option<CostFunction>,
+    max_climb_angle: Option<f32>,
+    max_descent_angle: Option<f32>,
+}
+
+impl Args {
+    // Returns None if help was requested
+    fn parse<I: Iterator<Item = String>>(mut iter: I) -> Result<Option<Self>, String> {
+        let mut args = Args::default();
+        let mut mission = None;
+        while let Some(arg) = iter.next() {
+            let mut value = |name: &str| {
+                iter.next()
+                    .ok_or_else(|| format!("missing value for {}", name))
+            };
+            match arg.as_str() {
+                "-h" | "--help" => return Ok(None),
+                "-o" | "--output" => args.output = Some(value(&arg)?),
+                "-g" | "--graph" => args.graph = Some(value(&arg)?),
+                "-f" | "--format" => {
+                    let format = value(&arg)?;
+                    args.format = Some(
+                        Format::parse(&format)
+                            .ok_or_else(|| format!("unknown format {}", format))?,
+                    );
+                }
+                "-c" | "--config" => args.config = Some(value(&arg)?),
+                "--buffer-size" => args.buffer_size = Some(parse_value(&arg, value(&arg)?)?),
+                "--max-process-time" => {
+                    args.max_process_time = Some(parse_value(&arg, value(&arg)?)?)
+                }
+                "--turning-radius" => args.turning_radius = Some(parse_value(&arg, value(&arg)?)?),
+                "--vertex-merge-threshold" => {
+                    args.vertex_merge_threshold = Some(parse_value(&arg, value(&arg)?)?)
+                }
+                "--virtualize-flyzone" => {
+                    args.virtualize_flyzone = Some(parse_value(&arg, value(&arg)?)?)
+                }
+                "--anytime" => args.anytime = true,
+                "--cruise-speed" => args.cruise_speed = Some(parse_value(&arg, value(&arg)?)?),
+                "--dubins" => args.dubins = true,
+                "--dubins-spacing" => args.dubins_spacing = Some(parse_value(&arg, value(&arg)?)?),
+                "--wind-speed" => args.wind_speed = Some(parse_value(&arg, value(&arg)?)?),
+                "--wind-direction" => args.wind_direction = Some(parse_value(&arg, value(&arg)?)?),
+                "--cost" => {
+                    let cost = value(&arg)?;
+                    args.cost =
+                        Some(parse_cost(&cost).ok_or_else(|| format!("unknown cost {}", cost))?);
+                }
+                "--max-climb-angle" => {
+                    args.max_climb_angle = Some(parse_value(&arg, value(&arg)?)?)
+                }
+                "--max-descent-angle" => {
+                    args.max_descent_angle = Some(parse_value(&arg, value(&arg)?)?)
+                }
+                _ if arg.starts_with('-') => return Err(format!("unknown option {}", arg)),
+                _ if mission.is_none() => mission = Some(arg),
+                _ => return Err(format!("unexpected argument {}", arg)),
+            }
+        }
+        args.mission = mission.ok_or_else(|| "missing mission file".to_string())?;
+        Ok(Some(args))
+    }
+
+    // Format requested explicitly, otherwise inferred from the output file
+    fn format(&self) -> Format {
+        self.format
+            .or_else(|| self.output.as_ref().and_then(|p| Format::from_path(p)))
+            .unwrap_or(Format::Json)
+    }
+
+    fn apply(&self, config: &mut TConfig) {
+        if let Some(buffer_size) = self.buffer_size {
+            config.buffer_size = buffer_size;
+        }
+        if let Some(secs) = self.max_process_time {
+            config.max_process_time = Duration::from_millis((secs.max(0f64) * 1000f64) as u64);
+        }
+        if let Some(turning_radius) = self.turning_radius {
+            config.turning_radius = turning_radius;
+        }
+        if let Some(threshold) = self.vertex_merge_threshold {
+            config.vertex_merge_threshold = threshold;
+        }
+        if let Some(virtualize) = self.virtualize_flyzone {
+            config.virtualize_flyzone = virtualize;
+        }
+        if self.anytime {
+            config.anytime = true;
+        }
+        if let Some(speed) = self.cruise_speed {
+            config.cruise_speed = speed;
+        }
+        if self.dubins {
+            config.dubins = true;
+        }
+        if let Some(spacing) = self.dubins_spacing {
+            config.dubins_spacing = spacing;
+        }
+        if self.wind_speed.is_some() || self.wind_direction.is_some() {
+            let wind = config.wind.unwrap_or_else(|| Wind::new(0f32, 0f32));
+            config.wind = Some(Wind::new(
+                self.wind_speed.unwrap_or(wind.speed),
+                self.wind_direction.unwrap_or(wind.direction),
+            ));
+        }
+        if let Some(cost) = self.cost {
+            config.cost = cost;
+        }
+        if let Some(angle) = self.max_climb_angle {
+            config.max_climb_angle = angle;
+        }
+        if let Some(angle) = self.max_descent_angle {
+            config.max_descent_angle = angle;
+        }
+    }
+}
+
+fn parse_cost(cost: &str) -> Option<CostFunction> {
+    match cost {
+        "distance" => Some(CostFunction::Distance),
+        "time" => Some(CostFunction::Time),
+        "energy" => Some(CostFunction::Energy),
+        "turn_penalized" => Some(CostFunction::TurnPenalized),
+        _ => None,
+    }
+}
+
+fn parse_value<T: std::str::FromStr>(name: &str, value: String) -> Result<T, String> {
+    value
+        .parse()
+        .map_err(|_| format!("invalid value {} for {}", value, name))
+}
+
+fn waypoint_json(wp: &Waypoint<Value>) -> Value {
+    json!({
+        "lat": wp.location.lat_degree(),
+        "lon": wp.location.lon_degree(),
+        "alt": wp.location.alt(),
+        "radius": wp.radius,
+        "data": wp.data,
+    })
+}
+
+fn to_json(route: &[Waypoint<Value>]) -> String {
+    let route: Vec<Value> = route.iter().map(waypoint_json).collect();
+    serde_json::to_string_pretty(&route).unwrap()
+}
+
+fn to_csv(route: &[Waypoint<Value>]) -> String {
+    let mut csv = String::from("lat,lon,alt,radius\n");
+    for wp in route {
+        writeln!(
+            csv,
+            "{},{},{},{}",
+            wp.location.lat_degree(),
+            wp.location.lon_degree(),
+            wp.location.alt(),
+            wp.radius
+        )
+        .unwrap();
+    }
+    csv
+}
+
+// Route as a line starting from the plane, followed by a point for every waypoint
+fn to_geojson(plane: &Plane, route: &[Waypoint<Value>]) -> String {
+    let position = |loc: &Location| json!([loc.lon_degree(), loc.lat_degree(), loc.alt()]);
+    let mut line = vec![position(&plane.location)];
+    line.extend(route.iter().map(|wp| position(&wp.location)));
+
+    let mut features = vec![json!({
+        "type": "Feature",
+        "geometry": { "type": "LineString", "coordinates": line },
+        "properties": { "name": "route" },
+    })];
+    features.extend(route.iter().enumerate().map(|(i, wp)| {
+        json!({
+            "type": "Feature",
+            "geometry": { "type": "Point", "coordinates": position(&wp.location) },
+            "properties": { "index": i, "radius": wp.radius, "data": wp.data },
+        })
+    }));
+    serde_json::to_string_pretty(&json!({
+        "type": "FeatureCollection",
+        "features": features,
+    }))
+    .unwrap()
+}
+
+fn write_output(args: &Args, contents: &str) -> io::Result<()> {
+    match args.output {
+        Some(ref path) => fs::write(path, contents),
+        None => {
+            let stdout = io::stdout();
+            let mut handle = stdout.lock();
+            handle.write_all(contents.as_bytes())?;
+            if !contents.ends_with('\n') {
+                handle.write_all(b"\n")?;
+            }
+            Ok(())
+        }
+    }
+}
+
+fn run(args: &Args) -> Result<i32, String> {
+    let mut mission = Mission::<Value>::load(&args.mission).map_err(|e| e.to_string())?;
+    mission.config = match args.config {
+        Some(ref path) => TConfig::load(path),
+        None => mission.config.with_env(),
+    }
+    .map_err(|e| e.to_string())?;
+    args.apply(&mut mission.config);
+
+    let mut pathfinder = mission.pathfinder().map_err(|e| e.to_string())?;
+    let waypoints = mission.waypoint_list();
+    pathfinder
+        .validate_waypoints(&waypoints)
+        .map_err(|e| e.to_string())?;
+
+    let (route, code) = match pathfinder.get_adjust_path(mission.plane, waypoints) {
+        Ok(route) => (route, EXIT_SUCCESS),
+        Err(failure) => {
+            eprintln!("pathfinder: {}", failure);
+            (failure.path, EXIT_PARTIAL)
+        }
+    };
+    if let Some(ref path) = args.graph {
+        let graph = pathfinder.path_geojson(&mission.plane, &route);
+        fs::write(path, serde_json::to_string_pretty(&graph).unwrap())
+            .map_err(|e| format!("failed to write graph: {}", e))?;
+    }
+    let route: Vec<Waypoint<Value>> = route.into_iter().collect();
+
+    let contents = match args.format() {
+        Format::Json => to_json(&route),
+        Format::Csv => to_csv(&route),
+        Format::GeoJson => to_geojson(&mission.plane, &route),
+    };
+    write_output(args, &contents).map_err(|e| format!("failed to write route: {}", e))?;
+    Ok(code)
+}
+
+fn main() {
+    let args = match Args::parse(env::args().skip(1)) {
+        Ok(Some(args)) => args,
+        Ok(None) => {
+            println!("{}", USAGE);
+            process::exit(EXIT_SUCCESS);
+        }
+        Err(e) => {
+            eprintln!("pathfinder: {}\n\n{}", e, USAGE);
+            process::exit(EXIT_INVALID);
+        }
+    };
+
+    match run(&args) {
+        Ok(code) => process::exit(code),
+        Err(e) => {
+            eprintln!("pathfinder: {}", e);
+            process::exit(EXIT_INVALID);
+        }
+    }
+}
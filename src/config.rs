@@ -0,0 +1,196 @@
+// config.rs
+// Loading configs from TOML files with overrides from the environment
+
+use error::{ConfigError, PathfinderError};
+use serde::de::DeserializeOwned;
+use serde::Serialize;
+use std::env;
+use std::fs;
+use std::path::Path;
+use toml::{Table, Value};
+
+// Prefix of the environment variables overriding config fields, PATHFINDER_BUFFER_SIZE
+// overrides buffer_size
+pub const ENV_PREFIX: &str = "PATHFINDER_";
+
+// Config read from TOML, fields missing from the file keep their default
+pub trait LoadConfig: Serialize + DeserializeOwned {
+    // Check every field is in range
+    fn validate(&self) -> Result<(), PathfinderError>;
+
+    fn from_toml(toml: &str) -> Result<Self, ConfigError> {
+        let config: Self = ::toml::from_str(toml)?;
+        config.validate()?;
+        Ok(config)
+    }
+
+    // Load config from a TOML file, then apply the environment overrides
+    fn load<P: AsRef<Path>>(path: P) -> Result<Self, ConfigError> {
+        let config: Self = ::toml::from_str(&fs::read_to_string(path)?)?;
+        config.with_env()
+    }
+
+    // Override fields with the environment variables named after them
+    fn with_env(self) -> Result<Self, ConfigError> {
+        self.with_overrides(env::vars())
+    }
+
+    // Override fields with the variables named after them, other variables are ignored
+    // Fields of nested tables are separated by a double underscore, PATHFINDER_WIND__SPEED
+    // overrides wind.speed, and a whole table is set with an inline table such as
+    // PATHFINDER_WIND="{ speed = 5, direction = 90 }"
+    // Values are read as TOML, or as a string if they are not valid TOML
+    fn with_overrides<I>(self, vars: I) -> Result<Self, ConfigError>
+    where
+        I: IntoIterator<Item = (String, String)>,
+    {
+        let mut table = match Value::try_from(&self) {
+            Ok(Value::Table(table)) => table,
+            _ => Table::new(),
+        };
+        let mut vars: Vec<(Vec<String>, String, String)> = vars
+            .into_iter()
+            .filter(|(name, _)| name.starts_with(ENV_PREFIX) && name.len() > ENV_PREFIX.len())
+            .map(|(name, value)| {
+                let path = name[ENV_PREFIX.len()..]
+                    .split("__")
+                    .map(str::to_lowercase)
+                    .collect();
+                (path, name, value)
+            })
+            .collect();
+        // Tables are set before the fields overriding them, and fields of a table follow each other
+        vars.sort();
+        let mut config = self;
+        for (i, (path, name, value)) in vars.iter().enumerate() {
+            let parsed = format!("value = {}", value)
+                .parse::<Table>()
+                .ok()
+                .and_then(|mut parsed| parsed.remove("value"))
+                .unwrap_or_else(|| Value::String(value.clone()));
+            let error = || ConfigError::Env {
+                name: name.clone(),
+                value: value.clone(),
+            };
+            let (key, tables) = path.split_last().expect("empty path");
+            let mut current = &mut table;
+            for field in tables {
+                current = match current
+                    .entry(field.clone())
+                    .or_insert_with(|| Value::Table(Table::new()))
+                {
+                    Value::Table(nested) => nested,
+                    _ => return Err(error()),
+                };
+            }
+            current.insert(key.clone(), parsed);
+            // Fields of a table are checked together, wind needs both its speed and direction
+            if vars.get(i + 1).map(|next| &next.0[0]) == Some(&path[0]) {
+                continue;
+            }
+            config = Value::Table(table.clone())
+                .try_into()
+                .map_err(|_| error())?;
+        }
+        config.validate()?;
+        Ok(config)
+    }
+}
+
+#[cfg(test)]
+mod test {
+    use super::*;
+    use tanstar::TConfig;
+
+    fn vars(vars: &[(&str, &str)]) -> Vec<(String, String)> {
+        vars.iter()
+            .map(|&(name, value)| (name.to_string(), value.to_string()))
+            .collect()
+    }
+
+    #[test]
+    fn from_toml_test() {
+        let config =
+            TConfig::from_toml(include_str!("../pathfinder.toml")).expect("repo config loads");
+        assert_eq!(config.direct_path_modifier_weight, 0.001f32);
+        assert_eq!(config.heading_modifier_weight, 0f32);
+        assert_eq!(config.turning_radius, TConfig::default().turning_radius);
+
+        let config = TConfig::from_toml("buffer_size = 5\ncost = \"distance\"").unwrap();
+        assert_eq!(config.buffer_size, 5f32);
+        assert!(TConfig::from_toml("turning_radius = -1.0").is_err());
+        assert!(TConfig::from_toml("buffer_size = ").is_err());
+    }
+
+    #[test]
+    fn overrides_test() {
+        let config = TConfig::default()
+            .with_overrides(vars(&[
+                ("PATHFINDER_BUFFER_SIZE", "3"),
+                ("PATHFINDER_ANYTIME", "true"),
+                ("PATHFINDER_COST", "energy"),
+            ]))
+            .unwrap();
+        assert_eq!(config.buffer_size, 3f32);
+        assert!(config.anytime);
+        assert_eq!(config.cost, ::tanstar::CostFunction::Energy);
+
+        match TConfig::default().with_overrides(vars(&[("PATHFINDER_BUFFER_SIZE", "wide")])) {
+            Err(ConfigError::Env { name, value }) => {
+                assert_eq!(name, "PATHFINDER_BUFFER_SIZE");
+                assert_eq!(value, "wide");
+            }
+            other => panic!("expected env error, got {:?}", other.map(|_| ())),
+        }
+        match TConfig::default().with_overrides(vars(&[("PATHFINDER_TURNING_RADIUS", "0")])) {
+            Err(ConfigError::Invalid(PathfinderError::InvalidConfig { field })) => {
+                assert_eq!(field, "turning_radius")
+            }
+            other => panic!("expected invalid config, got {:?}", other.map(|_| ())),
+        }
+        // Other variables are ignored
+        let config = TConfig::default()
+            .with_overrides(vars(&[("PATHFINDER_UNKNOWN", "1"), ("HOME", "/root")]))
+            .unwrap();
+        assert_eq!(config, TConfig::default());
+    }
+
+    #[test]
+    fn nested_overrides_test() {
+        let wind = Some(::tanstar::Wind::new(5f32, 90f32));
+        let config = TConfig::default()
+            .with_overrides(vars(&[
+                ("PATHFINDER_WIND__SPEED", "5"),
+                ("PATHFINDER_WIND__DIRECTION", "90"),
+            ]))
+            .unwrap();
+        assert_eq!(config.wind, wind);
+        let config = TConfig::default()
+            .with_overrides(vars(&[(
+                "PATHFINDER_WIND",
+                "{ speed = 5, direction = 90 }",
+            )]))
+            .unwrap();
+        assert_eq!(config.wind, wind);
+
+        // fields override the table set by another variable
+        let config = TConfig::default()
+            .with_overrides(vars(&[
+                ("PATHFINDER_WIND__SPEED", "8"),
+                ("PATHFINDER_WIND", "{ speed = 5, direction = 90 }"),
+            ]))
+            .unwrap();
+        assert_eq!(config.wind, Some(::tanstar::Wind::new(8f32, 90f32)));
+
+        // a wind without a direction, and a field of a number, are reported
+        for (name, value) in [
+            ("PATHFINDER_WIND__SPEED", "5"),
+            ("PATHFINDER_BUFFER_SIZE__INNER", "1"),
+        ] {
+            match TConfig::default().with_overrides(vars(&[(name, value)])) {
+                Err(ConfigError::Env { name: found, .. }) => assert_eq!(found, name),
+                other => panic!("expected env error, got {:?}", other.map(|_| ())),
+            }
+        }
+    }
+}
@@ -0,0 +1,406 @@
+// error.rs
+// errors reported when pathfinder is given invalid mission data or fails to find a path
+
+use obj::Waypoint;
+use std::collections::LinkedList;
+use std::error::Error;
+use std::fmt;
+#[cfg(feature = "serde")]
+use std::io;
+
+#[derive(Clone, Debug, PartialEq)]
+pub enum PathfinderError {
+    // No flyzone was provided
+    EmptyFlyzone,
+    // Flyzone does not have enough vertices to form a polygon
+    TooFewVertices {
+        flyzone: usize,
+        count: usize,
+    },
+    // Two non-adjacent edges of a flyzone cross each other
+    SelfIntersection {
+        flyzone: usize,
+        edges: (usize, usize),
+    },
+    // Keep-out of a flyzone does not have enough vertices to form a polygon
+    TooFewKeepOutVertices {
+        flyzone: usize,
+        keep_out: usize,
+        count: usize,
+    },
+    // Two non-adjacent edges of a keep-out cross each other
+    KeepOutSelfIntersection {
+        flyzone: usize,
+        keep_out: usize,
+        edges: (usize, usize),
+    },
+    // Obstacle center, or a prism vertex, is not inside any flyzone or is inside a keep-out
+    ObstacleOutsideFlyzone {
+        obstacle: usize,
+    },
+    // No obstacle at the index given to remove or update
+    UnknownObstacle {
+        obstacle: usize,
+    },
+    // Prism obstacle does not have enough vertices to form a polygon
+    TooFewObstacleVertices {
+        obstacle: usize,
+        count: usize,
+    },
+    // Trajectory of a moving obstacle has fewer than two locations, or times that do not
+    // increase from one location to the next
+    InvalidTrajectory {
+        obstacle: usize,
+    },
+    // Floor of a flyzone is above its ceiling
+    InvalidAltitudes {
+        flyzone: usize,
+    },
+    // Waypoint is not inside any flyzone
+    WaypointOutsideFlyzone {
+        waypoint: usize,
+    },
+    // Waypoint is below the floor or above the ceiling of the flyzones containing it
+    WaypointAltitudeOutsideFlyzone {
+        waypoint: usize,
+    },
+    // Graph construction ran longer than the configured maximum process time
+    ProcessTimeExceeded,
+    // Config field is out of range, such as a negative or infinite size, or a grid cell size
+    // too small for the flyzones
+    InvalidConfig {
+        field: &'static str,
+    },
+}
+
+impl fmt::Display for PathfinderError {
+    fn fmt(&self, f: &mut fmt::Formatter) -> fmt::Result {
+        match self {
+            PathfinderError::EmptyFlyzone => write!(f, "require at least one flyzone"),
+            PathfinderError::TooFewVertices { flyzone, count } => write!(
+                f,
+                "flyzone {} has {} vertices, require at least 3",
+                flyzone, count
+            ),
+            PathfinderError::SelfIntersection {
+                flyzone,
+                edges: (a, b),
+            } => write!(
+                f,
+                "flyzone {} intersects itself between edge {} and edge {}",
+                flyzone, a, b
+            ),
+            PathfinderError::TooFewKeepOutVertices {
+                flyzone,
+                keep_out,
+                count,
+            } => write!(
+                f,
+                "keep-out {} of flyzone {} has {} vertices, require at least 3",
+                keep_out, flyzone, count
+            ),
+            PathfinderError::KeepOutSelfIntersection {
+                flyzone,
+                keep_out,
+                edges: (a, b),
+            } => write!(
+                f,
+                "keep-out {} of flyzone {} intersects itself between edge {} and edge {}",
+                keep_out, flyzone, a, b
+            ),
+            PathfinderError::ObstacleOutsideFlyzone { obstacle } => {
+                write!(f, "obstacle {} is outside of all flyzones", obstacle)
+            }
+            PathfinderError::UnknownObstacle { obstacle } => {
+                write!(f, "no obstacle at index {}", obstacle)
+            }
+            PathfinderError::TooFewObstacleVertices { obstacle, count } => write!(
+                f,
+                "obstacle {} has {} vertices, require at least 3",
+                obstacle, count
+            ),
+            PathfinderError::InvalidTrajectory { obstacle } => write!(
+                f,
+                "obstacle {} requires a trajectory of at least 2 locations at increasing times",
+                obstacle
+            ),
+            PathfinderError::InvalidAltitudes { flyzone } => write!(
+                f,
+                "flyzone {} has a minimum altitude above its maximum altitude",
+                flyzone
+            ),
+            PathfinderError::WaypointOutsideFlyzone { waypoint } => {
+                write!(f, "waypoint {} is outside of all flyzones", waypoint)
+            }
+            PathfinderError::WaypointAltitudeOutsideFlyzone { waypoint } => write!(
+                f,
+                "waypoint {} is outside the altitude limits of its flyzone",
+                waypoint
+            ),
+            PathfinderError::ProcessTimeExceeded => {
+                write!(f, "exceeded maximum process time while building graph")
+            }
+            PathfinderError::InvalidConfig { field } => {
+                write!(f, "config field {} is out of range", field)
+            }
+        }
+    }
+}
+
+impl Error for PathfinderError {}
+
+// Reason an algorithm failed to find a path between two locations
+#[derive(Clone, Copy, Debug, PartialEq)]
+pub enum PathError {
+    // Start location is inside an obstacle
+    StartInsideObstacle { obstacle: usize },
+    // Goal location is inside an obstacle
+    GoalInsideObstacle { obstacle: usize },
+    // Goal location is not inside any flyzone
+    GoalOutsideFlyzone,
+    // Goal altitude is outside the altitude limits of its flyzones
+    GoalAltitudeOutsideFlyzone,
+    // Every reachable vertex was explored without reaching the goal
+    OpenSetExhausted,
+    // Search ran longer than the configured maximum process time
+    ProcessTimeExceeded,
+    // Altitude change is too steep for the climb or descent angle, and no loop fits to lengthen
+    // the path
+    ClimbTooSteep,
+}
+
+impl fmt::Display for PathError {
+    fn fmt(&self, f: &mut fmt::Formatter) -> fmt::Result {
+        match self {
+            PathError::StartInsideObstacle { obstacle } => {
+                write!(f, "start is inside obstacle {}", obstacle)
+            }
+            PathError::GoalInsideObstacle { obstacle } => {
+                write!(f, "goal is inside obstacle {}", obstacle)
+            }
+            PathError::GoalOutsideFlyzone => write!(f, "goal is outside of all flyzones"),
+            PathError::GoalAltitudeOutsideFlyzone => {
+                write!(f, "goal is outside the altitude limits of its flyzone")
+            }
+            PathError::OpenSetExhausted => write!(f, "no path exists to the goal"),
+            PathError::ProcessTimeExceeded => write!(f, "exceeded maximum process time"),
+            PathError::ClimbTooSteep => {
+                write!(f, "altitude change is too steep to climb or descend")
+            }
+        }
+    }
+}
+
+impl Error for PathError {}
+
+// Report of a waypoint list that could not be fully adjusted
+#[derive(Debug)]
+pub struct AdjustPathFailure<T> {
+    // Index of the waypoint that could not be reached
+    pub index: usize,
+    pub reason: PathError,
+    // Adjusted path up to, but not including, the unreachable waypoint
+    pub path: LinkedList<Waypoint<T>>,
+}
+
+impl<T> fmt::Display for AdjustPathFailure<T> {
+    fn fmt(&self, f: &mut fmt::Formatter) -> fmt::Result {
+        write!(
+            f,
+            "failed to reach waypoint {}: {}",
+            self.index, self.reason
+        )
+    }
+}
+
+impl<T: fmt::Debug> Error for AdjustPathFailure<T> {
+    fn source(&self) -> Option<&(dyn Error + 'static)> {
+        Some(&self.reason)
+    }
+}
+
+// Errors parsing MAVLink mission files
+#[derive(Debug)]
+pub enum MavlinkError {
+    // Waypoints file does not start with the QGC WPL 110 header
+    InvalidHeader,
+    // Line of a waypoints file is not a valid mission item
+    InvalidItem {
+        line: usize,
+    },
+    // Mission item uses a frame other than a global AMSL or relative altitude frame
+    UnsupportedFrame {
+        seq: u16,
+        frame: u8,
+    },
+    // Mission item uses another altitude frame than the waypoints before it
+    MixedFrames {
+        seq: u16,
+    },
+    // Waypoints file or plan has no home position
+    MissingHome,
+    // Plan file is not valid QGroundControl plan json
+    #[cfg(feature = "serde")]
+    InvalidPlan(::serde_json::Error),
+}
+
+impl fmt::Display for MavlinkError {
+    fn fmt(&self, f: &mut fmt::Formatter) -> fmt::Result {
+        match self {
+            MavlinkError::InvalidHeader => write!(f, "missing QGC WPL 110 header"),
+            MavlinkError::InvalidItem { line } => {
+                write!(f, "invalid mission item on line {}", line)
+            }
+            MavlinkError::UnsupportedFrame { seq, frame } => {
+                write!(f, "mission item {} uses unsupported frame {}", seq, frame)
+            }
+            MavlinkError::MixedFrames { seq } => {
+                write!(f, "mission item {} changes the altitude frame", seq)
+            }
+            MavlinkError::MissingHome => write!(f, "missing home position"),
+            #[cfg(feature = "serde")]
+            MavlinkError::InvalidPlan(e) => write!(f, "invalid plan: {}", e),
+        }
+    }
+}
+
+impl Error for MavlinkError {
+    fn source(&self) -> Option<&(dyn Error + 'static)> {
+        match self {
+            #[cfg(feature = "serde")]
+            MavlinkError::InvalidPlan(e) => Some(e),
+            _ => None,
+        }
+    }
+}
+
+#[cfg(feature = "serde")]
+impl From<::serde_json::Error> for MavlinkError {
+    fn from(e: ::serde_json::Error) -> Self {
+        MavlinkError::InvalidPlan(e)
+    }
+}
+
+// Errors reading or writing a mission file
+#[cfg(feature = "serde")]
+#[derive(Debug)]
+pub enum MissionError {
+    Io(io::Error),
+    Json(::serde_json::Error),
+    TomlDe(::toml::de::Error),
+    TomlSer(::toml::ser::Error),
+}
+
+#[cfg(feature = "serde")]
+impl fmt::Display for MissionError {
+    fn fmt(&self, f: &mut fmt::Formatter) -> fmt::Result {
+        match self {
+            MissionError::Io(e) => write!(f, "failed to access mission file: {}", e),
+            MissionError::Json(e) => write!(f, "invalid mission json: {}", e),
+            MissionError::TomlDe(e) => write!(f, "invalid mission toml: {}", e),
+            MissionError::TomlSer(e) => write!(f, "failed to write mission toml: {}", e),
+        }
+    }
+}
+
+#[cfg(feature = "serde")]
+impl Error for MissionError {
+    fn source(&self) -> Option<&(dyn Error + 'static)> {
+        match self {
+            MissionError::Io(e) => Some(e),
+            MissionError::Json(e) => Some(e),
+            MissionError::TomlDe(e) => Some(e),
+            MissionError::TomlSer(e) => Some(e),
+        }
+    }
+}
+
+#[cfg(feature = "serde")]
+impl From<io::Error> for MissionError {
+    fn from(e: io::Error) -> Self {
+        MissionError::Io(e)
+    }
+}
+
+#[cfg(feature = "serde")]
+impl From<::serde_json::Error> for MissionError {
+    fn from(e: ::serde_json::Error) -> Self {
+        MissionError::Json(e)
+    }
+}
+
+#[cfg(feature = "serde")]
+impl From<::toml::de::Error> for MissionError {
+    fn from(e: ::toml::de::Error) -> Self {
+        MissionError::TomlDe(e)
+    }
+}
+
+#[cfg(feature = "serde")]
+impl From<::toml::ser::Error> for MissionError {
+    fn from(e: ::toml::ser::Error) -> Self {
+        MissionError::TomlSer(e)
+    }
+}
+
+// Errors loading a config file or applying environment overrides
+#[cfg(feature = "serde")]
+#[derive(Debug)]
+pub enum ConfigError {
+    Io(io::Error),
+    Toml(::toml::de::Error),
+    // Environment variable does not hold a valid value for its field
+    Env { name: String, value: String },
+    Invalid(PathfinderError),
+}
+
+#[cfg(feature = "serde")]
+impl fmt::Display for ConfigError {
+    fn fmt(&self, f: &mut fmt::Formatter) -> fmt::Result {
+        match self {
+            ConfigError::Io(e) => write!(f, "failed to access config file: {}", e),
+            ConfigError::Toml(e) => write!(f, "invalid config toml: {}", e),
+            ConfigError::Env { name, value } => {
+                write!(
+                    f,
+                    "invalid value {} for environment variable {}",
+                    value, name
+                )
+            }
+            ConfigError::Invalid(e) => write!(f, "invalid config: {}", e),
+        }
+    }
+}
+
+#[cfg(feature = "serde")]
+impl Error for ConfigError {
+    fn source(&self) -> Option<&(dyn Error + 'static)> {
+        match self {
+            ConfigError::Io(e) => Some(e),
+            ConfigError::Toml(e) => Some(e),
+            ConfigError::Env { .. } => None,
+            ConfigError::Invalid(e) => Some(e),
+        }
+    }
+}
+
+#[cfg(feature = "serde")]
+impl From<io::Error> for ConfigError {
+    fn from(e: io::Error) -> Self {
+        ConfigError::Io(e)
+    }
+}
+
+#[cfg(feature = "serde")]
+impl From<::toml::de::Error> for ConfigError {
+    fn from(e: ::toml::de::Error) -> Self {
+        ConfigError::Toml(e)
+    }
+}
+
+#[cfg(feature = "serde")]
+impl From<PathfinderError> for ConfigError {
+    fn from(e: PathfinderError) -> Self {
+        ConfigError::Invalid(e)
+    }
+}
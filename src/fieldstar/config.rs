@@ -0,0 +1,4 @@
+// Config struct for field d*
+pub use gridplan::config::*;
+
+pub type FConfig = GridConfig;
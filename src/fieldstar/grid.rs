@@ -0,0 +1,343 @@
+// grid.rs
+// Cells covering the flyzones, marked with the altitude up to which they are blocked
+
+use super::*;
+
+// Fraction of a cell within which points are considered on a cell edge
+const EPSILON: f32 = 1e-3;
+
+#[derive(Default)]
+pub struct Grid {
+    pub cell_size: f32,
+    pub cols: usize,
+    pub rows: usize,
+    // whether a cell lies inside the flyzones
+    inside: Vec<bool>,
+    // altitude up to which a cell is blocked, infinite outside of the flyzones
+    thresholds: Vec<f32>,
+}
+
+impl Grid {
+    // Cover the bounding box of the flyzones, origin being its lower left corner
+    pub fn new(
+        flyzones: &[Flyzone],
+        origin: &Location,
+        cell_size: f32,
+    ) -> Result<Self, PathfinderError> {
+        // Boundary followed by the keep-outs of each flyzone
+        let polygons: Vec<Vec<Vec<Point>>> = flyzones
+            .iter()
+            .map(|flyzone| {
+                flyzone
+                    .polygons()
+                    .map(|polygon| polygon_points(polygon, origin))
+                    .collect()
+            })
+            .collect();
+        let in_flyzone = |p: &Point| {
+            polygons.iter().any(|rings| {
+                inside_polygon(p, &rings[0]) && !rings[1..].iter().any(|k| inside_polygon(p, k))
+            })
+        };
+        let (max_x, max_y) = polygons
+            .iter()
+            .flatten()
+            .flatten()
+            .fold((0f32, 0f32), |(x, y), p| (x.max(p.x), y.max(p.y)));
+        let (cols, rows) = grid_size(max_x, max_y, cell_size)?;
+
+        let mut grid = Self {
+            cell_size,
+            cols,
+            rows,
+            inside: Vec::with_capacity(cols * rows),
+            thresholds: Vec::new(),
+        };
+        for cell in 0..cols * rows {
+            // Corners are pulled towards the center so cells along a flyzone edge count as inside
+            let (lower, upper) = grid.bounds(cell);
+            let inset = EPSILON * cell_size;
+            let corners = [
+                Point::new(lower.x + inset, lower.y + inset, 0f32),
+                Point::new(upper.x - inset, lower.y + inset, 0f32),
+                Point::new(upper.x - inset, upper.y - inset, 0f32),
+                Point::new(lower.x + inset, upper.y - inset, 0f32),
+            ];
+            let inside = corners.iter().all(&in_flyzone)
+                && !polygons.iter().flatten().flatten().any(|v| {
+                    v.x > lower.x + inset
+                        && v.x < upper.x - inset
+                        && v.y > lower.y + inset
+                        && v.y < upper.y - inset
+                });
+            grid.inside.push(inside);
+        }
+        grid.thresholds = grid.unblocked();
+        Ok(grid)
+    }
+
+    // Thresholds before any obstacle is marked
+    fn unblocked(&self) -> Vec<f32> {
+        self.inside
+            .iter()
+            .map(|&inside| {
+                if inside {
+                    f32::NEG_INFINITY
+                } else {
+                    f32::INFINITY
+                }
+            })
+            .collect()
+    }
+
+    // Mark cells blocked by the static obstacles, returns the cells whose threshold changed
+    pub fn mark(&mut self, obstacles: &[Obstacle], origin: &Location, buffer: f32) -> Vec<usize> {
+        let mut thresholds = self.unblocked();
+        let half_diagonal = self.cell_size * 2f32.sqrt() / 2f32;
+        for obstacle in obstacles.iter().filter(|o| !o.is_moving()) {
+            let center = Point::from((&obstacle.location, origin));
+            let polygon = match obstacle.shape {
+                Shape::Cylinder => Vec::new(),
+                Shape::Prism(ref vertices) => polygon_points(vertices, origin),
+            };
+            let reach = obstacle.radius + buffer;
+            for cell in self.cells_within(&center, reach) {
+                let (lower, upper) = self.bounds(cell);
+                let blocked = match obstacle.shape {
+                    Shape::Cylinder => {
+                        let dx = center.x - center.x.max(lower.x).min(upper.x);
+                        let dy = center.y - center.y.max(lower.y).min(upper.y);
+                        (dx * dx + dy * dy).sqrt() < reach
+                    }
+                    Shape::Prism(_) => {
+                        let mid = Point::new(
+                            (lower.x + upper.x) / 2f32,
+                            (lower.y + upper.y) / 2f32,
+                            0f32,
+                        );
+                        let n = polygon.len();
+                        inside_polygon(&mid, &polygon)
+                            || (0..n).any(|i| {
+                                let (_, _, dist2, _) =
+                                    intersect_distance(&polygon[i], &polygon[(i + 1) % n], &mid);
+                                dist2.sqrt() < buffer + half_diagonal
+                            })
+                    }
+                };
+                if blocked {
+                    thresholds[cell] = thresholds[cell].max(obstacle.height);
+                }
+            }
+        }
+        let changed = (0..thresholds.len())
+            .filter(|&cell| thresholds[cell] != self.thresholds[cell])
+            .collect();
+        self.thresholds = thresholds;
+        changed
+    }
+
+    // Relative cost of flying through a cell at the given altitude
+    pub fn cost(&self, cell: usize, min_height: f32) -> f32 {
+        if min_height > self.thresholds[cell] {
+            1f32
+        } else {
+            f32::INFINITY
+        }
+    }
+
+    pub fn node_count(&self) -> usize {
+        (self.cols + 1) * (self.rows + 1)
+    }
+
+    pub fn node_point(&self, node: usize) -> Point {
+        Point::new(
+            (node % (self.cols + 1)) as f32 * self.cell_size,
+            (node / (self.cols + 1)) as f32 * self.cell_size,
+            0f32,
+        )
+    }
+
+    // Corner nodes of a cell, counter clockwise from the lower left
+    pub fn corners(&self, cell: usize) -> [usize; 4] {
+        let (i, j) = (cell % self.cols, cell / self.cols);
+        let node = |i: usize, j: usize| j * (self.cols + 1) + i;
+        [
+            node(i, j),
+            node(i + 1, j),
+            node(i + 1, j + 1),
+            node(i, j + 1),
+        ]
+    }
+
+    // Lower left and upper right corner of a cell
+    pub fn bounds(&self, cell: usize) -> (Point, Point) {
+        let (i, j) = (cell % self.cols, cell / self.cols);
+        let size = self.cell_size;
+        (
+            Point::new(i as f32 * size, j as f32 * size, 0f32),
+            Point::new((i + 1) as f32 * size, (j + 1) as f32 * size, 0f32),
+        )
+    }
+
+    // Cells whose boundary contains the point, empty if the point is off the grid
+    pub fn cells_at(&self, point: &Point) -> Vec<usize> {
+        let (u, v) = (point.x / self.cell_size, point.y / self.cell_size);
+        if u < -EPSILON
+            || v < -EPSILON
+            || u > self.cols as f32 + EPSILON
+            || v > self.rows as f32 + EPSILON
+        {
+            return Vec::new();
+        }
+        let range = |x: f32, n: usize| {
+            let low = ((x - EPSILON).floor().max(0f32) as usize).min(n - 1);
+            let high = ((x + EPSILON).floor().max(0f32) as usize).min(n - 1);
+            low..=high
+        };
+        let mut cells = Vec::new();
+        for j in range(v, self.rows) {
+            for i in range(u, self.cols) {
+                cells.push(j * self.cols + i);
+            }
+        }
+        cells
+    }
+
+    // Cell containing the point, if it is on the grid
+    pub fn cell_at(&self, point: &Point) -> Option<usize> {
+        self.cells_at(point).first().cloned()
+    }
+
+    // Nodes sharing a cell with the node
+    pub fn neighbors(&self, node: usize) -> Vec<usize> {
+        let mut neighbors: Vec<usize> = self
+            .cells_at(&self.node_point(node))
+            .iter()
+            .flat_map(|&cell| self.corners(cell).to_vec())
+            .filter(|&n| n != node)
+            .collect();
+        neighbors.sort_unstable();
+        neighbors.dedup();
+        neighbors
+    }
+
+    // Whether the point lies on segment ab
+    pub fn on_segment(&self, point: &Point, a: &Point, b: &Point) -> bool {
+        let (_, _, dist2, _) = intersect_distance(a, b, point);
+        dist2.sqrt() < EPSILON * self.cell_size
+    }
+
+    // Snap points close to a node onto it
+    pub fn snap(&self, point: Point) -> Point {
+        let size = self.cell_size;
+        let node = Point::new(
+            (point.x / size).round() * size,
+            (point.y / size).round() * size,
+            point.z,
+        );
+        if point.distance(&node) < EPSILON * size {
+            node
+        } else {
+            point
+        }
+    }
+
+    // Cells intersecting the square around the center
+    fn cells_within(&self, center: &Point, reach: f32) -> Vec<usize> {
+        let index = |x: f32, n: usize| ((x / self.cell_size).floor().max(0f32) as usize).min(n);
+        let (i0, i1) = (
+            index(center.x - reach, self.cols),
+            index(center.x + reach, self.cols - 1),
+        );
+        let (j0, j1) = (
+            index(center.y - reach, self.rows),
+            index(center.y + reach, self.rows - 1),
+        );
+        let mut cells = Vec::new();
+        for j in j0..=j1 {
+            for i in i0..=i1 {
+                cells.push(j * self.cols + i);
+            }
+        }
+        cells
+    }
+}
+
+#[cfg(test)]
+mod test {
+    use super::*;
+
+    fn square(origin: &Location, size: f32) -> Vec<Flyzone> {
+        vec![vec![
+            Location::from_meters(0f32, 0f32, 0f32, origin),
+            Location::from_meters(0f32, size, 0f32, origin),
+            Location::from_meters(size, size, 0f32, origin),
+            Location::from_meters(size, 0f32, 0f32, origin),
+        ]
+        .into()]
+    }
+
+    #[test]
+    fn grid_layout_test() {
+        let origin = Location::from_degrees(0f64, 0f64, 0f32);
+        let grid = Grid::new(&square(&origin, 100f32), &origin, 10f32).unwrap();
+        assert_eq!((grid.cols, grid.rows), (10, 10));
+        assert!(grid.inside.iter().all(|&inside| inside));
+        assert_eq!(grid.corners(11), [12, 13, 24, 23]);
+        assert_eq!(grid.cells_at(&Point::new(15f32, 15f32, 0f32)), vec![11]);
+        assert_eq!(grid.cells_at(&Point::new(10f32, 15f32, 0f32)), vec![10, 11]);
+        assert_eq!(grid.cells_at(&Point::new(10f32, 10f32, 0f32)).len(), 4);
+        assert!(grid.cells_at(&Point::new(-5f32, 5f32, 0f32)).is_empty());
+        assert_eq!(grid.neighbors(0), vec![1, 11, 12]);
+        assert_eq!(grid.neighbors(12).len(), 8);
+    }
+
+    #[test]
+    fn grid_mark_test() {
+        let origin = Location::from_degrees(0f64, 0f64, 0f32);
+        let mut grid = Grid::new(&square(&origin, 100f32), &origin, 10f32).unwrap();
+        let obstacles = vec![Obstacle::new(
+            Location::from_meters(50f32, 50f32, 0f32, &origin),
+            8f32,
+            30f32,
+        )];
+        let changed = grid.mark(&obstacles, &origin, 2f32);
+        // Buffered radius reaches only the four cells sharing the center corner
+        assert_eq!(changed.len(), 4);
+        let center = grid.cell_at(&Point::new(55f32, 55f32, 0f32)).unwrap();
+        assert_eq!(grid.cost(center, 20f32), f32::INFINITY);
+        assert_eq!(grid.cost(center, 40f32), 1f32);
+        let corner = grid.cell_at(&Point::new(5f32, 5f32, 0f32)).unwrap();
+        assert_eq!(grid.cost(corner, 0f32), 1f32);
+        assert_eq!(
+            grid.cells_at(&Point::new(50f32, 50f32, 0f32))
+                .iter()
+                .filter(|&&cell| grid.cost(cell, 20f32) == 1f32)
+                .count(),
+            0
+        );
+
+        // Nothing changes when marking the same obstacles again
+        let changed = grid.mark(&obstacles, &origin, 2f32);
+        assert!(changed.is_empty());
+    }
+
+    #[test]
+    fn grid_keep_out_test() {
+        let origin = Location::from_degrees(0f64, 0f64, 0f32);
+        let mut flyzones = square(&origin, 100f32);
+        flyzones[0].keep_outs.push(vec![
+            Location::from_meters(30f32, 30f32, 0f32, &origin),
+            Location::from_meters(30f32, 70f32, 0f32, &origin),
+            Location::from_meters(70f32, 70f32, 0f32, &origin),
+            Location::from_meters(70f32, 30f32, 0f32, &origin),
+        ]);
+        let grid = Grid::new(&flyzones, &origin, 10f32).unwrap();
+        // Cells covered by the keep-out are outside, their neighbors stay inside
+        assert_eq!(grid.inside.iter().filter(|&&inside| !inside).count(), 16);
+        let hole = grid.cell_at(&Point::new(45f32, 45f32, 0f32)).unwrap();
+        assert_eq!(grid.cost(hole, 0f32), f32::INFINITY);
+        let edge = grid.cell_at(&Point::new(25f32, 45f32, 0f32)).unwrap();
+        assert_eq!(grid.cost(edge, 0f32), 1f32);
+    }
+}
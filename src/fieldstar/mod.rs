@@ -0,0 +1,436 @@
+// mod.rs
+// Field D*, grid based planner replanning incrementally as obstacles change
+use super::gridplan::{
+    generate_waypoint, grid_size, inside_obstacle, polygon_points, time_exceeded,
+};
+use super::obj::*;
+use super::tanstar::{inside_polygon, intersect_distance, Point, Tanstar};
+use super::Algorithm;
+use super::{PathError, PathfinderError};
+
+pub mod config;
+
+mod grid;
+mod search;
+
+pub use self::config::*;
+
+use self::grid::Grid;
+use self::search::Search;
+use std::collections::LinkedList;
+use std::time::SystemTime;
+
+pub struct Fieldstar {
+    // Configuration options
+    config: FConfig,
+    flyzones: Vec<Flyzone>,
+    obstacles: Vec<Obstacle>,
+    // private
+    start_time: SystemTime,
+    origin: Location, // Lower left corner of the grid
+    grid: Grid,
+    search: Option<Search>, // Kept between calls planning towards the same goal
+}
+
+impl Default for Fieldstar {
+    fn default() -> Self {
+        Self {
+            config: FConfig::default(),
+            flyzones: Vec::new(),
+            obstacles: Vec::new(),
+            start_time: SystemTime::now(),
+            origin: Location::from_degrees(0f64, 0f64, 0f32),
+            grid: Grid::default(),
+            search: None,
+        }
+    }
+}
+
+impl Fieldstar {
+    pub fn new() -> Self {
+        Fieldstar::default()
+    }
+
+    // Number of nodes expanded by the search towards the current goal
+    pub fn expanded(&self) -> usize {
+        match self.search {
+            Some(ref search) => search.expanded,
+            None => 0,
+        }
+    }
+
+    // Build the grid and its origin without touching the current state
+    fn build_grid(
+        config: &FConfig,
+        flyzones: &[Flyzone],
+        obstacles: &[Obstacle],
+    ) -> Result<(Location, Grid), PathfinderError> {
+        let origin = Tanstar::find_origin(flyzones);
+        let mut grid = Grid::new(flyzones, &origin, config.cell_size)?;
+        grid.mark(obstacles, &origin, config.buffer_size);
+        debug!(cols = grid.cols, rows = grid.rows, "built grid");
+        Ok((origin, grid))
+    }
+
+    // Swap in a new grid, dropping the search since node indices change
+    fn set_grid(&mut self, (origin, grid): (Location, Grid)) {
+        self.origin = origin;
+        self.grid = grid;
+        self.search = None;
+    }
+
+    fn time_exceeded(&self) -> bool {
+        time_exceeded(&self.start_time, self.config.max_process_time)
+    }
+
+    fn inside_obstacle(&self, location: &Location) -> Option<usize> {
+        inside_obstacle(&self.obstacles, &self.origin, location)
+    }
+
+    // Follow the interpolated costs from start until the goal cell is reached
+    // The cell holding the start is flown regardless of its cost
+    fn extract(&self, search: &Search, start: &Point, start_cell: usize) -> Option<Vec<Point>> {
+        let grid = &self.grid;
+        let mut points = vec![*start];
+        for _ in 0..grid.node_count() {
+            let current = points[points.len() - 1];
+            let cells = grid.cells_at(&current);
+            if cells.contains(&search.goal_cell) {
+                points.push(search.goal);
+                return Some(points);
+            }
+            let first = points.len() == 1;
+            let (cost, next) = cells
+                .into_iter()
+                .map(|cell| {
+                    let k = if first && cell == start_cell {
+                        1f32
+                    } else {
+                        search.cost(grid, cell)
+                    };
+                    search.traverse(grid, &current, cell, k)
+                })
+                .fold((f32::INFINITY, current), |best, candidate| {
+                    if candidate.0 < best.0 {
+                        candidate
+                    } else {
+                        best
+                    }
+                });
+            if cost.is_infinite() {
+                return None;
+            }
+            points.push(grid.snap(next));
+        }
+        None
+    }
+
+    // Drop points within a tenth of a cell of the line flown, set altitude by distance flown
+    fn waypoints<T>(
+        &self,
+        points: &[Point],
+        start_alt: f32,
+        end_alt: f32,
+    ) -> LinkedList<Waypoint<T>> {
+        let tolerance = self.config.cell_size / 10f32;
+        let mut corners = vec![points[0]];
+        let mut anchor = 0;
+        for i in 2..points.len() {
+            let (a, c) = (points[anchor], points[i]);
+            let deviates = points[anchor + 1..i].iter().any(|b| {
+                let (_, _, dist2, _) = intersect_distance(&a, &c, b);
+                dist2.sqrt() > tolerance
+            });
+            if deviates {
+                anchor = i - 1;
+                corners.push(points[anchor]);
+            }
+        }
+        corners.push(points[points.len() - 1]);
+        generate_waypoint(
+            &self.flyzones,
+            &self.origin,
+            &corners,
+            start_alt,
+            end_alt,
+            self.config.cell_size / 2f32,
+        )
+    }
+}
+
+impl Algorithm for Fieldstar {
+    type Config = FConfig;
+
+    fn init(
+        &mut self,
+        config: Self::Config,
+        flyzones: Vec<Flyzone>,
+        obstacles: Vec<Obstacle>,
+    ) -> Result<(), PathfinderError> {
+        config.validate()?;
+        Tanstar::validate_flyzones(&flyzones)?;
+        Tanstar::validate_obstacles(&flyzones, &obstacles)?;
+        let grid = Fieldstar::build_grid(&config, &flyzones, &obstacles)?;
+
+        self.config = config;
+        self.flyzones = flyzones;
+        self.obstacles = obstacles;
+        self.set_grid(grid);
+        Ok(())
+    }
+
+    // Find best path over the grid, reusing the previous search if the goal did not change
+    // Moving obstacles are ignored
+    fn adjust_path<T>(
+        &mut self,
+        start: Location,
+        end: Location,
+    ) -> Result<LinkedList<Waypoint<T>>, PathError> {
+        if let Some(obstacle) = self.inside_obstacle(&start) {
+            return Err(PathError::StartInsideObstacle { obstacle });
+        }
+        match self.altitude_band(&end) {
+            None => return Err(PathError::GoalOutsideFlyzone),
+            Some((min, max)) if end.alt() < min || end.alt() > max => {
+                return Err(PathError::GoalAltitudeOutsideFlyzone)
+            }
+            Some(_) => (),
+        }
+        if let Some(obstacle) = self.inside_obstacle(&end) {
+            return Err(PathError::GoalInsideObstacle { obstacle });
+        }
+
+        self.start_time = SystemTime::now();
+        let start_point = Point::from((&start, &self.origin));
+        let end_point = Point::from((&end, &self.origin));
+        let min_height = start.alt().min(end.alt());
+        let (start_cell, end_cell) = match (
+            self.grid.cell_at(&start_point),
+            self.grid.cell_at(&end_point),
+        ) {
+            (Some(start_cell), Some(end_cell)) => (start_cell, end_cell),
+            _ => return Err(PathError::OpenSetExhausted),
+        };
+
+        let mut search = match self.search.take() {
+            Some(mut search) if search.goal.x == end_point.x && search.goal.y == end_point.y => {
+                debug!("replanning towards previous goal");
+                search.move_start(start_point);
+                search.set_min_height(&self.grid, min_height);
+                search
+            }
+            _ => Search::new(&self.grid, end_point, end_cell, start_point, min_height),
+        };
+        let result = search.compute(&self.grid, start_cell, &|| self.time_exceeded());
+        let path = result.and_then(|_| match self.extract(&search, &start_point, start_cell) {
+            Some(points) => Ok(self.waypoints(&points, start.alt(), end.alt())),
+            None => Err(PathError::OpenSetExhausted),
+        });
+        debug!(expanded = search.expanded, "searched grid");
+        self.search = Some(search);
+        path
+    }
+
+    fn in_flyzone(&self, location: &Location) -> bool {
+        Tanstar::inside_flyzones(&self.flyzones, &self.origin, location)
+    }
+
+    fn altitude_band(&self, location: &Location) -> Option<(f32, f32)> {
+        Tanstar::flyzone_altitudes(&self.flyzones, &self.origin, location)
+    }
+
+    fn get_config(&self) -> &Self::Config {
+        &self.config
+    }
+
+    fn get_flyzone(&mut self) -> &Vec<Flyzone> {
+        &self.flyzones
+    }
+
+    fn get_obstacles(&self) -> &Vec<Obstacle> {
+        &self.obstacles
+    }
+
+    fn set_config(&mut self, config: Self::Config) -> Result<(), PathfinderError> {
+        config.validate()?;
+        let grid = Fieldstar::build_grid(&config, &self.flyzones, &self.obstacles)?;
+        self.config = config;
+        self.set_grid(grid);
+        Ok(())
+    }
+
+    fn set_flyzone(&mut self, flyzone: Vec<Flyzone>) -> Result<(), PathfinderError> {
+        Tanstar::validate_flyzones(&flyzone)?;
+        Tanstar::validate_obstacles(&flyzone, &self.obstacles)?;
+        let grid = Fieldstar::build_grid(&self.config, &flyzone, &self.obstacles)?;
+        self.flyzones = flyzone;
+        self.set_grid(grid);
+        Ok(())
+    }
+
+    // Only the cells whose obstacles changed are repaired in the search
+    fn set_obstacles(&mut self, obstacles: Vec<Obstacle>) -> Result<(), PathfinderError> {
+        Tanstar::validate_obstacles(&self.flyzones, &obstacles)?;
+        self.obstacles = obstacles;
+        let changed = self
+            .grid
+            .mark(&self.obstacles, &self.origin, self.config.buffer_size);
+        if let Some(ref mut search) = self.search {
+            search.update_cells(&self.grid, &changed);
+        }
+        Ok(())
+    }
+}
+
+#[cfg(test)]
+mod test {
+    use super::*;
+    use fixture::{leg, square_flyzone, wall};
+    use std::time::Duration;
+
+    fn length(start: &Location, end: &Location, path: &LinkedList<Waypoint<()>>) -> f32 {
+        let mut points = vec![*start];
+        points.extend(path.iter().map(|wp| wp.location));
+        points.push(*end);
+        points
+            .windows(2)
+            .map(|p| Point::from((&p[0], start)).distance(&Point::from((&p[1], start))))
+            .sum()
+    }
+
+    // Path points stay out of the obstacle, including points along every segment
+    fn assert_clear(origin: &Location, obstacle: &Obstacle, points: &[Location]) {
+        let center = Point::from((&obstacle.location, origin));
+        for pair in points.windows(2) {
+            let a = Point::from((&pair[0], origin));
+            let b = Point::from((&pair[1], origin));
+            let (_, _, dist2, _) = intersect_distance(&a, &b, &center);
+            assert!(dist2.sqrt() > obstacle.radius);
+        }
+    }
+
+    #[test]
+    fn fieldstar_path_test() {
+        let origin = Location::from_degrees(30.32469, -97.60466, 0f32);
+        let mut fieldstar = Fieldstar::new();
+        fieldstar
+            .init(
+                FConfig::default(),
+                square_flyzone(&origin),
+                vec![wall(&origin, 50f32)],
+            )
+            .unwrap();
+        let origin = fieldstar.origin;
+        let (start, end) = leg(&origin);
+
+        let path = fieldstar.adjust_path::<()>(start, end).unwrap();
+        assert!(!path.is_empty());
+        let mut points = vec![start];
+        points.extend(path.iter().map(|wp| wp.location));
+        points.push(end);
+        assert_clear(&origin, &wall(&origin, 50f32), &points);
+        // Detour around the wall stays close to the shortest path hugging the buffer
+        let distance = length(&start, &end, &path);
+        assert!(distance > 300f32 && distance < 340f32);
+
+        // Above the wall the route is straight
+        let high = Location::from_meters(50f32, 200f32, 60f32, &origin);
+        let over = Location::from_meters(350f32, 200f32, 60f32, &origin);
+        let path = fieldstar.adjust_path::<()>(high, over).unwrap();
+        assert!(path.is_empty());
+    }
+
+    #[test]
+    fn fieldstar_replan_test() {
+        let origin = Location::from_degrees(30.32469, -97.60466, 0f32);
+        let mut fieldstar = Fieldstar::new();
+        fieldstar
+            .init(FConfig::default(), square_flyzone(&origin), vec![])
+            .unwrap();
+        let origin = fieldstar.origin;
+        let (start, end) = leg(&origin);
+        assert!(fieldstar.adjust_path::<()>(start, end).unwrap().is_empty());
+        let initial = fieldstar.expanded();
+
+        // Obstacle appears on the route, only the cells around it are repaired
+        fieldstar.set_obstacles(vec![wall(&origin, 50f32)]).unwrap();
+        let path = fieldstar.adjust_path::<()>(start, end).unwrap();
+        assert!(!path.is_empty());
+
+        let mut fresh = Fieldstar::new();
+        fresh
+            .init(
+                FConfig::default(),
+                square_flyzone(&origin),
+                vec![wall(&origin, 50f32)],
+            )
+            .unwrap();
+        let expected = fresh.adjust_path::<()>(start, end).unwrap();
+        assert!((length(&start, &end, &path) - length(&start, &end, &expected)).abs() < 1f32);
+        assert!(fieldstar.expanded() - initial < fresh.expanded());
+
+        // Plane moved along the route, the search continues from the new start
+        let moved = Location::from_meters(100f32, 150f32, 10f32, &origin);
+        let path = fieldstar.adjust_path::<()>(moved, end).unwrap();
+        let mut points = vec![moved];
+        points.extend(path.iter().map(|wp| wp.location));
+        points.push(end);
+        assert_clear(&origin, &wall(&origin, 50f32), &points);
+    }
+
+    #[test]
+    fn fieldstar_invalid_test() {
+        let origin = Location::from_degrees(30.32469, -97.60466, 0f32);
+        let mut fieldstar = Fieldstar::new();
+        fieldstar
+            .init(
+                FConfig::default(),
+                square_flyzone(&origin),
+                vec![wall(&origin, 50f32)],
+            )
+            .unwrap();
+        let origin = fieldstar.origin;
+        let start = Location::from_meters(50f32, 200f32, 10f32, &origin);
+        let inside = Location::from_meters(200f32, 200f32, 10f32, &origin);
+        let outside = Location::from_meters(500f32, 200f32, 10f32, &origin);
+        assert_eq!(
+            fieldstar.adjust_path::<()>(start, inside),
+            Err(PathError::GoalInsideObstacle { obstacle: 0 })
+        );
+        assert_eq!(
+            fieldstar.adjust_path::<()>(start, outside),
+            Err(PathError::GoalOutsideFlyzone)
+        );
+    }
+
+    #[test]
+    fn fieldstar_config_test() {
+        let origin = Location::from_degrees(30.32469, -97.60466, 0f32);
+        let mut fieldstar = Fieldstar::new();
+        for &(buffer_size, secs, cell_size, field) in &[
+            (2f32, 10u64, 0f32, "cell_size"),
+            (2f32, 10u64, -1f32, "cell_size"),
+            (2f32, 10u64, ::std::f32::NAN, "cell_size"),
+            (2f32, 10u64, 1e-3f32, "cell_size"),
+            (-1f32, 10u64, 10f32, "buffer_size"),
+            (2f32, 0u64, 10f32, "max_process_time"),
+        ] {
+            let config = FConfig::new(buffer_size, Duration::from_secs(secs), cell_size);
+            assert_eq!(
+                fieldstar.init(config, square_flyzone(&origin), Vec::new()),
+                Err(PathfinderError::InvalidConfig { field })
+            );
+        }
+
+        fieldstar
+            .init(FConfig::default(), square_flyzone(&origin), Vec::new())
+            .unwrap();
+        let cols = fieldstar.grid.cols;
+        assert_eq!(
+            fieldstar.set_config(FConfig::new(2f32, Duration::from_secs(10), 0f32)),
+            Err(PathfinderError::InvalidConfig { field: "cell_size" })
+        );
+        assert_eq!(fieldstar.get_config(), &FConfig::default());
+        assert_eq!(fieldstar.grid.cols, cols);
+    }
+}
@@ -0,0 +1,250 @@
+// search.rs
+// Incremental backward search from the goal (D* Lite) with costs interpolated along cell edges
+
+extern crate ordered_float;
+
+use self::ordered_float::OrderedFloat;
+use super::*;
+use std::cmp::Reverse;
+use std::collections::BinaryHeap;
+
+type Key = (OrderedFloat<f32>, OrderedFloat<f32>);
+
+pub struct Search {
+    pub goal: Point,
+    pub goal_cell: usize,
+    start: Point,
+    min_height: f32,
+    km: f32, // Accumulated heuristic change as the start moves
+    g: Vec<f32>,
+    rhs: Vec<f32>,
+    open: BinaryHeap<Reverse<(Key, usize)>>,
+    keys: Vec<Option<Key>>, // Current key of nodes in the open list, stale heap entries differ
+    pub expanded: usize,
+}
+
+impl Search {
+    // Corners of the goal cell are seeded with the straight distance to the goal
+    pub fn new(grid: &Grid, goal: Point, goal_cell: usize, start: Point, min_height: f32) -> Self {
+        let count = grid.node_count();
+        let mut search = Self {
+            goal,
+            goal_cell,
+            start,
+            min_height,
+            km: 0f32,
+            g: vec![f32::INFINITY; count],
+            rhs: vec![f32::INFINITY; count],
+            open: BinaryHeap::new(),
+            keys: vec![None; count],
+            expanded: 0,
+        };
+        for &node in &grid.corners(goal_cell) {
+            search.rhs[node] = grid.node_point(node).distance(&goal);
+            search.push(grid, node);
+        }
+        search
+    }
+
+    // Relative cost of a cell, the goal cell is always flown to reach the goal
+    pub fn cost(&self, grid: &Grid, cell: usize) -> f32 {
+        if cell == self.goal_cell {
+            1f32
+        } else {
+            grid.cost(cell, self.min_height)
+        }
+    }
+
+    // Cheapest way from point across a cell of cost k onto one of its edges not containing point
+    // Returns the total cost to goal and the point on the edge
+    pub fn traverse(&self, grid: &Grid, point: &Point, cell: usize, k: f32) -> (f32, Point) {
+        let corners = grid.corners(cell);
+        let mut best = (f32::INFINITY, *point);
+        for i in 0..4 {
+            let (a, b) = (corners[i], corners[(i + 1) % 4]);
+            let (pa, pb) = (grid.node_point(a), grid.node_point(b));
+            if grid.on_segment(point, &pa, &pb) {
+                continue;
+            }
+            let candidate = interpolate(point, &pa, &pb, self.g[a], self.g[b], k);
+            if candidate.0 < best.0 {
+                best = candidate;
+            }
+        }
+        best
+    }
+
+    // Plane moved, keys already in the open list are offset instead of recomputed
+    pub fn move_start(&mut self, start: Point) {
+        self.km += self.start.distance(&start);
+        self.start = start;
+    }
+
+    // Cells blocked at the new altitude but not at the old one, or the other way around, change
+    pub fn set_min_height(&mut self, grid: &Grid, min_height: f32) {
+        if min_height == self.min_height {
+            return;
+        }
+        let changed: Vec<usize> = (0..grid.cols * grid.rows)
+            .filter(|&cell| grid.cost(cell, min_height) != grid.cost(cell, self.min_height))
+            .collect();
+        self.min_height = min_height;
+        self.update_cells(grid, &changed);
+    }
+
+    // Repair nodes around cells whose cost changed
+    pub fn update_cells(&mut self, grid: &Grid, cells: &[usize]) {
+        for &cell in cells {
+            for &node in &grid.corners(cell) {
+                self.update_vertex(grid, node);
+            }
+        }
+        debug!(cells = cells.len(), "updated changed cells");
+    }
+
+    // Expand nodes until the corners of the start cell are consistent
+    pub fn compute(
+        &mut self,
+        grid: &Grid,
+        start_cell: usize,
+        time_exceeded: &dyn Fn() -> bool,
+    ) -> Result<(), PathError> {
+        let corners = grid.corners(start_cell);
+        while let Some(top) = self.top_key() {
+            let done = corners
+                .iter()
+                .all(|&node| self.g[node] == self.rhs[node] && top >= self.key(grid, node));
+            if done {
+                break;
+            }
+            if time_exceeded() {
+                return Err(PathError::ProcessTimeExceeded);
+            }
+
+            let Reverse((old, node)) = self.open.pop().unwrap();
+            self.keys[node] = None;
+            let new = self.key(grid, node);
+            if old < new {
+                self.push(grid, node);
+                continue;
+            }
+            self.expanded += 1;
+            if self.g[node] > self.rhs[node] {
+                self.g[node] = self.rhs[node];
+            } else {
+                self.g[node] = f32::INFINITY;
+                self.update_vertex(grid, node);
+            }
+            for neighbor in grid.neighbors(node) {
+                self.update_vertex(grid, neighbor);
+            }
+        }
+        trace!(expanded = self.expanded, "computed path");
+        Ok(())
+    }
+
+    fn key(&self, grid: &Grid, node: usize) -> Key {
+        let cost = self.g[node].min(self.rhs[node]);
+        let h = grid.node_point(node).distance(&self.start);
+        (OrderedFloat(cost + h + self.km), OrderedFloat(cost))
+    }
+
+    fn push(&mut self, grid: &Grid, node: usize) {
+        let key = self.key(grid, node);
+        self.keys[node] = Some(key);
+        self.open.push(Reverse((key, node)));
+    }
+
+    // Smallest key in the open list, dropping stale entries
+    fn top_key(&mut self) -> Option<Key> {
+        while let Some(&Reverse((key, node))) = self.open.peek() {
+            if self.keys[node] == Some(key) {
+                return Some(key);
+            }
+            self.open.pop();
+        }
+        None
+    }
+
+    fn update_vertex(&mut self, grid: &Grid, node: usize) {
+        let point = grid.node_point(node);
+        if !grid.corners(self.goal_cell).contains(&node) {
+            self.rhs[node] = grid
+                .cells_at(&point)
+                .into_iter()
+                .map(|cell| self.traverse(grid, &point, cell, self.cost(grid, cell)).0)
+                .fold(f32::INFINITY, f32::min);
+        }
+        if self.g[node] != self.rhs[node] {
+            self.push(grid, node);
+        } else {
+            self.keys[node] = None;
+        }
+    }
+}
+
+// Minimize the cost of flying from p across a cell of cost k to a point of edge ab plus the cost
+// to goal there, linearly interpolated between ga at a and gb at b
+// Returns the total cost and the point on the edge
+pub fn interpolate(p: &Point, a: &Point, b: &Point, ga: f32, gb: f32, k: f32) -> (f32, Point) {
+    if k.is_infinite() || (ga.is_infinite() && gb.is_infinite()) {
+        return (f32::INFINITY, *a);
+    }
+    if ga.is_infinite() {
+        return (k * p.distance(b) + gb, *b);
+    }
+    if gb.is_infinite() {
+        return (k * p.distance(a) + ga, *a);
+    }
+    let length = a.distance(b);
+    let (ux, uy) = ((b.x - a.x) / length, (b.y - a.y) / length);
+    let (dx, dy) = (p.x - a.x, p.y - a.y);
+    let along = dx * ux + dy * uy;
+    let across = (dx * uy - dy * ux).abs();
+    let slope = (gb - ga) / length;
+
+    // Where the derivative of k * |p - q(s)| + ga + slope * s vanishes
+    let s = if slope.abs() < k {
+        (along - slope * across / (k * k - slope * slope).sqrt()).clamp(0f32, length)
+    } else if slope > 0f32 {
+        0f32
+    } else {
+        length
+    };
+    let q = Point::new(a.x + ux * s, a.y + uy * s, 0f32);
+    (k * p.distance(&q) + ga + slope * s, q)
+}
+
+#[cfg(test)]
+mod test {
+    use super::*;
+
+    #[test]
+    fn interpolate_test() {
+        let a = Point::new(10f32, 0f32, 0f32);
+        let b = Point::new(10f32, 10f32, 0f32);
+        let p = Point::new(0f32, 5f32, 0f32);
+
+        // Equal costs along the edge, fly straight across
+        let (cost, q) = interpolate(&p, &a, &b, 5f32, 5f32, 1f32);
+        assert!((cost - 15f32).abs() < 1e-4);
+        assert!((q.y - 5f32).abs() < 1e-4);
+
+        // Cost drops steeply towards b, aim for it
+        let (cost, q) = interpolate(&p, &a, &b, 20f32, 0f32, 1f32);
+        assert!((q.y - 10f32).abs() < 1e-4);
+        assert!((cost - 125f32.sqrt()).abs() < 1e-4);
+
+        // Gentle slope cuts the edge between the straight and the corner point
+        let (_, q) = interpolate(&p, &a, &b, 3f32, 0f32, 1f32);
+        assert!(q.y > 5f32 && q.y < 10f32);
+
+        assert_eq!(
+            interpolate(&p, &a, &b, 0f32, 0f32, f32::INFINITY).0,
+            f32::INFINITY
+        );
+        let (cost, q) = interpolate(&p, &a, &b, f32::INFINITY, 1f32, 1f32);
+        assert_eq!((q.x, q.y), (b.x, b.y));
+        assert!((cost - 1f32 - 125f32.sqrt()).abs() < 1e-4);
+    }
+}
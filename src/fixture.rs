@@ -0,0 +1,31 @@
+// fixture.rs
+// Flyzone and obstacle shared by the tests of the planners
+use obj::*;
+
+// Square flyzone 400 meters wide, north east of origin
+pub fn square_flyzone(origin: &Location) -> Vec<Flyzone> {
+    vec![vec![
+        Location::from_meters(0f32, 0f32, 0f32, origin),
+        Location::from_meters(0f32, 400f32, 0f32, origin),
+        Location::from_meters(400f32, 400f32, 0f32, origin),
+        Location::from_meters(400f32, 0f32, 0f32, origin),
+    ]
+    .into()]
+}
+
+// Cylinder in the middle of the square flyzone, across the leg
+pub fn wall(origin: &Location, height: f32) -> Obstacle {
+    Obstacle::new(
+        Location::from_meters(200f32, 200f32, 50f32, origin),
+        50f32,
+        height,
+    )
+}
+
+// Start and end of a leg from west to east through the wall, 10 meters up
+pub fn leg(origin: &Location) -> (Location, Location) {
+    (
+        Location::from_meters(50f32, 200f32, 10f32, origin),
+        Location::from_meters(350f32, 200f32, 10f32, origin),
+    )
+}
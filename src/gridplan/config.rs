@@ -0,0 +1,59 @@
+// Config struct shared by the grid planners
+use std::time::Duration;
+
+use super::PathfinderError;
+
+pub const DEFAULT_BUFFER_SIZE: f32 = 2f32;
+pub const DEFAULT_PROCESS_TIME: u64 = 10u64;
+pub const DEFAULT_CELL_SIZE: f32 = 10f32;
+
+#[derive(Clone, Debug, PartialEq)]
+#[cfg_attr(feature = "serde", derive(Serialize, Deserialize), serde(default))]
+pub struct GridConfig {
+    // buffer around obstacles, in meters
+    pub buffer_size: f32,
+    // maximum processed time allowed, serialized in seconds
+    #[cfg_attr(feature = "serde", serde(with = "::tanstar::config::seconds"))]
+    pub max_process_time: Duration,
+    // width of the square grid cells, in meters
+    pub cell_size: f32,
+}
+
+impl Default for GridConfig {
+    fn default() -> Self {
+        Self::new(
+            DEFAULT_BUFFER_SIZE,
+            Duration::from_secs(DEFAULT_PROCESS_TIME),
+            DEFAULT_CELL_SIZE,
+        )
+    }
+}
+
+impl GridConfig {
+    pub fn new(buffer_size: f32, max_process_time: Duration, cell_size: f32) -> Self {
+        Self {
+            buffer_size,
+            max_process_time,
+            cell_size,
+        }
+    }
+
+    // Buffer must be finite and not negative, cell size finite and positive, and the process
+    // time not zero
+    pub fn validate(&self) -> Result<(), PathfinderError> {
+        if !self.buffer_size.is_finite() || self.buffer_size < 0f32 {
+            return Err(PathfinderError::InvalidConfig {
+                field: "buffer_size",
+            });
+        }
+        if !self.cell_size.is_finite() || self.cell_size <= 0f32 {
+            return Err(PathfinderError::InvalidConfig { field: "cell_size" });
+        }
+        if self.max_process_time == Duration::from_secs(0) {
+            return Err(PathfinderError::InvalidConfig {
+                field: "max_process_time",
+            });
+        }
+        Ok(())
+    }
+}
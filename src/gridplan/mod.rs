@@ -0,0 +1,120 @@
+// mod.rs
+// Helpers shared by the grid planners, Field D* and Phi*
+use super::obj::*;
+use super::tanstar::{inside_polygon, intersect_distance, Point, Tanstar};
+use super::PathfinderError;
+
+pub mod config;
+
+use std::collections::LinkedList;
+use std::time::{Duration, SystemTime};
+
+// Most cells a grid may have, smaller cells over the flyzones are rejected
+pub const MAX_CELLS: usize = 1 << 22;
+
+// Number of cells along x and y covering up to max_x and max_y
+pub fn grid_size(
+    max_x: f32,
+    max_y: f32,
+    cell_size: f32,
+) -> Result<(usize, usize), PathfinderError> {
+    let cols = ((max_x / cell_size).ceil() as usize).max(1);
+    let rows = ((max_y / cell_size).ceil() as usize).max(1);
+    match cols.checked_mul(rows) {
+        Some(cells) if cells <= MAX_CELLS => Ok((cols, rows)),
+        _ => Err(PathfinderError::InvalidConfig { field: "cell_size" }),
+    }
+}
+
+pub fn time_exceeded(start_time: &SystemTime, max_process_time: Duration) -> bool {
+    match start_time.elapsed() {
+        Ok(elapsed) => elapsed > max_process_time,
+        Err(_) => false,
+    }
+}
+
+// Vertices of a polygon relative to origin
+pub fn polygon_points(vertices: &[Location], origin: &Location) -> Vec<Point> {
+    vertices.iter().map(|v| Point::from((v, origin))).collect()
+}
+
+// Find the first static obstacle containing the location below its height
+pub fn inside_obstacle(
+    obstacles: &[Obstacle],
+    origin: &Location,
+    location: &Location,
+) -> Option<usize> {
+    let point = Point::from((location, origin));
+    obstacles.iter().position(|obstacle| {
+        if obstacle.is_moving() {
+            return false;
+        }
+        let inside = match obstacle.shape {
+            Shape::Cylinder => {
+                let center = Point::from((&obstacle.location, origin));
+                point.distance(&center) < obstacle.radius
+            }
+            Shape::Prism(ref vertices) => inside_polygon(&point, &polygon_points(vertices, origin)),
+        };
+        location.alt() < obstacle.height && inside
+    })
+}
+
+// Whether segment ab comes within distance of the polygon edges
+pub fn near_polygon(a: &Point, b: &Point, polygon: &[Point], distance: f32) -> bool {
+    let n = polygon.len();
+    let within = |p: &Point, q: &Point, c: &Point| {
+        let (_, _, dist2, _) = intersect_distance(p, q, c);
+        dist2.sqrt() < distance
+    };
+    (0..n).any(|i| {
+        let (c, d) = (&polygon[i], &polygon[(i + 1) % n]);
+        within(a, b, c) || within(a, b, d) || within(c, d, a) || within(c, d, b)
+    })
+}
+
+// Waypoints at the inner points of the path, altitude set by distance flown
+pub fn generate_waypoint<T>(
+    flyzones: &[Flyzone],
+    origin: &Location,
+    points: &[Point],
+    start_alt: f32,
+    end_alt: f32,
+    radius: f32,
+) -> LinkedList<Waypoint<T>> {
+    let total: f32 = points.windows(2).map(|p| p[0].distance(&p[1])).sum();
+    let slope = if total > 0f32 {
+        (end_alt - start_alt) / total
+    } else {
+        0f32
+    };
+    let mut flown = 0f32;
+    let mut waypoint_list = LinkedList::new();
+    for pair in points[..points.len() - 1].windows(2) {
+        flown += pair[0].distance(&pair[1]);
+        let point = Point::new(pair[1].x, pair[1].y, start_alt + flown * slope);
+        let mut location = Location::from((&point, origin));
+        Tanstar::clamp_altitude(flyzones, origin, &mut location);
+        trace!(location = %location, "waypoint");
+        waypoint_list.push_back(Waypoint::new(location, radius));
+    }
+    waypoint_list
+}
+
+#[cfg(test)]
+mod test {
+    use super::*;
+
+    #[test]
+    fn grid_size_test() {
+        assert_eq!(grid_size(95f32, 0f32, 10f32), Ok((10, 1)));
+        assert_eq!(
+            grid_size(400f32, 400f32, 1e-3f32),
+            Err(PathfinderError::InvalidConfig { field: "cell_size" })
+        );
+        assert_eq!(
+            grid_size(1e30f32, 1e30f32, 1f32),
+            Err(PathfinderError::InvalidConfig { field: "cell_size" })
+        );
+    }
+}
@@ -3,15 +3,48 @@
 #![allow(dead_code)]
 #![allow(unused_variables)]
 
+#[cfg(feature = "rayon")]
+extern crate rayon;
+#[macro_use]
+extern crate tracing;
+#[cfg(feature = "serde")]
+extern crate serde;
+#[cfg(feature = "serde")]
+#[macro_use]
+extern crate serde_derive;
+#[cfg(feature = "serde")]
+#[macro_use]
+extern crate serde_json;
+#[cfg(feature = "serde")]
+extern crate toml;
+
+#[cfg(feature = "serde")]
+pub mod config;
+pub mod error;
+pub mod fieldstar;
+pub mod mavlink;
+pub mod mission;
 pub mod obj;
+pub mod phistar;
 pub mod tanstar;
 
 mod algorithm;
+#[cfg(test)]
+mod fixture;
+mod gridplan;
 
+#[cfg(feature = "serde")]
+pub use config::LoadConfig;
+pub use error::{AdjustPathFailure, MavlinkError, PathError, PathfinderError};
+#[cfg(feature = "serde")]
+pub use error::{ConfigError, MissionError};
+pub use fieldstar::{FConfig, Fieldstar};
+pub use mission::Mission;
 pub use obj::*;
-pub use tanstar::{TConfig, Tanstar};
+pub use phistar::{PConfig, Phistar};
+pub use tanstar::{CostFunction, CostModel, TConfig, Tanstar, Wind};
 
-use algorithm::Algorithm;
+use algorithm::{Algorithm, Leg};
 use std::collections::LinkedList;
 
 pub struct Pathfinder<A: Algorithm> {
@@ -19,61 +52,178 @@ pub struct Pathfinder<A: Algorithm> {
 }
 
 impl<A: Algorithm> Pathfinder<A> {
-    pub fn new(
-        mut algo: A,
+    // Panics if flyzones or obstacles are invalid, see try_new
+    // Flyzones are Flyzone or plain boundaries without altitude limits
+    pub fn new<F: Into<Flyzone>>(
+        algo: A,
         config: A::Config,
-        flyzones: Vec<Vec<Location>>,
+        flyzones: Vec<F>,
         obstacles: Vec<Obstacle>,
     ) -> Self {
-        algo.init(config, flyzones, obstacles);
-        Self { algo }
+        match Self::try_new(algo, config, flyzones, obstacles) {
+            Ok(pathfinder) => pathfinder,
+            Err(e) => panic!("{}", e),
+        }
+    }
+
+    pub fn try_new<F: Into<Flyzone>>(
+        mut algo: A,
+        config: A::Config,
+        flyzones: Vec<F>,
+        obstacles: Vec<Obstacle>,
+    ) -> Result<Self, PathfinderError> {
+        algo.init(config, into_flyzones(flyzones), obstacles)?;
+        Ok(Self { algo })
     }
 
+    // Adjust every leg of the waypoint list, starting from the plane location, then let the
+    // algorithm smooth the whole path
+    // On failure, reports the unreachable waypoint and the path adjusted before it, unsmoothed
     pub fn get_adjust_path<T>(
         &mut self,
         plane: Plane,
-        mut wp_list: LinkedList<Waypoint<T>>,
-    ) -> LinkedList<Waypoint<T>> {
+        wp_list: LinkedList<Waypoint<T>>,
+    ) -> Result<LinkedList<Waypoint<T>>, AdjustPathFailure<T>> {
+        let goals: Vec<Location> = wp_list.iter().map(|wp| wp.location).collect();
+        if let Some(legs) = self.algo.adjust_legs(&plane, &goals) {
+            return self.stitch_legs(plane, wp_list, legs);
+        }
+
         let mut new_wp_list = LinkedList::new();
         let mut current_loc = plane.location;
+        // Seconds after planning begins the plane reaches current_loc
+        let mut departure = 0f32;
 
-        while let Some(current_wp) = wp_list.pop_front() {
+        for (index, current_wp) in wp_list.into_iter().enumerate() {
+            let span = info_span!("adjust_path", waypoint = index);
+            let _enter = span.enter();
             let next_loc = current_wp.location;
 
-            if let Some(mut path) = self.algo.adjust_path::<T>(current_loc, next_loc) {
-                println!("appending");
-                new_wp_list.append(&mut path);
+            // The first leg starts from the plane heading
+            let adjusted = if index == 0 {
+                self.algo.adjust_path_from::<T>(&plane, next_loc, departure)
             } else {
-                println!("no path");
-                break;
+                self.algo
+                    .adjust_path_at::<T>(current_loc, next_loc, departure)
+            };
+            match adjusted {
+                Ok((mut path, arrival)) => {
+                    debug!(inserted = path.len(), arrival, "adjusted leg");
+                    departure = arrival;
+                    new_wp_list.append(&mut path);
+                }
+                Err(reason) => {
+                    warn!(%reason, "failed to adjust leg");
+                    return Err(AdjustPathFailure {
+                        index,
+                        reason,
+                        path: new_wp_list,
+                    });
+                }
             }
 
             current_loc = current_wp.location;
             new_wp_list.push_back(current_wp);
-            // self.wp_list.push_back(self.current_wp.clone()); // Push original waypoint
-            // self.wp_list.push_back(Waypoint::from_degrees(0, 30.69, -97.69, 100f32, 10f32));
         }
 
-        new_wp_list
+        Ok(self.algo.smooth_path(&plane, new_wp_list))
     }
 
-    pub fn set_config(&mut self, config: A::Config) {
-        self.algo.set_config(config);
+    // Join legs adjusted at once in mission order, the same as adjusting them one after the other
+    fn stitch_legs<T>(
+        &self,
+        plane: Plane,
+        wp_list: LinkedList<Waypoint<T>>,
+        legs: Vec<Leg>,
+    ) -> Result<LinkedList<Waypoint<T>>, AdjustPathFailure<T>> {
+        let mut new_wp_list = LinkedList::new();
+        let mut departure = 0f32;
+        for (index, (current_wp, adjusted)) in wp_list.into_iter().zip(legs).enumerate() {
+            match adjusted {
+                Ok((path, duration)) => {
+                    departure += duration;
+                    debug!(
+                        waypoint = index,
+                        inserted = path.len(),
+                        arrival = departure,
+                        "adjusted leg"
+                    );
+                    new_wp_list.extend(
+                        path.into_iter()
+                            .map(|wp| Waypoint::new(wp.location, wp.radius)),
+                    );
+                }
+                Err(reason) => {
+                    warn!(waypoint = index, %reason, "failed to adjust leg");
+                    return Err(AdjustPathFailure {
+                        index,
+                        reason,
+                        path: new_wp_list,
+                    });
+                }
+            }
+            new_wp_list.push_back(current_wp);
+        }
+        Ok(self.algo.smooth_path(&plane, new_wp_list))
     }
 
-    pub fn set_flyzone(&mut self, flyzone: Vec<Vec<Location>>) {
-        self.algo.set_flyzone(flyzone);
+    // Check that every waypoint lies inside the flyzones, within their altitude limits
+    // get_adjust_path does not call it, callers check the waypoints before planning, otherwise
+    // the leg to a waypoint outside fails with PathError::GoalOutsideFlyzone, or
+    // GoalAltitudeOutsideFlyzone outside the altitude limits
+    pub fn validate_waypoints<T>(
+        &self,
+        wp_list: &LinkedList<Waypoint<T>>,
+    ) -> Result<(), PathfinderError> {
+        for (i, wp) in wp_list.iter().enumerate() {
+            match self.algo.altitude_band(&wp.location) {
+                None => return Err(PathfinderError::WaypointOutsideFlyzone { waypoint: i }),
+                Some((min, max)) if wp.location.alt() < min || wp.location.alt() > max => {
+                    return Err(PathfinderError::WaypointAltitudeOutsideFlyzone { waypoint: i })
+                }
+                Some(_) => (),
+            }
+        }
+        Ok(())
     }
 
-    pub fn set_obstacles(&mut self, obstacles: Vec<Obstacle>) {
-        self.algo.set_obstacles(obstacles);
+    pub fn set_config(&mut self, config: A::Config) -> Result<(), PathfinderError> {
+        self.algo.set_config(config)
+    }
+
+    pub fn set_flyzone<F: Into<Flyzone>>(
+        &mut self,
+        flyzone: Vec<F>,
+    ) -> Result<(), PathfinderError> {
+        self.algo.set_flyzone(into_flyzones(flyzone))
+    }
+
+    pub fn set_obstacles(&mut self, obstacles: Vec<Obstacle>) -> Result<(), PathfinderError> {
+        self.algo.set_obstacles(obstacles)
+    }
+
+    // Add, remove or replace a single obstacle, Tan* only updates the affected part of its graph
+    pub fn add_obstacle(&mut self, obstacle: Obstacle) -> Result<(), PathfinderError> {
+        self.algo.add_obstacle(obstacle)
+    }
+
+    pub fn remove_obstacle(&mut self, index: usize) -> Result<Obstacle, PathfinderError> {
+        self.algo.remove_obstacle(index)
+    }
+
+    pub fn update_obstacle(
+        &mut self,
+        index: usize,
+        obstacle: Obstacle,
+    ) -> Result<Obstacle, PathfinderError> {
+        self.algo.update_obstacle(index, obstacle)
     }
 
     pub fn get_config(&self) -> &A::Config {
         self.algo.get_config()
     }
 
-    pub fn get_flyzone(&mut self) -> &Vec<Vec<Location>> {
+    pub fn get_flyzone(&mut self) -> &Vec<Flyzone> {
         self.algo.get_flyzone()
     }
 
@@ -82,14 +232,41 @@ impl<A: Algorithm> Pathfinder<A> {
     }
 }
 
+fn into_flyzones<F: Into<Flyzone>>(flyzones: Vec<F>) -> Vec<Flyzone> {
+    flyzones.into_iter().map(Into::into).collect()
+}
+
+#[cfg(feature = "serde")]
+impl Pathfinder<Tanstar> {
+    // Visibility graph as GeoJSON, see Tanstar::graph_geojson
+    pub fn graph_geojson(&self) -> serde_json::Value {
+        self.algo.graph_geojson()
+    }
+
+    // Visibility graph and the path flown from the plane as GeoJSON
+    pub fn path_geojson<T>(
+        &self,
+        plane: &Plane,
+        path: &LinkedList<Waypoint<T>>,
+    ) -> serde_json::Value {
+        self.algo.path_geojson(&plane.location, path)
+    }
+}
+
 #[cfg(test)]
 mod tests {
     use super::*;
+    use fixture::square_flyzone;
 
     #[test]
     #[should_panic]
     fn tanstar_invalid_flyzones_test() {
-        Pathfinder::new(Tanstar::new(), TConfig::default(), vec![], Vec::new());
+        Pathfinder::new(
+            Tanstar::new(),
+            TConfig::default(),
+            Vec::<Flyzone>::new(),
+            Vec::new(),
+        );
     }
 
     #[test]
@@ -97,4 +274,291 @@ mod tests {
     fn tanstar_invalid_flyzone_test() {
         Pathfinder::new(Tanstar::new(), TConfig::default(), vec![vec![]], Vec::new());
     }
+
+    // South west corner of the square flyzone
+    fn corner() -> Location {
+        Location::from_degrees(30.32069, -97.60466, 0f32)
+    }
+
+    #[test]
+    fn try_new_invalid_flyzones_test() {
+        let result = Pathfinder::try_new(
+            Tanstar::new(),
+            TConfig::default(),
+            Vec::<Flyzone>::new(),
+            Vec::new(),
+        );
+        assert_eq!(result.err(), Some(PathfinderError::EmptyFlyzone));
+
+        let flyzone = vec![square_flyzone(&corner())[0].boundary[..2].to_vec()];
+        let result = Pathfinder::try_new(Tanstar::new(), TConfig::default(), flyzone, Vec::new());
+        assert_eq!(
+            result.err(),
+            Some(PathfinderError::TooFewVertices {
+                flyzone: 0,
+                count: 2
+            })
+        );
+
+        let mut flyzone = square_flyzone(&corner());
+        flyzone[0].boundary.swap(2, 3);
+        let result = Pathfinder::try_new(Tanstar::new(), TConfig::default(), flyzone, Vec::new());
+        assert_eq!(
+            result.err(),
+            Some(PathfinderError::SelfIntersection {
+                flyzone: 0,
+                edges: (1, 3)
+            })
+        );
+    }
+
+    #[test]
+    fn obstacle_outside_flyzone_test() {
+        let obstacles = vec![
+            Obstacle::from_degrees(30.32269, -97.60266, 10f32, 10f32),
+            Obstacle::from_degrees(30.33269, -97.60266, 10f32, 10f32),
+        ];
+        let result = Pathfinder::try_new(
+            Tanstar::new(),
+            TConfig::default(),
+            square_flyzone(&corner()),
+            obstacles.clone(),
+        );
+        assert_eq!(
+            result.err(),
+            Some(PathfinderError::ObstacleOutsideFlyzone { obstacle: 1 })
+        );
+
+        let mut pathfinder = Pathfinder::new(
+            Tanstar::new(),
+            TConfig::default(),
+            square_flyzone(&corner()),
+            obstacles[..1].to_vec(),
+        );
+        assert!(pathfinder.set_obstacles(obstacles).is_err());
+        // Rejected obstacles leave the previous state untouched
+        assert_eq!(pathfinder.get_obstacle().len(), 1);
+    }
+
+    #[test]
+    fn single_obstacle_updates_test() {
+        let inside = Obstacle::from_degrees(30.32269, -97.60266, 10f32, 10f32);
+        let outside = Obstacle::from_degrees(30.33269, -97.60266, 10f32, 10f32);
+        // Field D* relies on the default updates going through set_obstacles
+        let mut pathfinder = Pathfinder::new(
+            Fieldstar::new(),
+            FConfig::default(),
+            square_flyzone(&corner()),
+            Vec::new(),
+        );
+        pathfinder.add_obstacle(inside.clone()).unwrap();
+        assert_eq!(
+            pathfinder.add_obstacle(outside.clone()),
+            Err(PathfinderError::ObstacleOutsideFlyzone { obstacle: 1 })
+        );
+        assert_eq!(
+            pathfinder.update_obstacle(0, outside),
+            Err(PathfinderError::ObstacleOutsideFlyzone { obstacle: 0 })
+        );
+        assert_eq!(
+            pathfinder.remove_obstacle(1),
+            Err(PathfinderError::UnknownObstacle { obstacle: 1 })
+        );
+        assert_eq!(pathfinder.get_obstacle(), &vec![inside.clone()]);
+        assert_eq!(pathfinder.remove_obstacle(0), Ok(inside));
+        assert!(pathfinder.get_obstacle().is_empty());
+    }
+
+    #[test]
+    fn invalid_prism_test() {
+        let prism = |vertices: &[(f64, f64)]| {
+            Obstacle::prism(
+                vertices
+                    .iter()
+                    .map(|&(lat, lon)| Location::from_degrees(lat, lon, 10f32))
+                    .collect(),
+                10f32,
+            )
+        };
+        let result = Pathfinder::try_new(
+            Tanstar::new(),
+            TConfig::default(),
+            square_flyzone(&corner()),
+            vec![prism(&[(30.32269, -97.60266), (30.32369, -97.60266)])],
+        );
+        assert_eq!(
+            result.err(),
+            Some(PathfinderError::TooFewObstacleVertices {
+                obstacle: 0,
+                count: 2
+            })
+        );
+
+        // center is inside the flyzone but one corner is not
+        let result = Pathfinder::try_new(
+            Tanstar::new(),
+            TConfig::default(),
+            square_flyzone(&corner()),
+            vec![prism(&[
+                (30.32269, -97.60266),
+                (30.32369, -97.60266),
+                (30.32369, -97.60000),
+            ])],
+        );
+        assert_eq!(
+            result.err(),
+            Some(PathfinderError::ObstacleOutsideFlyzone { obstacle: 0 })
+        );
+    }
+
+    #[test]
+    fn waypoint_outside_flyzone_test() {
+        let pathfinder = Pathfinder::new(
+            Tanstar::new(),
+            TConfig::default(),
+            square_flyzone(&corner()),
+            Vec::new(),
+        );
+        let mut waypoints = LinkedList::new();
+        waypoints.push_back(Waypoint::<()>::from_degrees(
+            30.32269, -97.60266, 100f32, 10f32,
+        ));
+        assert!(pathfinder.validate_waypoints(&waypoints).is_ok());
+        waypoints.push_back(Waypoint::from_degrees(30.31269, -97.60266, 100f32, 10f32));
+        assert_eq!(
+            pathfinder.validate_waypoints(&waypoints),
+            Err(PathfinderError::WaypointOutsideFlyzone { waypoint: 1 })
+        );
+    }
+
+    #[test]
+    fn flyzone_altitudes_test() {
+        let boundary = square_flyzone(&corner()).remove(0).boundary;
+        let result = Pathfinder::try_new(
+            Tanstar::new(),
+            TConfig::default(),
+            vec![Flyzone::new(boundary.clone(), 120f32, 30f32)],
+            Vec::new(),
+        );
+        assert_eq!(
+            result.err(),
+            Some(PathfinderError::InvalidAltitudes { flyzone: 0 })
+        );
+
+        let pathfinder = Pathfinder::new(
+            Tanstar::new(),
+            TConfig::default(),
+            vec![Flyzone::new(boundary, 30f32, 120f32)],
+            Vec::new(),
+        );
+        let mut waypoints = LinkedList::new();
+        waypoints.push_back(Waypoint::<()>::from_degrees(
+            30.32269, -97.60266, 100f32, 10f32,
+        ));
+        assert!(pathfinder.validate_waypoints(&waypoints).is_ok());
+        waypoints.push_back(Waypoint::from_degrees(30.32269, -97.60366, 150f32, 10f32));
+        assert_eq!(
+            pathfinder.validate_waypoints(&waypoints),
+            Err(PathfinderError::WaypointAltitudeOutsideFlyzone { waypoint: 1 })
+        );
+    }
+
+    #[test]
+    fn adjust_path_failure_test() {
+        let obstacles = vec![Obstacle::from_degrees(30.32269, -97.60266, 20f32, 50f32)];
+        let mut pathfinder = Pathfinder::new(
+            Tanstar::new(),
+            TConfig::default(),
+            square_flyzone(&corner()),
+            obstacles,
+        );
+        let plane = Plane::from_degrees(30.32169, -97.60366, 20f32);
+
+        let mut waypoints = LinkedList::new();
+        waypoints.push_back(Waypoint::<()>::from_degrees(
+            30.32369, -97.60166, 20f32, 10f32,
+        ));
+        waypoints.push_back(Waypoint::from_degrees(30.33369, -97.60166, 20f32, 10f32));
+        let failure = pathfinder.get_adjust_path(plane, waypoints).unwrap_err();
+        assert_eq!(failure.index, 1);
+        assert_eq!(failure.reason, PathError::GoalOutsideFlyzone);
+        assert!(!failure.path.is_empty());
+
+        let mut waypoints = LinkedList::new();
+        waypoints.push_back(Waypoint::<()>::from_degrees(
+            30.32269, -97.60266, 20f32, 10f32,
+        ));
+        let failure = pathfinder.get_adjust_path(plane, waypoints).unwrap_err();
+        assert_eq!(failure.index, 0);
+        assert_eq!(
+            failure.reason,
+            PathError::GoalInsideObstacle { obstacle: 0 }
+        );
+        assert!(failure.path.is_empty());
+
+        let mut waypoints = LinkedList::new();
+        waypoints.push_back(Waypoint::<()>::from_degrees(
+            30.32369, -97.60166, 20f32, 10f32,
+        ));
+        let plane = Plane::from_degrees(30.32269, -97.60266, 20f32);
+        let failure = pathfinder.get_adjust_path(plane, waypoints).unwrap_err();
+        assert_eq!(
+            failure.reason,
+            PathError::StartInsideObstacle { obstacle: 0 }
+        );
+    }
+
+    #[test]
+    fn multi_leg_test() {
+        let obstacles = vec![Obstacle::from_degrees(30.32269, -97.60266, 50f32, 50f32)];
+        let mut pathfinder = Pathfinder::new(
+            Tanstar::new(),
+            TConfig::default(),
+            square_flyzone(&corner()),
+            obstacles,
+        );
+        let mut plane = Plane::from_degrees(30.32169, -97.60366, 20f32);
+        plane.yaw = 45f32;
+
+        // Search pattern crossing the obstacle on every leg
+        let corners = [
+            (30.32369, -97.60166),
+            (30.32169, -97.60366),
+            (30.32369, -97.60366),
+            (30.32169, -97.60166),
+            (30.32369, -97.60166),
+            (30.32169, -97.60366),
+        ];
+        let waypoints: LinkedList<Waypoint<usize>> = corners
+            .iter()
+            .enumerate()
+            .map(|(i, &(lat, lon))| {
+                Waypoint::new_with_data(Location::from_degrees(lat, lon, 20f32), 10f32, i)
+            })
+            .collect();
+
+        // Legs adjusted one after the other, each starting at the previous waypoint
+        let mut expected = LinkedList::new();
+        let mut departure = 0f32;
+        for (i, wp) in waypoints.iter().enumerate() {
+            let (mut path, arrival) = if i == 0 {
+                pathfinder
+                    .algo
+                    .adjust_path_from(&plane, wp.location, departure)
+            } else {
+                let start = Location::from_degrees(corners[i - 1].0, corners[i - 1].1, 20f32);
+                pathfinder
+                    .algo
+                    .adjust_path_at(start, wp.location, departure)
+            }
+            .unwrap();
+            departure = arrival;
+            expected.append(&mut path);
+            expected.push_back(wp.clone());
+        }
+
+        let path = pathfinder.get_adjust_path(plane, waypoints).unwrap();
+        assert!(path.len() > corners.len());
+        assert_eq!(path, expected);
+    }
 }
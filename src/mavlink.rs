@@ -0,0 +1,520 @@
+// mavlink.rs
+// Conversion between adjusted paths and MAVLink missions
+// Supports MISSION_ITEM_INT sequences, QGroundControl .waypoints (QGC WPL 110) and .plan files
+
+use error::MavlinkError;
+use obj::{Location, Waypoint};
+use std::collections::LinkedList;
+use std::fmt::Write;
+
+// MAV_CMD_NAV_WAYPOINT
+pub const MAV_CMD_NAV_WAYPOINT: u16 = 16;
+
+// MAV_FRAME values
+const MAV_FRAME_GLOBAL: u8 = 0;
+const MAV_FRAME_GLOBAL_RELATIVE_ALT: u8 = 3;
+const MAV_FRAME_GLOBAL_INT: u8 = 5;
+const MAV_FRAME_GLOBAL_RELATIVE_ALT_INT: u8 = 6;
+
+const WPL_HEADER: &str = "QGC WPL 110";
+
+// Reference of waypoint altitudes
+#[derive(Clone, Copy, Debug, PartialEq)]
+pub enum AltitudeFrame {
+    // Above mean sea level
+    Amsl,
+    // Relative to the home position
+    Relative,
+}
+
+impl AltitudeFrame {
+    // MAV_FRAME used by MISSION_ITEM_INT
+    pub fn int_frame(self) -> u8 {
+        match self {
+            AltitudeFrame::Amsl => MAV_FRAME_GLOBAL_INT,
+            AltitudeFrame::Relative => MAV_FRAME_GLOBAL_RELATIVE_ALT_INT,
+        }
+    }
+
+    // MAV_FRAME used by MISSION_ITEM and the QGroundControl file formats
+    pub fn frame(self) -> u8 {
+        match self {
+            AltitudeFrame::Amsl => MAV_FRAME_GLOBAL,
+            AltitudeFrame::Relative => MAV_FRAME_GLOBAL_RELATIVE_ALT,
+        }
+    }
+
+    pub fn from_frame(frame: u8) -> Option<Self> {
+        match frame {
+            MAV_FRAME_GLOBAL | MAV_FRAME_GLOBAL_INT => Some(AltitudeFrame::Amsl),
+            MAV_FRAME_GLOBAL_RELATIVE_ALT | MAV_FRAME_GLOBAL_RELATIVE_ALT_INT => {
+                Some(AltitudeFrame::Relative)
+            }
+            _ => None,
+        }
+    }
+}
+
+// Frame shared by the waypoints read so far, items switching frame are rejected
+fn merge_frame(
+    frame: &mut Option<AltitudeFrame>,
+    seq: u16,
+    item_frame: u8,
+) -> Result<(), MavlinkError> {
+    let item_frame = match AltitudeFrame::from_frame(item_frame) {
+        Some(item_frame) => item_frame,
+        None => {
+            return Err(MavlinkError::UnsupportedFrame {
+                seq,
+                frame: item_frame,
+            })
+        }
+    };
+    match *frame {
+        Some(frame) if frame != item_frame => Err(MavlinkError::MixedFrames { seq }),
+        _ => {
+            *frame = Some(item_frame);
+            Ok(())
+        }
+    }
+}
+
+// Fields of a MISSION_ITEM_INT message, excluding target system and component
+#[derive(Clone, Copy, Debug, PartialEq)]
+pub struct MissionItemInt {
+    pub seq: u16,
+    pub frame: u8,
+    pub command: u16,
+    pub current: u8,
+    pub autocontinue: u8,
+    pub param1: f32, // hold time, in seconds
+    pub param2: f32, // acceptance radius, in meters
+    pub param3: f32, // pass radius, in meters
+    pub param4: f32, // yaw, in degrees
+    pub x: i32,      // latitude, in degrees * 1e7
+    pub y: i32,      // longitude, in degrees * 1e7
+    pub z: f32,      // altitude, in meters
+}
+
+impl MissionItemInt {
+    // NAV_WAYPOINT at the waypoint, accepted within the waypoint radius
+    pub fn nav_waypoint<T>(seq: u16, waypoint: &Waypoint<T>, frame: AltitudeFrame) -> Self {
+        Self {
+            seq,
+            frame: frame.int_frame(),
+            command: MAV_CMD_NAV_WAYPOINT,
+            current: 0,
+            autocontinue: 1,
+            param1: 0f32,
+            param2: waypoint.radius,
+            param3: 0f32,
+            param4: 0f32,
+            x: (waypoint.location.lat_degree() * 1e7).round() as i32,
+            y: (waypoint.location.lon_degree() * 1e7).round() as i32,
+            z: waypoint.location.alt(),
+        }
+    }
+
+    pub fn location(&self) -> Location {
+        Location::from_degrees(f64::from(self.x) / 1e7, f64::from(self.y) / 1e7, self.z)
+    }
+}
+
+// Convert a path into NAV_WAYPOINT items numbered from zero
+pub fn to_mission_items<T>(
+    path: &LinkedList<Waypoint<T>>,
+    frame: AltitudeFrame,
+) -> Vec<MissionItemInt> {
+    path.iter()
+        .enumerate()
+        .map(|(seq, wp)| {
+            let mut item = MissionItemInt::nav_waypoint(seq as u16, wp, frame);
+            if seq == 0 {
+                item.current = 1;
+            }
+            item
+        })
+        .collect()
+}
+
+// Convert NAV_WAYPOINT items back into waypoints and the frame of their altitudes, other
+// commands are skipped
+// Waypoints must share a frame, which is AMSL when there are none
+pub fn from_mission_items<T>(
+    items: &[MissionItemInt],
+) -> Result<(LinkedList<Waypoint<T>>, AltitudeFrame), MavlinkError> {
+    let mut path = LinkedList::new();
+    let mut frame = None;
+    for item in items {
+        if item.command != MAV_CMD_NAV_WAYPOINT {
+            continue;
+        }
+        merge_frame(&mut frame, item.seq, item.frame)?;
+        path.push_back(Waypoint::new(item.location(), item.param2));
+    }
+    Ok((path, frame.unwrap_or(AltitudeFrame::Amsl)))
+}
+
+// Write home and path as a QGroundControl waypoints file
+// Home is item 0 in the AMSL frame, the path follows in the given frame
+pub fn to_waypoints_file<T>(
+    home: &Location,
+    path: &LinkedList<Waypoint<T>>,
+    frame: AltitudeFrame,
+) -> String {
+    let mut out = String::new();
+    let _ = writeln!(out, "{}", WPL_HEADER);
+    let home = Waypoint::<T>::new(*home, 0f32);
+    let mut home = MissionItemInt::nav_waypoint(0, &home, AltitudeFrame::Amsl);
+    home.current = 1;
+    write_wpl_item(&mut out, &home, AltitudeFrame::Amsl);
+    for (i, wp) in path.iter().enumerate() {
+        let item = MissionItemInt::nav_waypoint(i as u16 + 1, wp, frame);
+        write_wpl_item(&mut out, &item, frame);
+    }
+    out
+}
+
+fn write_wpl_item(out: &mut String, item: &MissionItemInt, frame: AltitudeFrame) {
+    let _ = writeln!(
+        out,
+        "{}\t{}\t{}\t{}\t{}\t{}\t{}\t{}\t{:.7}\t{:.7}\t{}\t{}",
+        item.seq,
+        item.current,
+        frame.frame(),
+        item.command,
+        item.param1,
+        item.param2,
+        item.param3,
+        item.param4,
+        f64::from(item.x) / 1e7,
+        f64::from(item.y) / 1e7,
+        item.z,
+        item.autocontinue
+    );
+}
+
+// Parse a QGroundControl waypoints file into its home, NAV_WAYPOINT items and their frame
+pub fn from_waypoints_file<T>(
+    text: &str,
+) -> Result<(Location, LinkedList<Waypoint<T>>, AltitudeFrame), MavlinkError> {
+    let mut lines = text
+        .lines()
+        .enumerate()
+        .filter(|(_, l)| !l.trim().is_empty());
+    match lines.next() {
+        Some((_, header)) if header.trim() == WPL_HEADER => (),
+        _ => return Err(MavlinkError::InvalidHeader),
+    }
+
+    let mut items = Vec::new();
+    for (i, line) in lines {
+        items.push(parse_wpl_item(line).ok_or(MavlinkError::InvalidItem { line: i + 1 })?);
+    }
+    if items.is_empty() {
+        return Err(MavlinkError::MissingHome);
+    }
+    let home = items.remove(0).location();
+    let (path, frame) = from_mission_items(&items)?;
+    Ok((home, path, frame))
+}
+
+fn parse_wpl_item(line: &str) -> Option<MissionItemInt> {
+    let fields: Vec<&str> = line.split_whitespace().collect();
+    if fields.len() != 12 {
+        return None;
+    }
+    let float = |i: usize| fields[i].parse::<f64>().ok();
+    Some(MissionItemInt {
+        seq: fields[0].parse().ok()?,
+        current: fields[1].parse().ok()?,
+        frame: fields[2].parse().ok()?,
+        command: fields[3].parse().ok()?,
+        param1: float(4)? as f32,
+        param2: float(5)? as f32,
+        param3: float(6)? as f32,
+        param4: float(7)? as f32,
+        x: (float(8)? * 1e7).round() as i32,
+        y: (float(9)? * 1e7).round() as i32,
+        z: float(10)? as f32,
+        autocontinue: fields[11].parse().ok()?,
+    })
+}
+
+#[cfg(feature = "serde")]
+pub use self::plan::*;
+
+// QGroundControl .plan json
+#[cfg(feature = "serde")]
+mod plan {
+    use super::*;
+    use serde_json::Value;
+
+    // MAV_AUTOPILOT_GENERIC and MAV_TYPE_FIXED_WING
+    const FIRMWARE_TYPE: u8 = 0;
+    const VEHICLE_TYPE: u8 = 1;
+
+    #[derive(Serialize, Deserialize)]
+    #[serde(rename_all = "camelCase")]
+    struct PlanFile {
+        file_type: String,
+        version: u32,
+        ground_station: String,
+        mission: PlanMission,
+        #[serde(default)]
+        geo_fence: Value,
+        #[serde(default)]
+        rally_points: Value,
+    }
+
+    #[derive(Serialize, Deserialize)]
+    #[serde(rename_all = "camelCase")]
+    struct PlanMission {
+        version: u32,
+        firmware_type: u8,
+        vehicle_type: u8,
+        #[serde(default)]
+        cruise_speed: f32,
+        #[serde(default)]
+        hover_speed: f32,
+        planned_home_position: Option<[f64; 3]>,
+        items: Vec<Value>,
+    }
+
+    #[derive(Serialize, Deserialize)]
+    #[serde(rename_all = "camelCase")]
+    struct SimpleItem {
+        #[serde(rename = "type")]
+        kind: String,
+        auto_continue: bool,
+        command: u16,
+        do_jump_id: u16,
+        frame: u8,
+        // hold, acceptance radius, pass radius, yaw, latitude, longitude, altitude
+        params: [Option<f64>; 7],
+    }
+
+    // Write home and path as a QGroundControl plan
+    pub fn to_plan<T>(
+        home: &Location,
+        path: &LinkedList<Waypoint<T>>,
+        frame: AltitudeFrame,
+    ) -> String {
+        let items = path
+            .iter()
+            .enumerate()
+            .map(|(i, wp)| {
+                let item = SimpleItem {
+                    kind: "SimpleItem".to_string(),
+                    auto_continue: true,
+                    command: MAV_CMD_NAV_WAYPOINT,
+                    do_jump_id: i as u16 + 1,
+                    frame: frame.frame(),
+                    params: [
+                        Some(0f64),
+                        Some(f64::from(wp.radius)),
+                        Some(0f64),
+                        None,
+                        Some(wp.location.lat_degree()),
+                        Some(wp.location.lon_degree()),
+                        Some(f64::from(wp.location.alt())),
+                    ],
+                };
+                serde_json::to_value(item).unwrap()
+            })
+            .collect();
+        let plan = PlanFile {
+            file_type: "Plan".to_string(),
+            version: 1,
+            ground_station: "QGroundControl".to_string(),
+            mission: PlanMission {
+                version: 2,
+                firmware_type: FIRMWARE_TYPE,
+                vehicle_type: VEHICLE_TYPE,
+                cruise_speed: 0f32,
+                hover_speed: 0f32,
+                planned_home_position: Some([
+                    home.lat_degree(),
+                    home.lon_degree(),
+                    f64::from(home.alt()),
+                ]),
+                items,
+            },
+            geo_fence: json!({ "circles": [], "polygons": [], "version": 2 }),
+            rally_points: json!({ "points": [], "version": 2 }),
+        };
+        serde_json::to_string_pretty(&plan).unwrap()
+    }
+
+    // Parse a QGroundControl plan into its home, NAV_WAYPOINT items and their frame
+    // Complex items such as surveys are skipped
+    pub fn from_plan<T>(
+        text: &str,
+    ) -> Result<(Location, LinkedList<Waypoint<T>>, AltitudeFrame), MavlinkError> {
+        let plan: PlanFile = serde_json::from_str(text)?;
+        let home = match plan.mission.planned_home_position {
+            Some([lat, lon, alt]) => Location::from_degrees(lat, lon, alt as f32),
+            None => return Err(MavlinkError::MissingHome),
+        };
+
+        let mut path = LinkedList::new();
+        let mut frame = None;
+        for item in plan.mission.items {
+            if item["type"] != "SimpleItem" {
+                continue;
+            }
+            let item: SimpleItem = serde_json::from_value(item)?;
+            if item.command != MAV_CMD_NAV_WAYPOINT {
+                continue;
+            }
+            merge_frame(&mut frame, item.do_jump_id, item.frame)?;
+            let param = |i: usize| item.params[i].unwrap_or(0f64);
+            let location = Location::from_degrees(param(4), param(5), param(6) as f32);
+            path.push_back(Waypoint::new(location, param(1) as f32));
+        }
+        Ok((home, path, frame.unwrap_or(AltitudeFrame::Amsl)))
+    }
+}
+
+#[cfg(test)]
+mod test {
+    use super::*;
+
+    fn home() -> Location {
+        Location::from_degrees(30.2881757, -97.7354343, 150f32)
+    }
+
+    fn path() -> LinkedList<Waypoint<()>> {
+        let mut path = LinkedList::new();
+        path.push_back(Waypoint::from_degrees(
+            30.2871818,
+            -97.7267150,
+            76f32,
+            10f32,
+        ));
+        path.push_back(Waypoint::from_degrees(30.2835845, -97.7312011, 80.5, 5f32));
+        path
+    }
+
+    fn assert_path_eq(a: &LinkedList<Waypoint<()>>, b: &LinkedList<Waypoint<()>>) {
+        assert_eq!(a.len(), b.len());
+        for (wa, wb) in a.iter().zip(b) {
+            assert!((wa.location.lat_degree() - wb.location.lat_degree()).abs() < 1e-7);
+            assert!((wa.location.lon_degree() - wb.location.lon_degree()).abs() < 1e-7);
+            assert_eq!(wa.location.alt(), wb.location.alt());
+            assert_eq!(wa.radius, wb.radius);
+        }
+    }
+
+    #[test]
+    fn mission_items_test() {
+        let items = to_mission_items(&path(), AltitudeFrame::Relative);
+        assert_eq!(items.len(), 2);
+        assert_eq!(items[0].current, 1);
+        assert_eq!(items[1].seq, 1);
+        assert_eq!(items[1].frame, MAV_FRAME_GLOBAL_RELATIVE_ALT_INT);
+        assert_eq!(items[1].command, MAV_CMD_NAV_WAYPOINT);
+        assert_eq!(items[1].param2, 5f32);
+        assert_eq!(items[1].x, 302_835_845);
+        let (parsed, frame) = from_mission_items(&items).unwrap();
+        assert_path_eq(&parsed, &path());
+        assert_eq!(frame, AltitudeFrame::Relative);
+
+        let mut items = to_mission_items(&path(), AltitudeFrame::Amsl);
+        assert_eq!(items[0].frame, MAV_FRAME_GLOBAL_INT);
+        assert_eq!(
+            from_mission_items::<()>(&items).unwrap().1,
+            AltitudeFrame::Amsl
+        );
+        items[1].frame = MAV_FRAME_GLOBAL_RELATIVE_ALT_INT;
+        assert!(matches!(
+            from_mission_items::<()>(&items),
+            Err(MavlinkError::MixedFrames { seq: 1 })
+        ));
+        items[1].frame = 1;
+        assert!(matches!(
+            from_mission_items::<()>(&items),
+            Err(MavlinkError::UnsupportedFrame { seq: 1, frame: 1 })
+        ));
+    }
+
+    #[test]
+    fn waypoints_file_test() {
+        let text = to_waypoints_file(&home(), &path(), AltitudeFrame::Relative);
+        let mut lines = text.lines();
+        assert_eq!(lines.next(), Some(WPL_HEADER));
+        assert!(lines.next().unwrap().starts_with("0\t1\t0\t16\t"));
+        assert!(lines.next().unwrap().starts_with("1\t0\t3\t16\t0\t10\t"));
+
+        let (parsed_home, parsed, frame) = from_waypoints_file(&text).unwrap();
+        assert!((parsed_home.lat_degree() - home().lat_degree()).abs() < 1e-7);
+        assert_eq!(parsed_home.alt(), 150f32);
+        assert_path_eq(&parsed, &path());
+        assert_eq!(frame, AltitudeFrame::Relative);
+    }
+
+    // Altitudes come back in the frame they were written in, relative ones not offset by home
+    #[test]
+    fn altitude_frame_round_trip_test() {
+        for &frame in &[AltitudeFrame::Relative, AltitudeFrame::Amsl] {
+            let text = to_waypoints_file(&home(), &path(), frame);
+            let (parsed_home, parsed, parsed_frame) = from_waypoints_file(&text).unwrap();
+            assert_eq!(parsed_home.alt(), home().alt());
+            assert_path_eq(&parsed, &path());
+            assert_eq!(parsed_frame, frame);
+
+            #[cfg(feature = "serde")]
+            {
+                let text = to_plan(&home(), &path(), frame);
+                let (parsed_home, parsed, parsed_frame) = from_plan(&text).unwrap();
+                assert_eq!(parsed_home.alt(), home().alt());
+                assert_path_eq(&parsed, &path());
+                assert_eq!(parsed_frame, frame);
+            }
+        }
+        assert_eq!(
+            from_waypoints_file::<()>("QGC WPL 110\n0\t1\t0\t16\t0\t0\t0\t0\t30\t-97\t150\t1\n")
+                .unwrap()
+                .2,
+            AltitudeFrame::Amsl
+        );
+    }
+
+    #[test]
+    fn waypoints_file_errors_test() {
+        assert!(matches!(
+            from_waypoints_file::<()>("QGC WPL 120\n"),
+            Err(MavlinkError::InvalidHeader)
+        ));
+        assert!(matches!(
+            from_waypoints_file::<()>("QGC WPL 110\n"),
+            Err(MavlinkError::MissingHome)
+        ));
+        assert!(matches!(
+            from_waypoints_file::<()>("QGC WPL 110\n0\t1\t0\t16\t0\t0\n"),
+            Err(MavlinkError::InvalidItem { line: 2 })
+        ));
+    }
+
+    #[cfg(feature = "serde")]
+    #[test]
+    fn plan_test() {
+        let text = to_plan(&home(), &path(), AltitudeFrame::Relative);
+        let plan: serde_json::Value = serde_json::from_str(&text).unwrap();
+        assert_eq!(plan["fileType"], "Plan");
+        assert_eq!(plan["mission"]["items"][0]["frame"], 3);
+        assert_eq!(
+            plan["mission"]["items"][0]["params"][3],
+            serde_json::Value::Null
+        );
+
+        let (parsed_home, parsed, frame) = from_plan(&text).unwrap();
+        assert_eq!(parsed_home.alt(), 150f32);
+        assert_path_eq(&parsed, &path());
+        assert_eq!(frame, AltitudeFrame::Relative);
+        assert!(matches!(
+            from_plan::<()>("{}"),
+            Err(MavlinkError::InvalidPlan(_))
+        ));
+    }
+}
@@ -0,0 +1,259 @@
+// mission.rs
+// Mission document bundling everything needed to plan a path
+
+use super::{Pathfinder, PathfinderError};
+use obj::*;
+use std::collections::LinkedList;
+use tanstar::{TConfig, Tanstar};
+
+#[cfg(feature = "serde")]
+use error::MissionError;
+#[cfg(feature = "serde")]
+use serde::de::DeserializeOwned;
+#[cfg(feature = "serde")]
+use serde::Serialize;
+#[cfg(feature = "serde")]
+use std::fs;
+#[cfg(feature = "serde")]
+use std::path::Path;
+
+#[derive(Clone, Debug, PartialEq)]
+#[cfg_attr(feature = "serde", derive(Serialize, Deserialize))]
+pub struct Mission<T = ()> {
+    // Plain boundaries are read as flyzones without altitude limits
+    pub flyzones: Vec<Flyzone>,
+    #[cfg_attr(feature = "serde", serde(default))]
+    pub obstacles: Vec<Obstacle>,
+    pub plane: Plane,
+    pub waypoints: Vec<Waypoint<T>>,
+    #[cfg_attr(feature = "serde", serde(default))]
+    pub config: TConfig,
+}
+
+impl<T> Mission<T> {
+    pub fn new<F: Into<Flyzone>>(
+        flyzones: Vec<F>,
+        obstacles: Vec<Obstacle>,
+        plane: Plane,
+        waypoints: Vec<Waypoint<T>>,
+    ) -> Self {
+        Self {
+            flyzones: flyzones.into_iter().map(Into::into).collect(),
+            obstacles,
+            plane,
+            waypoints,
+            config: TConfig::default(),
+        }
+    }
+
+    // Create a Tan* pathfinder for the flyzones and obstacles of the mission
+    pub fn pathfinder(&self) -> Result<Pathfinder<Tanstar>, PathfinderError> {
+        Pathfinder::try_new(
+            Tanstar::new(),
+            self.config.clone(),
+            self.flyzones.clone(),
+            self.obstacles.clone(),
+        )
+    }
+}
+
+impl<T: Clone> Mission<T> {
+    // Waypoints in the form expected by get_adjust_path
+    pub fn waypoint_list(&self) -> LinkedList<Waypoint<T>> {
+        self.waypoints.iter().cloned().collect()
+    }
+}
+
+#[cfg(feature = "serde")]
+impl<T: Serialize + DeserializeOwned> Mission<T> {
+    pub fn from_json(json: &str) -> Result<Self, MissionError> {
+        Ok(::serde_json::from_str(json)?)
+    }
+
+    pub fn to_json(&self) -> Result<String, MissionError> {
+        Ok(::serde_json::to_string_pretty(self)?)
+    }
+
+    pub fn from_toml(toml: &str) -> Result<Self, MissionError> {
+        Ok(::toml::from_str(toml)?)
+    }
+
+    pub fn to_toml(&self) -> Result<String, MissionError> {
+        Ok(::toml::to_string_pretty(self)?)
+    }
+
+    // Load mission from a file, read as TOML if the extension is .toml and JSON otherwise
+    pub fn load<P: AsRef<Path>>(path: P) -> Result<Self, MissionError> {
+        let contents = fs::read_to_string(&path)?;
+        if is_toml(path.as_ref()) {
+            Self::from_toml(&contents)
+        } else {
+            Self::from_json(&contents)
+        }
+    }
+
+    // Save mission to a file, written as TOML if the extension is .toml and JSON otherwise
+    pub fn save<P: AsRef<Path>>(&self, path: P) -> Result<(), MissionError> {
+        let contents = if is_toml(path.as_ref()) {
+            self.to_toml()?
+        } else {
+            self.to_json()?
+        };
+        fs::write(path, contents)?;
+        Ok(())
+    }
+}
+
+#[cfg(feature = "serde")]
+fn is_toml(path: &Path) -> bool {
+    match path.extension() {
+        Some(ext) => ext == "toml",
+        None => false,
+    }
+}
+
+#[cfg(all(test, feature = "serde"))]
+mod test {
+    use super::*;
+    use std::time::Duration;
+
+    fn assert_location_eq(a: &Location, b: &Location) {
+        assert!((a.lat_degree() - b.lat_degree()).abs() < 1e-9);
+        assert!((a.lon_degree() - b.lon_degree()).abs() < 1e-9);
+        assert_eq!(a.alt(), b.alt());
+    }
+
+    fn assert_mission_eq(a: &Mission<u32>, b: &Mission<u32>) {
+        for (fa, fb) in a.flyzones.iter().zip(&b.flyzones) {
+            for (la, lb) in fa.boundary.iter().zip(&fb.boundary) {
+                assert_location_eq(la, lb);
+            }
+            assert_eq!((fa.min_alt, fa.max_alt), (fb.min_alt, fb.max_alt));
+            assert_eq!(fa.keep_outs.len(), fb.keep_outs.len());
+            for (ka, kb) in fa.keep_outs.iter().zip(&fb.keep_outs) {
+                for (la, lb) in ka.iter().zip(kb) {
+                    assert_location_eq(la, lb);
+                }
+            }
+        }
+        for (oa, ob) in a.obstacles.iter().zip(&b.obstacles) {
+            assert_location_eq(&oa.location, &ob.location);
+            assert_eq!((oa.radius, oa.height), (ob.radius, ob.height));
+            match (&oa.shape, &ob.shape) {
+                (Shape::Cylinder, Shape::Cylinder) => (),
+                (Shape::Prism(va), Shape::Prism(vb)) => {
+                    for (la, lb) in va.iter().zip(vb) {
+                        assert_location_eq(la, lb);
+                    }
+                }
+                _ => panic!("obstacle shape changed"),
+            }
+            assert_eq!(oa.motion, ob.motion);
+        }
+        for (wa, wb) in a.waypoints.iter().zip(&b.waypoints) {
+            assert_location_eq(&wa.location, &wb.location);
+            assert_eq!((wa.radius, wa.data), (wb.radius, wb.data));
+        }
+        assert_location_eq(&a.plane.location, &b.plane.location);
+        assert_eq!(a.plane.yaw, b.plane.yaw);
+        assert_eq!(a.config, b.config);
+    }
+
+    fn dummy_mission() -> Mission<u32> {
+        let mut mission = Mission::new(
+            vec![Flyzone::new(
+                vec![
+                    Location::from_degrees(30.32469, -97.60466, 0f32),
+                    Location::from_degrees(30.32469, -97.60066, 0f32),
+                    Location::from_degrees(30.32069, -97.60066, 0f32),
+                ],
+                10f32,
+                f32::INFINITY,
+            )
+            .keep_out(vec![
+                Location::from_degrees(30.32449, -97.60416, 0f32),
+                Location::from_degrees(30.32449, -97.60366, 0f32),
+                Location::from_degrees(30.32419, -97.60366, 0f32),
+            ])],
+            vec![
+                Obstacle::from_degrees(30.32369, -97.60266, 20f32, 50f32),
+                Obstacle::from_degrees(30.32269, -97.60366, 10f32, 60f32).velocity(2f32, -1f32),
+                Obstacle::prism(
+                    vec![
+                        Location::from_degrees(30.32169, -97.60166, 30f32),
+                        Location::from_degrees(30.32169, -97.60116, 30f32),
+                        Location::from_degrees(30.32119, -97.60116, 30f32),
+                    ],
+                    30f32,
+                ),
+            ],
+            Plane::from_degrees(30.32169, -97.60366, 20f32).yaw(90f32),
+            vec![
+                Waypoint::<()>::from_degrees(30.32369, -97.60166, 20f32, 10f32).add_data(7),
+                Waypoint::from_degrees(30.32269, -97.60366, 40f32, 5f32),
+            ],
+        );
+        mission.config.max_process_time = Duration::from_millis(2500);
+        mission.config.anytime = true;
+        mission
+    }
+
+    #[test]
+    fn json_round_trip() {
+        let mission = dummy_mission();
+        let json = mission.to_json().unwrap();
+        assert_mission_eq(&mission, &Mission::from_json(&json).unwrap());
+    }
+
+    #[test]
+    fn toml_round_trip() {
+        let mission = dummy_mission();
+        let toml = mission.to_toml().unwrap();
+        assert_mission_eq(&mission, &Mission::from_toml(&toml).unwrap());
+    }
+
+    #[test]
+    fn json_defaults() {
+        let json = r#"{
+            "flyzones": [[
+                {"lat": 30.32469, "lon": -97.60466},
+                {"lat": 30.32469, "lon": -97.60066},
+                {"lat": 30.32069, "lon": -97.60066}
+            ]],
+            "obstacles": [{
+                "height": 30,
+                "shape": {"prism": [
+                    {"lat": 30.32369, "lon": -97.60166},
+                    {"lat": 30.32369, "lon": -97.60116},
+                    {"lat": 30.32319, "lon": -97.60116}
+                ]}
+            }],
+            "plane": {"location": {"lat": 30.32169, "lon": -97.60366, "alt": 20}},
+            "waypoints": [{"location": {"lat": 30.32369, "lon": -97.60166, "alt": 20}, "radius": 10}],
+            "config": {"buffer_size": 4}
+        }"#;
+        let mission: Mission = Mission::from_json(json).unwrap();
+        assert_eq!(mission.flyzones[0].min_alt, f32::NEG_INFINITY);
+        assert_eq!(mission.flyzones[0].max_alt, f32::INFINITY);
+        assert!(mission.flyzones[0].keep_outs.is_empty());
+        // bounding circle of the prism is derived from its vertices
+        assert!(mission.obstacles[0].radius > 0f32);
+        assert!(mission.pathfinder().is_ok());
+        assert_eq!(mission.plane.yaw, -1f32);
+        assert_eq!(mission.waypoints[0].data, None);
+        assert_eq!(mission.config.buffer_size, 4f32);
+        let json = r#"{"flyzones": [{"boundary": [], "max_alt": 120}], "plane": {"location": {"lat": 0, "lon": 0}}, "waypoints": []}"#;
+        let mission = Mission::<()>::from_json(json).unwrap();
+        assert_eq!(mission.flyzones[0].min_alt, f32::NEG_INFINITY);
+        assert_eq!(mission.flyzones[0].max_alt, 120f32);
+        let json = r#"{"flyzones": [{"boundary": [], "keep_outs": [[{"lat": 0, "lon": 0}]]}], "plane": {"location": {"lat": 0, "lon": 0}}, "waypoints": []}"#;
+        let mission = Mission::<()>::from_json(json).unwrap();
+        assert_eq!(mission.flyzones[0].keep_outs[0].len(), 1);
+        let json = r#"{"flyzones": [], "obstacles": [{"height": 30}], "plane": {"location": {"lat": 0, "lon": 0}}, "waypoints": []}"#;
+        assert!(Mission::<()>::from_json(json).is_err());
+        assert_eq!(
+            mission.config.turning_radius,
+            TConfig::default().turning_radius
+        );
+    }
+}
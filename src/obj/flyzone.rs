@@ -0,0 +1,102 @@
+use super::Location;
+use std::f32;
+
+// Polygon the plane must stay inside, between a floor and a ceiling, and outside of its keep-outs
+#[derive(Clone, Debug, PartialEq)]
+#[cfg_attr(
+    feature = "serde",
+    derive(Serialize, Deserialize),
+    serde(from = "FlyzoneDef")
+)]
+pub struct Flyzone {
+    pub boundary: Vec<Location>,
+    // In meters, same reference as Location::alt, infinite when unbounded
+    #[cfg_attr(feature = "serde", serde(skip_serializing_if = "is_unbounded"))]
+    pub min_alt: f32,
+    #[cfg_attr(feature = "serde", serde(skip_serializing_if = "is_unbounded"))]
+    pub max_alt: f32,
+    // Polygons inside the boundary the plane must stay out of, such as a runway
+    #[cfg_attr(
+        feature = "serde",
+        serde(default, skip_serializing_if = "Vec::is_empty")
+    )]
+    pub keep_outs: Vec<Vec<Location>>,
+}
+
+// Serialized form of flyzone, a plain boundary is a flyzone without altitude limits
+#[cfg(feature = "serde")]
+#[derive(Deserialize)]
+#[serde(untagged)]
+enum FlyzoneDef {
+    Boundary(Vec<Location>),
+    Bounded {
+        boundary: Vec<Location>,
+        min_alt: Option<f32>,
+        max_alt: Option<f32>,
+        #[serde(default)]
+        keep_outs: Vec<Vec<Location>>,
+    },
+}
+
+#[cfg(feature = "serde")]
+impl From<FlyzoneDef> for Flyzone {
+    fn from(def: FlyzoneDef) -> Self {
+        match def {
+            FlyzoneDef::Boundary(boundary) => boundary.into(),
+            FlyzoneDef::Bounded {
+                boundary,
+                min_alt,
+                max_alt,
+                keep_outs,
+            } => Self {
+                keep_outs,
+                ..Self::new(
+                    boundary,
+                    min_alt.unwrap_or(f32::NEG_INFINITY),
+                    max_alt.unwrap_or(f32::INFINITY),
+                )
+            },
+        }
+    }
+}
+
+#[cfg(feature = "serde")]
+fn is_unbounded(alt: &f32) -> bool {
+    alt.is_infinite()
+}
+
+impl From<Vec<Location>> for Flyzone {
+    fn from(boundary: Vec<Location>) -> Self {
+        Self::new(boundary, f32::NEG_INFINITY, f32::INFINITY)
+    }
+}
+
+impl Flyzone {
+    pub fn new(boundary: Vec<Location>, min_alt: f32, max_alt: f32) -> Self {
+        Self {
+            boundary,
+            min_alt,
+            max_alt,
+            keep_outs: Vec::new(),
+        }
+    }
+
+    // Add a polygon the plane must stay out of
+    pub fn keep_out(mut self, polygon: Vec<Location>) -> Self {
+        self.keep_outs.push(polygon);
+        self
+    }
+
+    // Boundary followed by the keep-outs
+    pub fn polygons<'a>(&'a self) -> impl Iterator<Item = &'a Vec<Location>> + 'a {
+        Some(&self.boundary).into_iter().chain(&self.keep_outs)
+    }
+
+    pub fn contains_alt(&self, alt: f32) -> bool {
+        alt >= self.min_alt && alt <= self.max_alt
+    }
+
+    pub fn clamp_alt(&self, alt: f32) -> f32 {
+        alt.max(self.min_alt).min(self.max_alt)
+    }
+}
@@ -4,6 +4,11 @@ use self::ordered_float::*;
 use std::fmt;
 
 #[derive(Clone, Copy, Debug, Eq, PartialEq, Hash)]
+#[cfg_attr(
+    feature = "serde",
+    derive(Serialize, Deserialize),
+    serde(from = "Degrees", into = "Degrees")
+)]
 pub struct Location {
     lat: OrderedFloat<f64>,     //In radians
     lon: OrderedFloat<f64>,     //In radians
@@ -22,6 +27,34 @@ impl fmt::Display for Location {
     }
 }
 
+// Serialized form of location, in degrees
+#[cfg(feature = "serde")]
+#[derive(Serialize, Deserialize)]
+struct Degrees {
+    lat: f64,
+    lon: f64,
+    #[serde(default)]
+    alt: f32,
+}
+
+#[cfg(feature = "serde")]
+impl From<Degrees> for Location {
+    fn from(degrees: Degrees) -> Self {
+        Self::from_degrees(degrees.lat, degrees.lon, degrees.alt)
+    }
+}
+
+#[cfg(feature = "serde")]
+impl From<Location> for Degrees {
+    fn from(location: Location) -> Self {
+        Self {
+            lat: location.lat_degree(),
+            lon: location.lon_degree(),
+            alt: location.alt(),
+        }
+    }
+}
+
 impl Location {
     // Create location from coordinates in degrees
     pub fn from_degrees(lat: f64, lon: f64, alt: f32) -> Self {
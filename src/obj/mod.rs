@@ -1,9 +1,11 @@
+mod flyzone;
 mod location;
 mod obstacle;
 mod plane;
 mod waypoint;
 
+pub use self::flyzone::Flyzone;
 pub use self::location::Location;
-pub use self::obstacle::Obstacle;
+pub use self::obstacle::{Motion, Obstacle, Shape};
 pub use self::plane::Plane;
 pub use self::waypoint::Waypoint;
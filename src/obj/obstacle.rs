@@ -1,10 +1,91 @@
 use super::Location;
 
-#[derive(Debug, Clone)]
+// Mean radius of earth, used to approximate the bounding circle of prisms
+const RADIUS: f64 = 6_371_000.0;
+
+#[derive(Debug, Clone, Default, PartialEq)]
+#[cfg_attr(feature = "serde", derive(Serialize, Deserialize))]
+#[cfg_attr(feature = "serde", serde(rename_all = "lowercase"))]
+pub enum Shape {
+    // Cylinder of the obstacle radius around its location
+    #[default]
+    Cylinder,
+    // Prism extruded from a polygon up to the obstacle height
+    Prism(Vec<Location>),
+}
+
+#[derive(Debug, Clone, Default, PartialEq)]
+#[cfg_attr(feature = "serde", derive(Serialize, Deserialize))]
+#[cfg_attr(feature = "serde", serde(rename_all = "lowercase"))]
+pub enum Motion {
+    #[default]
+    Static,
+    // Constant velocity from the obstacle location, in meters per second
+    Velocity {
+        east: f32,
+        north: f32,
+    },
+    // Locations at seconds after planning starts, obstacle holds still before the first
+    // and after the last
+    Trajectory(Vec<(f32, Location)>),
+}
+
+impl Motion {
+    pub fn is_static(&self) -> bool {
+        *self == Motion::Static
+    }
+}
+
+#[derive(Debug, Clone, PartialEq)]
+#[cfg_attr(
+    feature = "serde",
+    derive(Serialize, Deserialize),
+    serde(try_from = "ObstacleDef")
+)]
 pub struct Obstacle {
     pub location: Location,
-    pub radius: f32, // In meters
+    pub radius: f32, // In meters, bounding circle of a prism
     pub height: f32, // In meters
+    pub shape: Shape,
+    // Moving obstacles are always avoided as a cylinder of the obstacle radius
+    #[cfg_attr(feature = "serde", serde(skip_serializing_if = "Motion::is_static"))]
+    pub motion: Motion,
+}
+
+// Serialized form of obstacle, location and radius can be left out for prisms
+#[cfg(feature = "serde")]
+#[derive(Deserialize)]
+struct ObstacleDef {
+    location: Option<Location>,
+    radius: Option<f32>,
+    height: f32,
+    #[serde(default)]
+    shape: Shape,
+    #[serde(default)]
+    motion: Motion,
+}
+
+#[cfg(feature = "serde")]
+impl ::std::convert::TryFrom<ObstacleDef> for Obstacle {
+    type Error = &'static str;
+
+    fn try_from(def: ObstacleDef) -> Result<Self, Self::Error> {
+        let obstacle = match (def.location, def.radius, def.shape) {
+            (Some(location), Some(radius), shape) => Self {
+                location,
+                radius,
+                height: def.height,
+                shape,
+                motion: Motion::Static,
+            },
+            (_, _, Shape::Prism(vertices)) => Self::prism(vertices, def.height),
+            _ => return Err("cylinder obstacle requires location and radius"),
+        };
+        Ok(Self {
+            motion: def.motion,
+            ..obstacle
+        })
+    }
 }
 
 // #TODO: swap radius and height to be consistent with waypoint
@@ -15,6 +96,8 @@ impl Obstacle {
             location,
             radius,
             height,
+            shape: Shape::Cylinder,
+            motion: Motion::Static,
         }
     }
     pub fn from_degrees(lon: f64, lat: f64, radius: f32, height: f32) -> Self {
@@ -24,4 +107,44 @@ impl Obstacle {
     pub fn from_radians(lon: f64, lat: f64, radius: f32, height: f32) -> Self {
         Self::new(Location::from_radians(lon, lat, height), radius, height)
     }
+
+    // Create prism from polygon vertices in order
+    // location and radius are set to a circle around the vertices
+    pub fn prism(vertices: Vec<Location>, height: f32) -> Self {
+        let count = vertices.len().max(1) as f64;
+        let lat = vertices.iter().map(|v| v.lat()).sum::<f64>() / count;
+        let lon = vertices.iter().map(|v| v.lon()).sum::<f64>() / count;
+        let center = Location::from_radians(lat, lon, height);
+        let radius = vertices
+            .iter()
+            .map(|v| {
+                let dx = RADIUS * lat.cos() * (v.lon() - lon);
+                let dy = RADIUS * (v.lat() - lat);
+                (dx * dx + dy * dy).sqrt() as f32
+            })
+            .fold(0f32, f32::max);
+        Self {
+            location: center,
+            radius,
+            height,
+            shape: Shape::Prism(vertices),
+            motion: Motion::Static,
+        }
+    }
+
+    // Move the obstacle with a constant velocity, in meters per second
+    pub fn velocity(mut self, east: f32, north: f32) -> Self {
+        self.motion = Motion::Velocity { east, north };
+        self
+    }
+
+    // Move the obstacle through locations at seconds after planning starts
+    pub fn trajectory(mut self, trajectory: Vec<(f32, Location)>) -> Self {
+        self.motion = Motion::Trajectory(trajectory);
+        self
+    }
+
+    pub fn is_moving(&self) -> bool {
+        !self.motion.is_static()
+    }
 }
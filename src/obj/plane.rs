@@ -2,15 +2,27 @@ use super::Location;
 
 // #TODO: standardize location name
 // #TODO: fully implement builder pattern for greater flexibility
-#[derive(Clone, Copy, Debug)]
+#[derive(Clone, Copy, Debug, PartialEq)]
+#[cfg_attr(feature = "serde", derive(Serialize, Deserialize))]
 pub struct Plane {
     pub location: Location,
-    pub yaw: f32,         // In degrees, -1 if not provided
-    pub pitch: f32,       // In degrees, -1 if not provided
-    pub roll: f32,        // In degrees, -1 if not provided
-    pub airspeed: f32,    // In meters per second, -1 if not provided
+    #[cfg_attr(feature = "serde", serde(default = "not_provided"))]
+    pub yaw: f32, // In degrees, -1 if not provided
+    #[cfg_attr(feature = "serde", serde(default = "not_provided"))]
+    pub pitch: f32, // In degrees, -1 if not provided
+    #[cfg_attr(feature = "serde", serde(default = "not_provided"))]
+    pub roll: f32, // In degrees, -1 if not provided
+    #[cfg_attr(feature = "serde", serde(default = "not_provided"))]
+    pub airspeed: f32, // In meters per second, -1 if not provided
+    #[cfg_attr(feature = "serde", serde(default = "not_provided"))]
     pub groundspeed: f32, // In meters per second, -1 if not provided
-    pub wind_dir: f32,    // In degrees, -1 if not provided
+    #[cfg_attr(feature = "serde", serde(default = "not_provided"))]
+    pub wind_dir: f32, // In degrees, -1 if not provided
+}
+
+#[cfg(feature = "serde")]
+fn not_provided() -> f32 {
+    -1f32
 }
 
 impl Plane {
@@ -1,9 +1,11 @@
 use super::Location;
 
-#[derive(Clone, Debug)]
+#[derive(Clone, Debug, PartialEq)]
+#[cfg_attr(feature = "serde", derive(Serialize, Deserialize))]
 pub struct Waypoint<T> {
     pub location: Location,
     pub radius: f32, // In meters
+    #[cfg_attr(feature = "serde", serde(skip_serializing_if = "Option::is_none"))]
     pub data: Option<T>,
 }
 
@@ -0,0 +1,4 @@
+// Config struct for phi*
+pub use gridplan::config::*;
+
+pub type PConfig = GridConfig;
@@ -0,0 +1,469 @@
+// mod.rs
+// Phi*, any-angle grid planner repairing its search tree as obstacles change
+use super::gridplan::{
+    generate_waypoint, grid_size, inside_obstacle, near_polygon, polygon_points, time_exceeded,
+};
+use super::obj::*;
+use super::tanstar::{intersect, intersect_polygon, perpendicular_intersect, Point, Tanstar};
+use super::Algorithm;
+use super::{PathError, PathfinderError};
+
+pub mod config;
+
+mod search;
+
+pub use self::config::*;
+
+use self::search::Search;
+use std::collections::LinkedList;
+use std::time::SystemTime;
+
+// Origin, number of cells along x and y, and the flyzone boundaries and keep-outs
+type Grid = (Location, (usize, usize), Vec<Vec<Point>>);
+
+pub struct Phistar {
+    // Configuration options
+    config: PConfig,
+    flyzones: Vec<Flyzone>,
+    obstacles: Vec<Obstacle>,
+    // private
+    start_time: SystemTime,
+    origin: Location,                // Lower left corner of the grid
+    size: (usize, usize),            // Number of cells along x and y
+    flyzone_points: Vec<Vec<Point>>, // Boundaries and keep-outs
+    search: Option<Search>,          // Kept between calls planning the same leg
+}
+
+impl Default for Phistar {
+    fn default() -> Self {
+        Self {
+            config: PConfig::default(),
+            flyzones: Vec::new(),
+            obstacles: Vec::new(),
+            start_time: SystemTime::now(),
+            origin: Location::from_degrees(0f64, 0f64, 0f32),
+            size: (0, 0),
+            flyzone_points: Vec::new(),
+            search: None,
+        }
+    }
+}
+
+impl Phistar {
+    pub fn new() -> Self {
+        Phistar::default()
+    }
+
+    // Number of nodes expanded by the search for the current leg
+    pub fn expanded(&self) -> usize {
+        match self.search {
+            Some(ref search) => search.expanded,
+            None => 0,
+        }
+    }
+
+    // Lay the grid over the bounding box of the flyzones without touching the current state
+    fn build_grid(config: &PConfig, flyzones: &[Flyzone]) -> Result<Grid, PathfinderError> {
+        let origin = Tanstar::find_origin(flyzones);
+        let flyzone_points: Vec<Vec<Point>> = flyzones
+            .iter()
+            .flat_map(|flyzone| flyzone.polygons())
+            .map(|polygon| polygon_points(polygon, &origin))
+            .collect();
+        let (max_x, max_y) = flyzone_points
+            .iter()
+            .flatten()
+            .fold((0f32, 0f32), |(x, y), p| (x.max(p.x), y.max(p.y)));
+        let size = grid_size(max_x, max_y, config.cell_size)?;
+        debug!(cols = size.0, rows = size.1, "built grid");
+        Ok((origin, size, flyzone_points))
+    }
+
+    // Swap in a new grid, dropping the search
+    fn set_grid(&mut self, (origin, size, flyzone_points): Grid) {
+        self.origin = origin;
+        self.size = size;
+        self.flyzone_points = flyzone_points;
+        self.search = None;
+    }
+
+    fn time_exceeded(&self) -> bool {
+        time_exceeded(&self.start_time, self.config.max_process_time)
+    }
+
+    // Whether segment ab stays inside the flyzones and clear of the buffered obstacles that are
+    // not below min_height
+    fn line_of_sight(&self, a: &Point, b: &Point, min_height: f32) -> bool {
+        for polygon in &self.flyzone_points {
+            let n = polygon.len();
+            if (0..n).any(|i| intersect(a, b, &polygon[i], &polygon[(i + 1) % n])) {
+                return false;
+            }
+        }
+        let buffer = self.config.buffer_size;
+        !self
+            .obstacles
+            .iter()
+            .filter(|obstacle| !obstacle.is_moving() && obstacle.height >= min_height)
+            .any(|obstacle| match obstacle.shape {
+                Shape::Cylinder => {
+                    let buffered =
+                        Obstacle::new(obstacle.location, obstacle.radius + buffer, obstacle.height);
+                    matches!(
+                        perpendicular_intersect(&self.origin, a, b, &buffered),
+                        (Some(_), Some(_))
+                    )
+                }
+                Shape::Prism(ref vertices) => {
+                    let polygon = polygon_points(vertices, &self.origin);
+                    intersect_polygon(a, b, &polygon) || near_polygon(a, b, &polygon, buffer)
+                }
+            })
+    }
+
+    fn inside_obstacle(&self, location: &Location) -> Option<usize> {
+        inside_obstacle(&self.obstacles, &self.origin, location)
+    }
+
+    // Buffered bounding box of an obstacle
+    fn footprint(&self, obstacle: &Obstacle) -> (Point, Point) {
+        let center = Point::from((&obstacle.location, &self.origin));
+        let reach = obstacle.radius + self.config.buffer_size + self.config.cell_size;
+        (
+            Point::new(center.x - reach, center.y - reach, 0f32),
+            Point::new(center.x + reach, center.y + reach, 0f32),
+        )
+    }
+
+    // Waypoints at the turns of the path, altitude set by distance flown
+    fn waypoints<T>(
+        &self,
+        points: &[Point],
+        start_alt: f32,
+        end_alt: f32,
+    ) -> LinkedList<Waypoint<T>> {
+        generate_waypoint(
+            &self.flyzones,
+            &self.origin,
+            points,
+            start_alt,
+            end_alt,
+            self.config.cell_size / 2f32,
+        )
+    }
+}
+
+impl Algorithm for Phistar {
+    type Config = PConfig;
+
+    fn init(
+        &mut self,
+        config: Self::Config,
+        flyzones: Vec<Flyzone>,
+        obstacles: Vec<Obstacle>,
+    ) -> Result<(), PathfinderError> {
+        config.validate()?;
+        Tanstar::validate_flyzones(&flyzones)?;
+        Tanstar::validate_obstacles(&flyzones, &obstacles)?;
+        let grid = Phistar::build_grid(&config, &flyzones)?;
+
+        self.config = config;
+        self.flyzones = flyzones;
+        self.obstacles = obstacles;
+        self.set_grid(grid);
+        Ok(())
+    }
+
+    // Find an any-angle path over the grid, reusing the previous search for the same leg
+    // Moving obstacles are ignored
+    fn adjust_path<T>(
+        &mut self,
+        start: Location,
+        end: Location,
+    ) -> Result<LinkedList<Waypoint<T>>, PathError> {
+        if let Some(obstacle) = self.inside_obstacle(&start) {
+            return Err(PathError::StartInsideObstacle { obstacle });
+        }
+        match self.altitude_band(&end) {
+            None => return Err(PathError::GoalOutsideFlyzone),
+            Some((min, max)) if end.alt() < min || end.alt() > max => {
+                return Err(PathError::GoalAltitudeOutsideFlyzone)
+            }
+            Some(_) => (),
+        }
+        if let Some(obstacle) = self.inside_obstacle(&end) {
+            return Err(PathError::GoalInsideObstacle { obstacle });
+        }
+
+        self.start_time = SystemTime::now();
+        let start_point = Point::from((&start, &self.origin));
+        let end_point = Point::from((&end, &self.origin));
+        let min_height = start.alt().min(end.alt());
+
+        let mut search = match self.search.take() {
+            Some(search)
+                if search.start.x == start_point.x
+                    && search.start.y == start_point.y
+                    && search.goal.x == end_point.x
+                    && search.goal.y == end_point.y
+                    && search.min_height == min_height =>
+            {
+                debug!("continuing previous search");
+                search
+            }
+            _ => Search::new(
+                self.config.cell_size,
+                self.size,
+                start_point,
+                end_point,
+                min_height,
+            ),
+        };
+        let result = search.compute(
+            &|a: &Point, b: &Point| self.line_of_sight(a, b, min_height),
+            &|| self.time_exceeded(),
+        );
+        let path = match result {
+            Ok(true) => match search.path() {
+                Some(points) => Ok(self.waypoints(&points, start.alt(), end.alt())),
+                None => Err(PathError::OpenSetExhausted),
+            },
+            Ok(false) => Err(PathError::OpenSetExhausted),
+            Err(e) => Err(e),
+        };
+        debug!(expanded = search.expanded, "searched grid");
+        self.search = Some(search);
+        path
+    }
+
+    fn in_flyzone(&self, location: &Location) -> bool {
+        Tanstar::inside_flyzones(&self.flyzones, &self.origin, location)
+    }
+
+    fn altitude_band(&self, location: &Location) -> Option<(f32, f32)> {
+        Tanstar::flyzone_altitudes(&self.flyzones, &self.origin, location)
+    }
+
+    fn get_config(&self) -> &Self::Config {
+        &self.config
+    }
+
+    fn get_flyzone(&mut self) -> &Vec<Flyzone> {
+        &self.flyzones
+    }
+
+    fn get_obstacles(&self) -> &Vec<Obstacle> {
+        &self.obstacles
+    }
+
+    fn set_config(&mut self, config: Self::Config) -> Result<(), PathfinderError> {
+        config.validate()?;
+        let grid = Phistar::build_grid(&config, &self.flyzones)?;
+        self.config = config;
+        self.set_grid(grid);
+        Ok(())
+    }
+
+    fn set_flyzone(&mut self, flyzone: Vec<Flyzone>) -> Result<(), PathfinderError> {
+        Tanstar::validate_flyzones(&flyzone)?;
+        Tanstar::validate_obstacles(&flyzone, &self.obstacles)?;
+        let grid = Phistar::build_grid(&self.config, &flyzone)?;
+        self.flyzones = flyzone;
+        self.set_grid(grid);
+        Ok(())
+    }
+
+    // Only the part of the search tree affected by added or removed obstacles is repaired
+    fn set_obstacles(&mut self, obstacles: Vec<Obstacle>) -> Result<(), PathfinderError> {
+        Tanstar::validate_obstacles(&self.flyzones, &obstacles)?;
+        let cleared: Vec<(Point, Point)> = self
+            .obstacles
+            .iter()
+            .filter(|obstacle| !obstacles.contains(obstacle))
+            .map(|obstacle| self.footprint(obstacle))
+            .collect();
+        self.obstacles = obstacles;
+        if let Some(mut search) = self.search.take() {
+            let min_height = search.min_height;
+            search.repair(
+                &|a: &Point, b: &Point| self.line_of_sight(a, b, min_height),
+                &cleared,
+            );
+            self.search = Some(search);
+        }
+        Ok(())
+    }
+}
+
+#[cfg(test)]
+mod test {
+    use super::*;
+    use fixture::{leg, square_flyzone, wall};
+    use std::time::Duration;
+
+    fn points(
+        start: &Location,
+        end: &Location,
+        path: &LinkedList<Waypoint<()>>,
+        origin: &Location,
+    ) -> Vec<Point> {
+        let mut points = vec![Point::from((start, origin))];
+        points.extend(path.iter().map(|wp| Point::from((&wp.location, origin))));
+        points.push(Point::from((end, origin)));
+        points
+    }
+
+    fn length(points: &[Point]) -> f32 {
+        points.windows(2).map(|p| p[0].distance(&p[1])).sum()
+    }
+
+    fn phistar(obstacles: Vec<Obstacle>) -> Phistar {
+        let origin = Location::from_degrees(30.32469, -97.60466, 0f32);
+        let mut phistar = Phistar::new();
+        phistar
+            .init(PConfig::default(), square_flyzone(&origin), obstacles)
+            .unwrap();
+        phistar
+    }
+
+    #[test]
+    fn phistar_path_test() {
+        let origin = Location::from_degrees(30.32469, -97.60466, 0f32);
+        let mut phistar = phistar(vec![wall(&origin, 50f32)]);
+        let origin = phistar.origin;
+        let (start, end) = leg(&origin);
+
+        let path = phistar.adjust_path::<()>(start, end).unwrap();
+        let route = points(&start, &end, &path, &origin);
+        for pair in route.windows(2) {
+            assert!(phistar.line_of_sight(&pair[0], &pair[1], 10f32));
+        }
+        // Any-angle turns hug the buffered wall instead of following grid directions
+        assert!(path.len() <= 4);
+        let distance = length(&route);
+        assert!(distance > 300f32 && distance < 340f32);
+
+        // Above the wall the route is straight
+        let high = Location::from_meters(50f32, 200f32, 60f32, &origin);
+        let over = Location::from_meters(350f32, 200f32, 60f32, &origin);
+        assert!(phistar.adjust_path::<()>(high, over).unwrap().is_empty());
+    }
+
+    #[test]
+    fn phistar_keep_out_test() {
+        let origin = Location::from_degrees(30.32469, -97.60466, 0f32);
+        let mut flyzones = square_flyzone(&origin);
+        flyzones[0].keep_outs.push(vec![
+            Location::from_meters(150f32, 100f32, 0f32, &origin),
+            Location::from_meters(150f32, 300f32, 0f32, &origin),
+            Location::from_meters(250f32, 300f32, 0f32, &origin),
+            Location::from_meters(250f32, 100f32, 0f32, &origin),
+        ]);
+        let mut phistar = Phistar::new();
+        phistar
+            .init(PConfig::default(), flyzones, Vec::new())
+            .unwrap();
+        let origin = phistar.origin;
+        let (start, end) = leg(&origin);
+
+        let path = phistar.adjust_path::<()>(start, end).unwrap();
+        let route = points(&start, &end, &path, &origin);
+        for pair in route.windows(2) {
+            assert!(phistar.line_of_sight(&pair[0], &pair[1], 10f32));
+        }
+        assert!(length(&route) > 380f32);
+        assert_eq!(
+            phistar.adjust_path::<()>(start, Location::from_meters(200f32, 200f32, 10f32, &origin)),
+            Err(PathError::GoalOutsideFlyzone)
+        );
+    }
+
+    #[test]
+    fn phistar_repair_test() {
+        let origin = Location::from_degrees(30.32469, -97.60466, 0f32);
+        let mut phistar = phistar(vec![]);
+        let origin = phistar.origin;
+        let (start, end) = leg(&origin);
+        assert!(phistar.adjust_path::<()>(start, end).unwrap().is_empty());
+
+        // Obstacle appears on the route, the repaired tree matches a fresh search
+        phistar.set_obstacles(vec![wall(&origin, 50f32)]).unwrap();
+        let path = phistar.adjust_path::<()>(start, end).unwrap();
+        let route = points(&start, &end, &path, &origin);
+        for pair in route.windows(2) {
+            assert!(phistar.line_of_sight(&pair[0], &pair[1], 10f32));
+        }
+        let mut fresh = self::phistar(vec![wall(&origin, 50f32)]);
+        let expected = fresh.adjust_path::<()>(start, end).unwrap();
+        let expected = points(&start, &end, &expected, &origin);
+        assert!((length(&route) - length(&expected)).abs() < 1f32);
+
+        // Obstacle removed again, the straight route is found from the cleared area
+        phistar.set_obstacles(vec![]).unwrap();
+        assert!(phistar.adjust_path::<()>(start, end).unwrap().is_empty());
+    }
+
+    #[test]
+    fn phistar_invalid_test() {
+        let origin = Location::from_degrees(30.32469, -97.60466, 0f32);
+        let mut phistar = phistar(vec![wall(&origin, 50f32)]);
+        let origin = phistar.origin;
+        let start = Location::from_meters(50f32, 200f32, 10f32, &origin);
+        let inside = Location::from_meters(200f32, 200f32, 10f32, &origin);
+        assert_eq!(
+            phistar.adjust_path::<()>(inside, start),
+            Err(PathError::StartInsideObstacle { obstacle: 0 })
+        );
+
+        // Wall across the flyzone leaves no way around
+        let barrier = Obstacle::prism(
+            vec![
+                Location::from_meters(190f32, 1f32, 50f32, &origin),
+                Location::from_meters(210f32, 1f32, 50f32, &origin),
+                Location::from_meters(210f32, 399f32, 50f32, &origin),
+                Location::from_meters(190f32, 399f32, 50f32, &origin),
+            ],
+            50f32,
+        );
+        phistar.set_obstacles(vec![barrier]).unwrap();
+        let end = Location::from_meters(350f32, 200f32, 10f32, &origin);
+        assert_eq!(
+            phistar.adjust_path::<()>(start, end),
+            Err(PathError::OpenSetExhausted)
+        );
+    }
+
+    #[test]
+    fn phistar_config_test() {
+        let origin = Location::from_degrees(30.32469, -97.60466, 0f32);
+        let mut phistar = Phistar::new();
+        for &(cell_size, field) in &[
+            (0f32, "cell_size"),
+            (::std::f32::NAN, "cell_size"),
+            (1e-3f32, "cell_size"),
+        ] {
+            let config = PConfig::new(2f32, Duration::from_secs(10), cell_size);
+            assert_eq!(
+                phistar.init(config, square_flyzone(&origin), Vec::new()),
+                Err(PathfinderError::InvalidConfig { field })
+            );
+        }
+
+        phistar
+            .init(PConfig::default(), square_flyzone(&origin), Vec::new())
+            .unwrap();
+        let size = phistar.size;
+        assert_eq!(
+            phistar.set_config(PConfig::new(-1f32, Duration::from_secs(10), 10f32)),
+            Err(PathfinderError::InvalidConfig {
+                field: "buffer_size"
+            })
+        );
+        assert_eq!(
+            phistar.set_config(PConfig::new(2f32, Duration::from_secs(10), 1e-3f32)),
+            Err(PathfinderError::InvalidConfig { field: "cell_size" })
+        );
+        assert_eq!(phistar.get_config(), &PConfig::default());
+        assert_eq!(phistar.size, size);
+    }
+}
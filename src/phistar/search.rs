@@ -0,0 +1,264 @@
+// search.rs
+// Theta* search over grid nodes, kept and repaired when obstacles change
+
+extern crate ordered_float;
+
+use self::ordered_float::OrderedFloat;
+use super::*;
+use std::cmp::Reverse;
+use std::collections::BinaryHeap;
+
+pub struct Search {
+    pub start: Point,
+    pub goal: Point,
+    pub min_height: f32,
+    cell_size: f32,
+    cols: usize,
+    rows: usize,
+    g: Vec<f32>,
+    parent: Vec<Option<usize>>,
+    closed: Vec<bool>,
+    open: BinaryHeap<Reverse<(OrderedFloat<f32>, usize)>>,
+    pub expanded: usize,
+}
+
+impl Search {
+    // Grid nodes are followed by the start and the goal
+    pub fn new(
+        cell_size: f32,
+        (cols, rows): (usize, usize),
+        start: Point,
+        goal: Point,
+        min_height: f32,
+    ) -> Self {
+        let count = (cols + 1) * (rows + 1) + 2;
+        let mut search = Self {
+            start,
+            goal,
+            min_height,
+            cell_size,
+            cols,
+            rows,
+            g: vec![f32::INFINITY; count],
+            parent: vec![None; count],
+            closed: vec![false; count],
+            open: BinaryHeap::new(),
+            expanded: 0,
+        };
+        let start = search.start_index();
+        search.g[start] = 0f32;
+        search.push(start);
+        search
+    }
+
+    fn start_index(&self) -> usize {
+        self.g.len() - 2
+    }
+
+    fn goal_index(&self) -> usize {
+        self.g.len() - 1
+    }
+
+    fn point(&self, node: usize) -> Point {
+        if node == self.start_index() {
+            self.start
+        } else if node == self.goal_index() {
+            self.goal
+        } else {
+            Point::new(
+                (node % (self.cols + 1)) as f32 * self.cell_size,
+                (node / (self.cols + 1)) as f32 * self.cell_size,
+                0f32,
+            )
+        }
+    }
+
+    // Grid nodes at the corners of the cell containing point
+    fn corners(&self, point: &Point) -> Vec<usize> {
+        let index = |x: f32, n: usize| ((x / self.cell_size).floor().max(0f32) as usize).min(n - 1);
+        let (i, j) = (index(point.x, self.cols), index(point.y, self.rows));
+        let node = |i: usize, j: usize| j * (self.cols + 1) + i;
+        vec![
+            node(i, j),
+            node(i + 1, j),
+            node(i + 1, j + 1),
+            node(i, j + 1),
+        ]
+    }
+
+    // Eight surrounding grid nodes, the start reaches its cell corners and the corners of the
+    // goal cell reach the goal
+    fn neighbors(&self, node: usize) -> Vec<usize> {
+        let goal = self.goal_index();
+        if node == goal {
+            return Vec::new();
+        }
+        if node == self.start_index() {
+            let mut neighbors = self.corners(&self.start);
+            neighbors.push(goal);
+            return neighbors;
+        }
+        let (i, j) = (
+            (node % (self.cols + 1)) as i64,
+            (node / (self.cols + 1)) as i64,
+        );
+        let mut neighbors = Vec::with_capacity(9);
+        for dj in -1..=1 {
+            for di in -1..=1 {
+                let (ni, nj) = (i + di, j + dj);
+                if (di, dj) != (0, 0)
+                    && ni >= 0
+                    && nj >= 0
+                    && ni <= self.cols as i64
+                    && nj <= self.rows as i64
+                {
+                    neighbors.push(nj as usize * (self.cols + 1) + ni as usize);
+                }
+            }
+        }
+        if self.corners(&self.goal).contains(&node) {
+            neighbors.push(goal);
+        }
+        neighbors
+    }
+
+    fn key(&self, node: usize) -> f32 {
+        self.g[node] + self.point(node).distance(&self.goal)
+    }
+
+    fn push(&mut self, node: usize) {
+        let key = self.key(node);
+        self.open.push(Reverse((OrderedFloat(key), node)));
+    }
+
+    // Expand nodes until the goal can not get any cheaper
+    // Returns whether the goal was reached
+    pub fn compute(
+        &mut self,
+        line_of_sight: &dyn Fn(&Point, &Point) -> bool,
+        time_exceeded: &dyn Fn() -> bool,
+    ) -> Result<bool, PathError> {
+        let goal = self.goal_index();
+        while let Some(&Reverse((OrderedFloat(key), node))) = self.open.peek() {
+            if key >= self.g[goal] {
+                break;
+            }
+            if time_exceeded() {
+                return Err(PathError::ProcessTimeExceeded);
+            }
+            self.open.pop();
+            // Skip entries replaced by a cheaper one
+            if self.closed[node] || key != self.key(node) {
+                continue;
+            }
+            self.closed[node] = true;
+            self.expanded += 1;
+            for neighbor in self.neighbors(node) {
+                self.update_vertex(node, neighbor, line_of_sight);
+            }
+        }
+        trace!(expanded = self.expanded, "computed path");
+        Ok(self.g[goal].is_finite())
+    }
+
+    // Connect neighbor to the parent of node if visible, otherwise to node itself
+    fn update_vertex(
+        &mut self,
+        node: usize,
+        neighbor: usize,
+        line_of_sight: &dyn Fn(&Point, &Point) -> bool,
+    ) {
+        let target = self.point(neighbor);
+        let candidates = self.parent[node].into_iter().chain(Some(node));
+        for from in candidates {
+            let point = self.point(from);
+            let cost = self.g[from] + point.distance(&target);
+            if cost >= self.g[neighbor] {
+                continue;
+            }
+            if line_of_sight(&point, &target) {
+                self.g[neighbor] = cost;
+                self.parent[neighbor] = Some(from);
+                self.closed[neighbor] = false;
+                self.push(neighbor);
+                return;
+            }
+        }
+    }
+
+    // Reset nodes whose path to their parent got blocked along with their descendants, and reopen
+    // nodes that could lead back into the reset region or into cleared areas
+    pub fn repair(
+        &mut self,
+        line_of_sight: &dyn Fn(&Point, &Point) -> bool,
+        cleared: &[(Point, Point)],
+    ) {
+        let count = self.g.len();
+        let mut children = vec![Vec::new(); count];
+        let mut reset = vec![false; count];
+        let mut stack = Vec::new();
+        for node in 0..count {
+            if let Some(parent) = self.parent[node] {
+                children[parent].push(node);
+                if !line_of_sight(&self.point(parent), &self.point(node)) {
+                    stack.push(node);
+                }
+            }
+        }
+        while let Some(node) = stack.pop() {
+            if reset[node] {
+                continue;
+            }
+            reset[node] = true;
+            stack.extend(children[node].iter().cloned());
+        }
+
+        let mut reopen = vec![false; count];
+        for node in (0..count).filter(|&n| reset[n]) {
+            self.g[node] = f32::INFINITY;
+            self.parent[node] = None;
+            self.closed[node] = false;
+            for neighbor in self.neighbors(node) {
+                reopen[neighbor] = true;
+            }
+        }
+        // Neighbors are symmetric between grid nodes, except for the start and the goal
+        reopen[self.start_index()] = true;
+        if reset[self.goal_index()] {
+            for corner in self.corners(&self.goal) {
+                reopen[corner] = true;
+            }
+        }
+        for (node, reopen) in reopen.iter_mut().enumerate() {
+            let point = self.point(node);
+            *reopen |= cleared.iter().any(|(lower, upper)| {
+                point.x >= lower.x && point.x <= upper.x && point.y >= lower.y && point.y <= upper.y
+            });
+        }
+        for node in 0..count {
+            if reopen[node] && !reset[node] && self.g[node].is_finite() {
+                self.closed[node] = false;
+                self.push(node);
+            }
+        }
+        debug!(
+            reset = reset.iter().filter(|&&r| r).count(),
+            "repaired search"
+        );
+    }
+
+    // Points from start to goal, None if the goal was not reached
+    pub fn path(&self) -> Option<Vec<Point>> {
+        let mut node = self.goal_index();
+        if self.g[node].is_infinite() {
+            return None;
+        }
+        let mut points = vec![self.point(node)];
+        while let Some(parent) = self.parent[node] {
+            points.push(self.point(parent));
+            node = parent;
+        }
+        points.reverse();
+        Some(points)
+    }
+}
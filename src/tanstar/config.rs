@@ -1,16 +1,64 @@
 // Config struct for tanstar
+use super::CostFunction;
+#[cfg(feature = "serde")]
+use config::LoadConfig;
+use error::PathfinderError;
 use std::time::Duration;
 
 pub const DEFAULT_BUFFER_SIZE: f32 = 2f32;
 pub const DEFAULT_PROCESS_TIME: u64 = 10u64;
 pub const DEFAULT_TURNING_RADIUS: f32 = 5f32;
 pub const DEFAULT_V_MERGE_THRESHOLD: f32 = 5f32;
+pub const DEFAULT_CRUISE_SPEED: f32 = 20f32;
+pub const DEFAULT_DUBINS_SPACING: f32 = 10f32;
+pub const DEFAULT_TURN_WEIGHT: f32 = 0.5f32;
+pub const DEFAULT_MAX_CLIMB_ANGLE: f32 = 30f32;
+pub const DEFAULT_MAX_DESCENT_ANGLE: f32 = 30f32;
 
-#[derive(Clone)]
+// Steady wind over the flyzones
+#[derive(Clone, Copy, Debug, PartialEq)]
+#[cfg_attr(feature = "serde", derive(Serialize, Deserialize))]
+pub struct Wind {
+    pub speed: f32,     // In meters per second
+    pub direction: f32, // In degrees clockwise from north the wind blows from, like Plane::wind_dir
+}
+
+impl Wind {
+    pub fn new(speed: f32, direction: f32) -> Self {
+        Self { speed, direction }
+    }
+
+    // Velocity of the air, east and north, in meters per second
+    pub fn velocity(&self) -> (f32, f32) {
+        let direction = self.direction.to_radians();
+        (-self.speed * direction.sin(), -self.speed * direction.cos())
+    }
+
+    // Speed over the ground of a plane flying at airspeed and crabbing to hold track, a unit
+    // vector east and north
+    // None if the crosswind is too strong or the headwind too fast to make progress
+    pub fn groundspeed(&self, airspeed: f32, (east, north): (f32, f32)) -> Option<f32> {
+        let (wx, wy) = self.velocity();
+        let cross = wx * north - wy * east;
+        if cross.abs() >= airspeed {
+            return None;
+        }
+        let groundspeed = wx * east + wy * north + (airspeed.powi(2) - cross.powi(2)).sqrt();
+        if groundspeed > 0f32 {
+            Some(groundspeed)
+        } else {
+            None
+        }
+    }
+}
+
+#[derive(Clone, Debug, PartialEq)]
+#[cfg_attr(feature = "serde", derive(Serialize, Deserialize), serde(default))]
 pub struct TConfig {
     // buffer around obstacles, in meters
     pub buffer_size: f32,
-    // maximum procssed time allowed
+    // maximum procssed time allowed, serialized in seconds
+    #[cfg_attr(feature = "serde", serde(with = "seconds"))]
     pub max_process_time: Duration,
     // turning radius of the plane, in meters
     pub turning_radius: f32,
@@ -18,6 +66,30 @@ pub struct TConfig {
     pub vertex_merge_threshold: f32,
     // whether generate virtual nodes for flyzones
     pub virtualize_flyzone: bool,
+    // whether to return the best path found so far instead of an error when
+    // max_process_time is exceeded
+    pub anytime: bool,
+    // speed of the plane used to time moving obstacles, in meters per second
+    pub cruise_speed: f32,
+    // whether to smooth the path into Dubins curves of turning radius
+    pub dubins: bool,
+    // distance between waypoints emitted along Dubins curves, in meters
+    pub dubins_spacing: f32,
+    // extra cost, in meters, per degree the plane turns away from its heading at the start
+    pub heading_modifier_weight: f32,
+    // extra cost per meter flown, per meter away from the straight line from start to goal
+    pub direct_path_modifier_weight: f32,
+    // wind the plane flies through at cruise speed
+    pub wind: Option<Wind>,
+    // cost of the edges searched, unless replaced with Tanstar::set_cost_model
+    pub cost: CostFunction,
+    // extra cost, in meters, per degree turned along a ring with CostFunction::TurnPenalized
+    pub turn_weight: f32,
+    // steepest climb and descent through the air, in degrees above and below the horizon
+    pub max_climb_angle: f32,
+    pub max_descent_angle: f32,
+    // whether to bin obstacles and flyzone edges in a grid instead of checking every one
+    pub spatial_index: bool,
 }
 
 impl Default for TConfig {
@@ -46,6 +118,110 @@ impl TConfig {
             turning_radius,
             vertex_merge_threshold,
             virtualize_flyzone,
+            anytime: false,
+            cruise_speed: DEFAULT_CRUISE_SPEED,
+            dubins: false,
+            dubins_spacing: DEFAULT_DUBINS_SPACING,
+            heading_modifier_weight: 0f32,
+            direct_path_modifier_weight: 0f32,
+            wind: None,
+            cost: CostFunction::default(),
+            turn_weight: DEFAULT_TURN_WEIGHT,
+            max_climb_angle: DEFAULT_MAX_CLIMB_ANGLE,
+            max_descent_angle: DEFAULT_MAX_DESCENT_ANGLE,
+            spatial_index: true,
+        }
+    }
+
+    // Sizes, speeds and weights must be finite; weights must not be negative, everything else
+    // must be positive and climb angles at most 90 degrees
+    pub fn validate(&self) -> Result<(), PathfinderError> {
+        let wind = self.wind.unwrap_or_else(|| Wind::new(0f32, 0f32));
+        let fields = [
+            ("buffer_size", self.buffer_size, false),
+            ("turning_radius", self.turning_radius, false),
+            ("vertex_merge_threshold", self.vertex_merge_threshold, false),
+            ("cruise_speed", self.cruise_speed, false),
+            ("dubins_spacing", self.dubins_spacing, false),
+            (
+                "heading_modifier_weight",
+                self.heading_modifier_weight,
+                true,
+            ),
+            (
+                "direct_path_modifier_weight",
+                self.direct_path_modifier_weight,
+                true,
+            ),
+            ("turn_weight", self.turn_weight, true),
+            ("wind.speed", wind.speed, true),
+            ("max_climb_angle", self.max_climb_angle, false),
+            ("max_descent_angle", self.max_descent_angle, false),
+        ];
+        for &(field, value, zero) in &fields {
+            if !value.is_finite() || value < 0f32 || (value == 0f32 && !zero) {
+                return Err(PathfinderError::InvalidConfig { field });
+            }
+        }
+        if self.max_climb_angle > 90f32 {
+            return Err(PathfinderError::InvalidConfig {
+                field: "max_climb_angle",
+            });
+        }
+        if self.max_descent_angle > 90f32 {
+            return Err(PathfinderError::InvalidConfig {
+                field: "max_descent_angle",
+            });
+        }
+        if !wind.direction.is_finite() {
+            return Err(PathfinderError::InvalidConfig {
+                field: "wind.direction",
+            });
         }
+        Ok(())
+    }
+
+    // Fastest climb and descent at cruise speed, in meters per second
+    pub fn climb_rate(&self) -> f32 {
+        self.cruise_speed * self.max_climb_angle.to_radians().sin()
+    }
+
+    pub fn descent_rate(&self) -> f32 {
+        self.cruise_speed * self.max_descent_angle.to_radians().sin()
+    }
+
+    // Turning radius over the ground flying downwind, a turn at constant bank widens with the
+    // square of the groundspeed
+    pub fn ground_turning_radius(&self) -> f32 {
+        match self.wind {
+            Some(wind) if self.cruise_speed > 0f32 => {
+                self.turning_radius * ((self.cruise_speed + wind.speed) / self.cruise_speed).powi(2)
+            }
+            _ => self.turning_radius,
+        }
+    }
+}
+
+#[cfg(feature = "serde")]
+impl LoadConfig for TConfig {
+    fn validate(&self) -> Result<(), PathfinderError> {
+        TConfig::validate(self)
+    }
+}
+
+// Serialize duration as fractional seconds
+#[cfg(feature = "serde")]
+pub(crate) mod seconds {
+    use serde::{Deserialize, Deserializer, Serializer};
+    use std::time::Duration;
+
+    pub fn serialize<S: Serializer>(duration: &Duration, serializer: S) -> Result<S::Ok, S::Error> {
+        let secs = duration.as_secs() as f64 + f64::from(duration.subsec_nanos()) * 1e-9;
+        serializer.serialize_f64(secs)
+    }
+
+    pub fn deserialize<'de, D: Deserializer<'de>>(deserializer: D) -> Result<Duration, D::Error> {
+        let secs = f64::deserialize(deserializer)?;
+        Ok(Duration::from_millis((secs.max(0f64) * 1000f64) as u64))
     }
 }
@@ -0,0 +1,382 @@
+// cost.rs
+// Cost models of the edges of the graph, called by the A* search of Tan*
+
+use super::*;
+
+const GRAVITY: f32 = 9.81;
+
+// Cost of flying the tangents and ring arcs of the graph, in meters or any unit consistent with
+// the heuristic and TConfig::heading_modifier_weight
+// The heuristic must not exceed the cost of reaching the goal for the path to be the cheapest
+// Models are shared by the clones of a planner, which may search from other threads
+pub trait CostModel: Send + Sync {
+    // Straight tangent from a to b of ground length, flown over obstacles below threshold
+    fn tangent(&self, config: &TConfig, a: &Point, b: &Point, length: f32, threshold: f32) -> f32;
+    // Arc of a ring of radius from angle, turning by turn radians, counter clockwise if positive
+    fn arc(&self, config: &TConfig, angle: f32, radius: f32, turn: f32) -> f32;
+    // Lower bound of the cost from a to the goal
+    fn heuristic(&self, config: &TConfig, a: &Point, goal: &Point) -> f32;
+}
+
+// Built-in cost models selected through TConfig::cost
+#[derive(Clone, Copy, Debug, Default, PartialEq)]
+#[cfg_attr(feature = "serde", derive(Serialize, Deserialize))]
+#[cfg_attr(feature = "serde", serde(rename_all = "snake_case"))]
+pub enum CostFunction {
+    // Ground distance
+    Distance,
+    // Flight time through the wind, as the distance in still air
+    #[default]
+    Time,
+    // Flight time with turns weighted by the induced drag of the bank
+    Energy,
+    // Ground distance plus TConfig::turn_weight per degree turned
+    TurnPenalized,
+}
+
+impl CostFunction {
+    pub fn model(self) -> &'static dyn CostModel {
+        match self {
+            CostFunction::Distance => &DistanceCost,
+            CostFunction::Time => &TimeCost,
+            CostFunction::Energy => &EnergyCost,
+            CostFunction::TurnPenalized => &TurnPenalizedCost,
+        }
+    }
+}
+
+pub struct DistanceCost;
+
+impl CostModel for DistanceCost {
+    fn tangent(&self, _: &TConfig, _: &Point, _: &Point, length: f32, _: f32) -> f32 {
+        length
+    }
+
+    fn arc(&self, _: &TConfig, _: f32, radius: f32, turn: f32) -> f32 {
+        radius * turn.abs()
+    }
+
+    fn heuristic(&self, _: &TConfig, a: &Point, goal: &Point) -> f32 {
+        a.distance(goal)
+    }
+}
+
+// Seconds of flight scaled by the cruise speed, equal to the distance without wind
+pub struct TimeCost;
+
+impl CostModel for TimeCost {
+    fn tangent(&self, config: &TConfig, a: &Point, b: &Point, length: f32, _: f32) -> f32 {
+        flight_time(config, a, b, length) * config.cruise_speed
+    }
+
+    fn arc(&self, config: &TConfig, angle: f32, radius: f32, turn: f32) -> f32 {
+        arc_flight_time(config, angle, radius, turn) * config.cruise_speed
+    }
+
+    // Flying straight downwind at best
+    fn heuristic(&self, config: &TConfig, a: &Point, goal: &Point) -> f32 {
+        let tailwind = config.wind.map_or(0f32, |wind| wind.speed);
+        a.distance(goal) * config.cruise_speed / (config.cruise_speed + tailwind)
+    }
+}
+
+// Flight time with arcs weighted by the squared load factor of the bank they need, as induced
+// drag grows with it
+pub struct EnergyCost;
+
+impl CostModel for EnergyCost {
+    fn tangent(&self, config: &TConfig, a: &Point, b: &Point, length: f32, threshold: f32) -> f32 {
+        TimeCost.tangent(config, a, b, length, threshold)
+    }
+
+    fn arc(&self, config: &TConfig, angle: f32, radius: f32, turn: f32) -> f32 {
+        let lateral = config.cruise_speed.powi(2) / (GRAVITY * radius);
+        TimeCost.arc(config, angle, radius, turn) * (1f32 + lateral.powi(2))
+    }
+
+    fn heuristic(&self, config: &TConfig, a: &Point, goal: &Point) -> f32 {
+        TimeCost.heuristic(config, a, goal)
+    }
+}
+
+pub struct TurnPenalizedCost;
+
+impl CostModel for TurnPenalizedCost {
+    fn tangent(&self, _: &TConfig, _: &Point, _: &Point, length: f32, _: f32) -> f32 {
+        length
+    }
+
+    fn arc(&self, config: &TConfig, _: f32, radius: f32, turn: f32) -> f32 {
+        (radius + config.turn_weight * 180f32 / PI) * turn.abs()
+    }
+
+    fn heuristic(&self, _: &TConfig, a: &Point, goal: &Point) -> f32 {
+        a.distance(goal)
+    }
+}
+
+// Seconds to fly from a to b, ground length apart, at cruise speed through the wind
+// Infinite if the plane can not hold the track
+pub fn flight_time(config: &TConfig, a: &Point, b: &Point, length: f32) -> f32 {
+    let distance = a.distance(b);
+    if distance > 0f32 {
+        let track = ((b.x - a.x) / distance, (b.y - a.y) / distance);
+        segment_time(config, track, length)
+    } else {
+        length / config.cruise_speed
+    }
+}
+
+// Seconds to fly the arc of a ring of radius from angle, turning by turn radians
+pub fn arc_flight_time(config: &TConfig, angle: f32, radius: f32, turn: f32) -> f32 {
+    let length = radius * turn.abs();
+    if config.wind.is_none() || length <= 0f32 {
+        return length / config.cruise_speed;
+    }
+    let direction = turn.signum();
+    let count = (turn.abs() / (PI / 8f32)).ceil().max(1f32) as usize;
+    (0..count)
+        .map(|k| {
+            let theta = angle + turn * (k as f32 + 0.5f32) / count as f32;
+            let heading = theta + direction * PI / 2f32;
+            segment_time(
+                config,
+                (heading.cos(), heading.sin()),
+                length / count as f32,
+            )
+        })
+        .sum()
+}
+
+fn segment_time(config: &TConfig, track: (f32, f32), length: f32) -> f32 {
+    let groundspeed = match config.wind {
+        Some(wind) => wind.groundspeed(config.cruise_speed, track),
+        None => Some(config.cruise_speed),
+    };
+    match groundspeed {
+        Some(groundspeed) => length / groundspeed,
+        None => f32::INFINITY,
+    }
+}
+
+impl Tanstar {
+    // Cost model used by the search, the one set with set_cost_model or the one of the config
+    pub(super) fn cost_model(&self) -> &dyn CostModel {
+        match self.cost_model {
+            Some(ref model) => model.as_ref(),
+            None => self.config.cost.model(),
+        }
+    }
+
+    // Replace the built-in cost model selected by TConfig::cost
+    pub fn set_cost_model(&mut self, model: Box<dyn CostModel>) {
+        self.cost_model = Some(Arc::from(model));
+    }
+
+    // Extra cost of flying length from a to b, direct_path_modifier_weight per meter flown per
+    // meter the middle of the edge lies away from the line through start and goal
+    pub(super) fn direct_cost(
+        &self,
+        (start, goal): (&Point, &Point),
+        a: &Point,
+        b: &Point,
+        length: f32,
+    ) -> f32 {
+        let weight = self.config.direct_path_modifier_weight;
+        if weight <= 0f32 {
+            return 0f32;
+        }
+        let middle = Point::new((a.x + b.x) / 2f32, (a.y + b.y) / 2f32, 0f32);
+        let span = start.distance(goal);
+        let offset = if span > 0f32 {
+            ((goal.x - start.x) * (start.y - middle.y) - (start.x - middle.x) * (goal.y - start.y))
+                .abs()
+                / span
+        } else {
+            start.distance(&middle)
+        };
+        weight * length * offset
+    }
+
+    // Turn on a ring of radius from angle along length, in the direction of the ring
+    pub(super) fn turn(angle: f32, length: f32, radius: f32) -> f32 {
+        if radius > 0f32 {
+            length / radius * if angle >= 0f32 { 1f32 } else { -1f32 }
+        } else {
+            0f32
+        }
+    }
+}
+
+#[cfg(test)]
+mod test {
+    use super::*;
+    use fixture::{leg, square_flyzone, wall};
+
+    #[test]
+    fn groundspeed_test() {
+        // from the west, blowing east
+        let wind = Wind::new(10f32, 270f32);
+        let tailwind = wind.groundspeed(20f32, (1f32, 0f32)).unwrap();
+        assert!((tailwind - 30f32).abs() < 1e-3);
+        let headwind = wind.groundspeed(20f32, (-1f32, 0f32)).unwrap();
+        assert!((headwind - 10f32).abs() < 1e-3);
+        // crabbing into the crosswind
+        let crosswind = wind.groundspeed(20f32, (0f32, 1f32)).unwrap();
+        assert!((crosswind - 300f32.sqrt()).abs() < 1e-3);
+
+        let gale = Wind::new(25f32, 270f32);
+        assert_eq!(gale.groundspeed(20f32, (-1f32, 0f32)), None);
+        assert_eq!(gale.groundspeed(20f32, (0f32, 1f32)), None);
+    }
+
+    #[test]
+    fn time_cost_test() {
+        let mut config = TConfig::default();
+        let time = CostFunction::Time.model();
+        let (a, b) = (Point::new(0f32, 0f32, 0f32), Point::new(100f32, 0f32, 0f32));
+        assert_eq!(time.tangent(&config, &a, &b, 100f32, 0f32), 100f32);
+        assert!((time.arc(&config, 0f32, 5f32, 2f32) - 10f32).abs() < 1e-3);
+
+        config.wind = Some(Wind::new(10f32, 270f32));
+        let downwind = time.tangent(&config, &a, &b, 100f32, 0f32);
+        assert!((downwind - 200f32 / 3f32).abs() < 1e-3);
+        assert!((time.tangent(&config, &b, &a, 100f32, 0f32) - 200f32).abs() < 1e-3);
+        assert!(time.heuristic(&config, &a, &b) <= downwind);
+
+        // full circles see every heading whichever way they turn
+        let left = time.arc(&config, 0f32, 10f32, 2f32 * PI);
+        let right = time.arc(&config, -2f32 * PI, 10f32, -2f32 * PI);
+        assert!(left > 20f32 * PI && (left - right).abs() < 1e-2);
+        // quarter of the left ring from the top turns from west to south, from the bottom it
+        // turns from east to north
+        let upwind = time.arc(&config, PI / 2f32, 10f32, PI / 2f32);
+        let downwind = time.arc(&config, 3f32 * PI / 2f32, 10f32, PI / 2f32);
+        assert!(upwind > downwind);
+    }
+
+    #[test]
+    fn cost_function_test() {
+        let config = TConfig {
+            turn_weight: 1f32,
+            ..Default::default()
+        };
+        let (a, b) = (Point::new(0f32, 0f32, 0f32), Point::new(100f32, 0f32, 0f32));
+        for cost in &[
+            CostFunction::Distance,
+            CostFunction::Time,
+            CostFunction::Energy,
+            CostFunction::TurnPenalized,
+        ] {
+            let model = cost.model();
+            assert!((model.tangent(&config, &a, &b, 100f32, 0f32) - 100f32).abs() < 1e-3);
+            assert!(model.heuristic(&config, &a, &b) <= 100f32 + 1e-3);
+            assert!(model.arc(&config, 0f32, 10f32, PI) >= 10f32 * PI - 1e-3);
+        }
+        let quarter = |cost: CostFunction| cost.model().arc(&config, 0f32, 10f32, PI / 2f32);
+        assert!(quarter(CostFunction::Energy) > quarter(CostFunction::Time));
+        assert!(
+            (quarter(CostFunction::TurnPenalized) - quarter(CostFunction::Distance) - 90f32).abs()
+                < 1e-2
+        );
+    }
+
+    #[test]
+    fn wind_test() {
+        let origin = Location::from_degrees(0f64, 0f64, 0f32);
+        let mut tanstar = Tanstar::new();
+        tanstar
+            .init(
+                TConfig::default(),
+                square_flyzone(&origin),
+                vec![wall(&origin, 50f32)],
+            )
+            .unwrap();
+        let origin = tanstar.origin;
+        let (west, east) = leg(&origin);
+        let (_, still) = tanstar.adjust_path_at::<()>(west, east, 0f32).unwrap();
+
+        // wind from the west at half the cruise speed
+        let mut config = tanstar.config.clone();
+        config.wind = Some(Wind::new(10f32, 270f32));
+        tanstar.set_config(config).unwrap();
+        assert!(tanstar.config.ground_turning_radius() > tanstar.config.turning_radius);
+        let (_, downwind) = tanstar.adjust_path_at::<()>(west, east, 0f32).unwrap();
+        let (_, upwind) = tanstar.adjust_path_at::<()>(east, west, 0f32).unwrap();
+        assert!(downwind < still * 0.8);
+        assert!(upwind > still * 1.5);
+    }
+
+    #[test]
+    fn cost_model_test() {
+        use std::sync::atomic::{AtomicUsize, Ordering};
+
+        // Distance that counts the edges it is asked about
+        struct Counting(Arc<AtomicUsize>);
+
+        impl CostModel for Counting {
+            fn tangent(&self, _: &TConfig, _: &Point, _: &Point, length: f32, _: f32) -> f32 {
+                self.0.fetch_add(1, Ordering::Relaxed);
+                length
+            }
+
+            fn arc(&self, _: &TConfig, _: f32, radius: f32, turn: f32) -> f32 {
+                self.0.fetch_add(1, Ordering::Relaxed);
+                radius * turn.abs()
+            }
+
+            fn heuristic(&self, _: &TConfig, a: &Point, goal: &Point) -> f32 {
+                a.distance(goal)
+            }
+        }
+
+        let origin = Location::from_degrees(0f64, 0f64, 0f32);
+        let mut tanstar = Tanstar::new();
+        tanstar
+            .init(
+                TConfig::default(),
+                square_flyzone(&origin),
+                vec![wall(&origin, 50f32)],
+            )
+            .unwrap();
+        let origin = tanstar.origin;
+        let (start, end) = leg(&origin);
+
+        let baseline = tanstar.adjust_path_at::<()>(start, end, 0f32).unwrap();
+        for cost in &[
+            CostFunction::Distance,
+            CostFunction::Energy,
+            CostFunction::TurnPenalized,
+        ] {
+            tanstar.config.cost = *cost;
+            let (path, arrival) = tanstar.adjust_path_at::<()>(start, end, 0f32).unwrap();
+            assert!(!path.is_empty());
+            assert!(arrival >= baseline.1 * 0.99);
+        }
+        // without wind, flight time is the distance
+        tanstar.config.cost = CostFunction::Distance;
+        assert_eq!(tanstar.adjust_path_at::<()>(start, end, 0f32), Ok(baseline));
+
+        let count = Arc::new(AtomicUsize::new(0));
+        tanstar.set_cost_model(Box::new(Counting(count.clone())));
+        assert!(tanstar.adjust_path_at::<()>(start, end, 0f32).is_ok());
+        assert!(count.load(Ordering::Relaxed) > 0);
+    }
+
+    #[test]
+    fn direct_path_test() {
+        let mut tanstar = Tanstar::new();
+        let (start, goal) = (Point::new(0f32, 0f32, 0f32), Point::new(100f32, 0f32, 0f32));
+        let (a, b) = (
+            Point::new(0f32, 10f32, 0f32),
+            Point::new(20f32, 10f32, 0f32),
+        );
+        assert_eq!(tanstar.direct_cost((&start, &goal), &a, &b, 20f32), 0f32);
+
+        tanstar.config.direct_path_modifier_weight = 0.5f32;
+        let on_line = tanstar.direct_cost((&start, &goal), &start, &goal, 100f32);
+        assert!(on_line.abs() < 1e-3);
+        let off_line = tanstar.direct_cost((&start, &goal), &a, &b, 20f32);
+        assert!((off_line - 100f32).abs() < 1e-3);
+    }
+}
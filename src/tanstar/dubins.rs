@@ -0,0 +1,511 @@
+// dubins.rs
+// Shortest paths of bounded curvature between two headed points, used to smooth the path
+
+use super::*;
+
+const TWO_PI: f32 = 2f32 * PI;
+
+#[derive(Clone, Copy, Debug, PartialEq)]
+pub enum Segment {
+    Left, // counter clockwise turn
+    Straight,
+    Right, // clockwise turn
+}
+
+use self::Segment::{Left, Right, Straight};
+
+// Position and heading, in radians counter clockwise from east
+#[derive(Clone, Copy, Debug)]
+pub struct Pose {
+    pub x: f32,
+    pub y: f32,
+    pub heading: f32,
+}
+
+impl Pose {
+    pub fn new(x: f32, y: f32, heading: f32) -> Self {
+        Self { x, y, heading }
+    }
+
+    // Pose after flying a segment of length u, in units of the turning radius
+    fn advance(&self, segment: Segment, u: f32, radius: f32) -> Self {
+        let (x, y, h) = (self.x, self.y, self.heading);
+        match segment {
+            Left => Pose::new(
+                x + radius * ((h + u).sin() - h.sin()),
+                y - radius * ((h + u).cos() - h.cos()),
+                h + u,
+            ),
+            Right => Pose::new(
+                x - radius * ((h - u).sin() - h.sin()),
+                y + radius * ((h - u).cos() - h.cos()),
+                h - u,
+            ),
+            Straight => Pose::new(x + radius * u * h.cos(), y + radius * u * h.sin(), h),
+        }
+    }
+}
+
+#[derive(Clone, Debug)]
+pub struct Dubins {
+    start: Pose,
+    radius: f32,
+    segments: [Segment; 3],
+    lengths: [f32; 3], // In units of the turning radius
+}
+
+fn mod2pi(theta: f32) -> f32 {
+    theta - TWO_PI * (theta / TWO_PI).floor()
+}
+
+impl Dubins {
+    // Every feasible word (LSL, RSR, LSR, RSL, RLR, LRL) from start to end, shortest first
+    pub fn paths(start: Pose, end: Pose, radius: f32) -> Vec<Dubins> {
+        let (dx, dy) = (end.x - start.x, end.y - start.y);
+        let d = (dx * dx + dy * dy).sqrt() / radius;
+        let phi = dy.atan2(dx);
+        let a = mod2pi(start.heading - phi);
+        let b = mod2pi(end.heading - phi);
+        let (sa, sb, ca, cb) = (a.sin(), b.sin(), a.cos(), b.cos());
+        let c_ab = (a - b).cos();
+
+        let mut words: Vec<([Segment; 3], Option<[f32; 3]>)> = Vec::new();
+
+        let p_sq = 2f32 + d * d - 2f32 * c_ab + 2f32 * d * (sa - sb);
+        words.push(([Left, Straight, Left], {
+            if p_sq >= 0f32 {
+                let tmp = (cb - ca).atan2(d + sa - sb);
+                Some([mod2pi(tmp - a), p_sq.sqrt(), mod2pi(b - tmp)])
+            } else {
+                None
+            }
+        }));
+
+        let p_sq = 2f32 + d * d - 2f32 * c_ab + 2f32 * d * (sb - sa);
+        words.push(([Right, Straight, Right], {
+            if p_sq >= 0f32 {
+                let tmp = (ca - cb).atan2(d - sa + sb);
+                Some([mod2pi(a - tmp), p_sq.sqrt(), mod2pi(tmp - b)])
+            } else {
+                None
+            }
+        }));
+
+        let p_sq = -2f32 + d * d + 2f32 * c_ab + 2f32 * d * (sa + sb);
+        words.push(([Left, Straight, Right], {
+            if p_sq >= 0f32 {
+                let p = p_sq.sqrt();
+                let tmp = (-ca - cb).atan2(d + sa + sb) - (-2f32).atan2(p);
+                Some([mod2pi(tmp - a), p, mod2pi(tmp - b)])
+            } else {
+                None
+            }
+        }));
+
+        let p_sq = -2f32 + d * d + 2f32 * c_ab - 2f32 * d * (sa + sb);
+        words.push(([Right, Straight, Left], {
+            if p_sq >= 0f32 {
+                let p = p_sq.sqrt();
+                let tmp = (ca + cb).atan2(d - sa - sb) - 2f32.atan2(p);
+                Some([mod2pi(a - tmp), p, mod2pi(b - tmp)])
+            } else {
+                None
+            }
+        }));
+
+        let tmp = (6f32 - d * d + 2f32 * c_ab + 2f32 * d * (sa - sb)) / 8f32;
+        words.push(([Right, Left, Right], {
+            if tmp.abs() <= 1f32 {
+                let p = mod2pi(TWO_PI - tmp.acos());
+                let t = mod2pi(a - (ca - cb).atan2(d - sa + sb) + p / 2f32);
+                Some([t, p, mod2pi(a - b - t + p)])
+            } else {
+                None
+            }
+        }));
+
+        let tmp = (6f32 - d * d + 2f32 * c_ab + 2f32 * d * (sb - sa)) / 8f32;
+        words.push(([Left, Right, Left], {
+            if tmp.abs() <= 1f32 {
+                let p = mod2pi(TWO_PI - tmp.acos());
+                let t = mod2pi(-a + (-ca + cb).atan2(d + sa - sb) + p / 2f32);
+                Some([t, p, mod2pi(b - a - t + p)])
+            } else {
+                None
+            }
+        }));
+
+        let mut paths: Vec<Dubins> = words
+            .into_iter()
+            .filter_map(|(segments, lengths)| {
+                lengths.map(|lengths| Dubins {
+                    start,
+                    radius,
+                    segments,
+                    lengths,
+                })
+            })
+            .collect();
+        paths.sort_by(|a, b| a.length().partial_cmp(&b.length()).unwrap());
+        paths
+    }
+
+    pub fn length(&self) -> f32 {
+        self.lengths.iter().sum::<f32>() * self.radius
+    }
+
+    // Pose after flying distance s along the path
+    pub fn sample(&self, s: f32) -> Pose {
+        let mut u = s.max(0f32) / self.radius;
+        let mut pose = self.start;
+        for (segment, length) in self.segments.iter().zip(&self.lengths) {
+            if u <= *length {
+                return pose.advance(*segment, u, self.radius);
+            }
+            pose = pose.advance(*segment, *length, self.radius);
+            u -= length;
+        }
+        pose
+    }
+
+    // Word of the path such as "LSR"
+    pub fn word(&self) -> String {
+        self.segments
+            .iter()
+            .map(|segment| match segment {
+                Left => 'L',
+                Straight => 'S',
+                Right => 'R',
+            })
+            .collect()
+    }
+}
+
+impl Tanstar {
+    // Replace the corners of the path with Dubins curves of turning radius, starting at the plane
+    // heading (or towards the first waypoint if unknown) and passing every waypoint along the
+    // bisector of its corner
+    // Curves crossing a flyzone edge or an obstacle below the leg altitude are dropped, keeping
+    // the straight leg; moving obstacles are checked at the time the plane flies each piece of
+    // the curve, timed along the smoothed path like the search times the path
+    pub fn smooth_dubins<T>(
+        &self,
+        plane: &Plane,
+        path: LinkedList<Waypoint<T>>,
+    ) -> LinkedList<Waypoint<T>> {
+        let mut points = vec![Point::from((&plane.location, &self.origin))];
+        points.extend(
+            path.iter()
+                .map(|wp| Point::from((&wp.location, &self.origin))),
+        );
+        let direction = |a: &Point, b: &Point| (b.y - a.y).atan2(b.x - a.x);
+
+        let n = points.len();
+        let mut headings = Vec::with_capacity(n);
+        headings.push(Self::heading(plane).unwrap_or_else(|| direction(&points[0], &points[1])));
+        for i in 1..n - 1 {
+            let (inbound, outbound) = (
+                direction(&points[i - 1], &points[i]),
+                direction(&points[i], &points[i + 1]),
+            );
+            let (x, y) = (
+                inbound.cos() + outbound.cos(),
+                inbound.sin() + outbound.sin(),
+            );
+            headings.push(if x.abs() + y.abs() > 1e-6 {
+                y.atan2(x)
+            } else {
+                inbound
+            });
+        }
+        headings.push(direction(&points[n - 2], &points[n - 1]));
+
+        let radius = self.config.ground_turning_radius();
+        let spacing = self.config.dubins_spacing;
+        let tracks: Vec<Track> = self
+            .obstacles
+            .iter()
+            .filter_map(|obstacle| Track::new(obstacle, &self.origin, self.config.buffer_size))
+            .collect();
+        let mut time = 0f32;
+        let mut smoothed = LinkedList::new();
+        for (i, waypoint) in path.into_iter().enumerate() {
+            let (a, b) = (points[i], points[i + 1]);
+            if a.distance(&b) > 0f32 {
+                let start = Pose::new(a.x, a.y, headings[i]);
+                let end = Pose::new(b.x, b.y, headings[i + 1]);
+                let curve = Dubins::paths(start, end, radius)
+                    .into_iter()
+                    .find_map(|curve| {
+                        self.curve_time(&curve, a.z.min(b.z), &tracks, time)
+                            .map(|duration| (curve, duration))
+                    });
+                match curve {
+                    Some((curve, duration)) => {
+                        time += duration;
+                        trace!(leg = i, word = %curve.word(), "smoothed leg");
+                        let length = curve.length();
+                        let count = (length / spacing).ceil() as usize;
+                        for k in 1..count {
+                            let s = length * k as f32 / count as f32;
+                            let pose = curve.sample(s);
+                            let alt = a.z + (b.z - a.z) * s / length;
+                            let point = Point::new(pose.x, pose.y, alt);
+                            smoothed.push_back(Waypoint::new(
+                                Location::from((&point, &self.origin)),
+                                waypoint.radius,
+                            ));
+                        }
+                    }
+                    None => {
+                        warn!(leg = i, "no valid dubins curve, keeping straight leg");
+                        time += flight_time(&self.config, &a, &b, a.distance(&b));
+                    }
+                }
+            }
+            smoothed.push_back(waypoint);
+        }
+        smoothed
+    }
+
+    // Seconds to fly the curve departing at departure, if it stays inside the flyzones, above
+    // every static obstacle it crosses and clear of the moving obstacles as they pass
+    fn curve_time(
+        &self,
+        curve: &Dubins,
+        min_height: f32,
+        tracks: &[Track],
+        departure: f32,
+    ) -> Option<f32> {
+        let step = (self.config.turning_radius / 4f32).max(0.5f32);
+        let length = curve.length();
+        let count = (length / step).ceil().max(1f32) as usize;
+        let mut prev = curve.sample(0f32);
+        let mut time = departure;
+        for k in 1..=count {
+            let pose = curve.sample(length * k as f32 / count as f32);
+            let (a, b) = (
+                Point::new(prev.x, prev.y, 0f32),
+                Point::new(pose.x, pose.y, 0f32),
+            );
+            let valid = match self.valid_path(&a, &b) {
+                PathValidity::Invalid => false,
+                PathValidity::Flyover(height) => min_height > height,
+                PathValidity::Valid => true,
+            };
+            let duration = flight_time(&self.config, &a, &b, a.distance(&b));
+            if !valid
+                || tracks.iter().any(|track| {
+                    min_height < track.height && track.collides(&a, &b, time, time + duration)
+                })
+            {
+                return None;
+            }
+            time += duration;
+            prev = pose;
+        }
+        Some(time - departure)
+    }
+}
+
+#[cfg(test)]
+mod test {
+    use super::*;
+    use fixture::{leg, square_flyzone, wall};
+
+    fn assert_reaches(curve: &Dubins, end: &Pose) {
+        let last = curve.sample(curve.length());
+        assert!((last.x - end.x).abs() < 1e-2 && (last.y - end.y).abs() < 1e-2);
+        assert!(mod2pi(last.heading - end.heading + 1e-3) < 2e-3);
+    }
+
+    #[test]
+    fn straight_test() {
+        let start = Pose::new(0f32, 0f32, 0f32);
+        let end = Pose::new(100f32, 0f32, 0f32);
+        let paths = Dubins::paths(start, end, 10f32);
+        assert!((paths[0].length() - 100f32).abs() < 1e-3);
+        assert_reaches(&paths[0], &end);
+    }
+
+    #[test]
+    fn u_turn_test() {
+        let start = Pose::new(0f32, 0f32, 0f32);
+        let end = Pose::new(0f32, 20f32, PI);
+        let paths = Dubins::paths(start, end, 10f32);
+        assert_eq!(paths[0].word(), "LSL");
+        assert!((paths[0].length() - 10f32 * PI).abs() < 1e-2);
+    }
+
+    #[test]
+    fn every_word_test() {
+        let start = Pose::new(0f32, 0f32, PI / 2f32);
+        let ends = [
+            Pose::new(50f32, 30f32, -PI / 4f32),
+            Pose::new(-40f32, -10f32, PI),
+            Pose::new(5f32, 5f32, -PI / 2f32),
+            Pose::new(0f32, 15f32, -PI / 2f32),
+        ];
+        let mut words = Vec::new();
+        for end in &ends {
+            for curve in Dubins::paths(start, *end, 10f32) {
+                assert_reaches(&curve, end);
+                words.push(curve.word());
+            }
+        }
+        for word in &["LSL", "RSR", "LSR", "RSL", "RLR", "LRL"] {
+            assert!(words.iter().any(|w| w == word), "{} never feasible", word);
+        }
+    }
+
+    #[test]
+    fn moving_obstacle_test() {
+        let origin = Location::from_degrees(0f64, 0f64, 0f32);
+        let at = |x: f32, y: f32| Location::from_meters(x, y, 10f32, &origin);
+        let flyzones = square_flyzone(&origin);
+        let config = TConfig {
+            dubins: true,
+            turning_radius: 20f32,
+            ..Default::default()
+        };
+        let (start, end) = leg(&origin);
+        let mut path = LinkedList::new();
+        path.push_back(Waypoint::<()>::new(end, 5f32));
+        // heading north, the shortest curve bulges to (70, 220) about 1.5 seconds in
+        let plane = Plane::new(start).yaw(0f32);
+        let smooth = |obstacles: Vec<Obstacle>| {
+            let mut tanstar = Tanstar::new();
+            tanstar
+                .init(config.clone(), flyzones.clone(), obstacles)
+                .unwrap();
+            tanstar.smooth_path(&plane, path.clone())
+        };
+        let balloon = Obstacle::new(at(70f32, 222f32), 5f32, 50f32);
+        let free = smooth(vec![]);
+
+        // a balloon over the curve turns it away, every piece is clear when the plane flies it
+        let parked = balloon.clone().velocity(0f32, 0f32);
+        let smoothed = smooth(vec![parked.clone()]);
+        assert_ne!(smoothed, free);
+        let track = Track::new(&parked, &origin, config.buffer_size).unwrap();
+        let mut time = 0f32;
+        let mut prev = Point::from((&start, &origin));
+        for wp in &smoothed {
+            let point = Point::from((&wp.location, &origin));
+            let duration = flight_time(&config, &prev, &point, prev.distance(&point));
+            assert!(!track.collides(&prev, &point, time, time + duration));
+            time += duration;
+            prev = point;
+        }
+
+        // a balloon arriving long after the plane passed leaves the curve as it is
+        let late = balloon.trajectory(vec![
+            (0f32, at(70f32, 600f32)),
+            (100f32, at(70f32, 600f32)),
+            (110f32, at(70f32, 222f32)),
+        ]);
+        assert_eq!(smooth(vec![late]), free);
+    }
+
+    #[test]
+    fn dubins_smoothing_test() {
+        let origin = Location::from_degrees(0f64, 0f64, 0f32);
+        let config = TConfig {
+            dubins: true,
+            turning_radius: 20f32,
+            ..Default::default()
+        };
+        let mut tanstar = Tanstar::new();
+        tanstar
+            .init(config, square_flyzone(&origin), vec![wall(&origin, 50f32)])
+            .unwrap();
+        let origin = tanstar.origin;
+        let (start, end) = leg(&origin);
+        let mut path = tanstar.adjust_path::<()>(start, end).unwrap();
+        path.push_back(Waypoint::new(end, 5f32));
+
+        // heading north, away from the goal
+        let plane = Plane::new(start).yaw(0f32);
+        let smoothed = tanstar.smooth_path(&plane, path.clone());
+        assert!(smoothed.len() > path.len());
+        assert!(path.iter().all(|wp| smoothed.contains(wp)));
+
+        let mut points = vec![Point::from((&start, &origin))];
+        points.extend(
+            smoothed
+                .iter()
+                .map(|wp| Point::from((&wp.location, &origin))),
+        );
+        // starts along the plane heading
+        assert!(points[1].y - points[0].y > (points[1].x - points[0].x).abs());
+        for pair in points.windows(2) {
+            assert!(pair[0].distance(&pair[1]) <= tanstar.config.dubins_spacing + 1e-2);
+            match tanstar.valid_path(&pair[0], &pair[1]) {
+                PathValidity::Flyover(height) => assert!(height < 10f32),
+                PathValidity::Invalid => panic!("smoothed path leaves the flyzone"),
+                PathValidity::Valid => (),
+            }
+        }
+
+        // disabled by default
+        tanstar.config.dubins = false;
+        assert_eq!(tanstar.smooth_path(&plane, path.clone()), path);
+    }
+
+    #[test]
+    fn heading_start_test() {
+        let origin = Location::from_degrees(0f64, 0f64, 0f32);
+        let config = TConfig {
+            turning_radius: 20f32,
+            ..Default::default()
+        };
+        let mut tanstar = Tanstar::new();
+        tanstar
+            .init(config, square_flyzone(&origin), vec![wall(&origin, 50f32)])
+            .unwrap();
+        let origin = tanstar.origin;
+        let start = Location::from_meters(200f32, 80f32, 10f32, &origin);
+        let end = Location::from_meters(200f32, 350f32, 10f32, &origin);
+
+        // heading unknown, same as starting from the location
+        let plane = Plane::new(start);
+        assert_eq!(
+            tanstar.adjust_path_from::<()>(&plane, end, 0f32),
+            tanstar.adjust_path_at::<()>(start, end, 0f32)
+        );
+
+        // heading south, away from the goal, the plane first turns on one of its circles
+        let plane = Plane::new(start).yaw(180f32);
+        let (path, arrival) = tanstar.adjust_path_from::<()>(&plane, end, 0f32).unwrap();
+        let first = Point::from((&path.front().unwrap().location, &origin));
+        let start = Point::from((&start, &origin));
+        assert!(first.y < start.y);
+        let on_circle =
+            |x: f32| (first.distance(&Point::new(x, start.y, 0f32)) - 20f32).abs() < 0.1;
+        assert!(on_circle(start.x - 20f32) || on_circle(start.x + 20f32));
+        let mut points = vec![start];
+        points.extend(path.iter().map(|wp| Point::from((&wp.location, &origin))));
+        for pair in points.windows(2) {
+            assert!(bool::from(tanstar.valid_path(&pair[0], &pair[1])));
+        }
+
+        let length: f32 = points.windows(2).map(|p| p[0].distance(&p[1])).sum();
+        assert!(arrival >= length / tanstar.config.cruise_speed * 0.99);
+
+        // turning is penalized on top of the distance, without slowing the plane
+        let node = Node::new(start, 20f32, 0f32);
+        let graph = tanstar.graph();
+        let mut search = Search::new(&graph);
+        let start_vertex = search.add_vertex(Vertex::new_start(&node, 0f32));
+        let (_, cost, time) = tanstar.turn_vertices(&mut search, start_vertex, &node, 0f32, PI);
+        tanstar.config.heading_modifier_weight = 1f32;
+        let (vertex, weighted, same) =
+            tanstar.turn_vertices(&mut search, start_vertex, &node, 0f32, PI);
+        assert!((weighted - cost - 180f32).abs() < 1e-2);
+        assert_eq!(time, same);
+        // a waypoint every quarter turn
+        assert_eq!(search.reached(vertex).g_cost, weighted);
+        let parent = search.reached(vertex).parent.unwrap();
+        assert!(search.reached(parent).parent.is_some());
+    }
+}
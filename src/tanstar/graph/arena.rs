@@ -0,0 +1,238 @@
+// arena.rs
+// Visibility graph stored in arenas, vertices refer to each other by index instead of pointers
+// so a built graph can be shared between threads
+
+use super::*;
+
+use std::collections::HashSet;
+use std::ops::Range;
+
+// Nodes and the vertices of their rings, each ring is a circular list starting at a header
+#[derive(Clone, Debug, Default)]
+pub struct Graph {
+    pub(crate) nodes: Vec<Node>,
+    // Headers of the left and right ring of each node
+    rings: Vec<(VertexId, VertexId)>,
+    vertices: Vec<Vertex>,
+    // Slots of removed vertices, reused before the arena grows
+    free: Vec<VertexId>,
+}
+
+// Access to the rings of the graph, shared by the graph and the search adding vertices to a leg
+pub trait Rings {
+    fn vertex(&self, id: VertexId) -> &Vertex;
+    // Header of the left ring of node, or of its right ring
+    fn ring(&self, node: usize, left: bool) -> VertexId;
+    fn next(&self, id: VertexId) -> VertexId;
+    fn prev(&self, id: VertexId) -> VertexId;
+
+    // Traverse ring to find current vertex and next vertex for angle
+    fn traverse_rings(&self, node: usize, angle: f32) -> (VertexId, VertexId) {
+        // Left ring for positive angles, right ring otherwise
+        let is_left = angle >= 0f32;
+        let mut current = self.ring(node, is_left);
+        loop {
+            let next = self.next(current);
+            let next_vertex = self.vertex(next);
+            if (next_vertex.kind == VertexKind::Header)       // Reached end of chain
+                || (is_left && angle < next_vertex.angle)   // Found furthest possible vertex on left
+                || (!is_left && angle > next_vertex.angle)
+            // Found furthest possible vertex on right
+            {
+                return (current, next);
+            }
+            current = next;
+        }
+    }
+
+    // Vertices of a ring, excluding its header
+    fn ring_vertices(&self, header: VertexId) -> Vec<VertexId> {
+        let mut vertices = Vec::new();
+        let mut current = self.next(header);
+        while self.vertex(current).kind != VertexKind::Header {
+            vertices.push(current);
+            current = self.next(current);
+        }
+        vertices
+    }
+
+    // Vertices of both rings of node, excluding their headers
+    fn node_vertices(&self, node: usize) -> Vec<VertexId> {
+        let mut vertices = self.ring_vertices(self.ring(node, true));
+        vertices.append(&mut self.ring_vertices(self.ring(node, false)));
+        vertices
+    }
+
+    // Arc length to the next vertex of the ring
+    fn neighbor_weight(&self, id: VertexId) -> f32 {
+        let vertex = self.vertex(id);
+        arc_length(
+            vertex.angle,
+            self.vertex(self.next(id)).angle,
+            vertex.radius,
+        )
+    }
+}
+
+impl Rings for Graph {
+    fn vertex(&self, id: VertexId) -> &Vertex {
+        &self.vertices[id]
+    }
+
+    fn ring(&self, node: usize, left: bool) -> VertexId {
+        let (left_ring, right_ring) = self.rings[node];
+        if left {
+            left_ring
+        } else {
+            right_ring
+        }
+    }
+
+    fn next(&self, id: VertexId) -> VertexId {
+        self.vertices[id].next.expect("broken chain")
+    }
+
+    fn prev(&self, id: VertexId) -> VertexId {
+        self.vertices[id].prev.expect("broken chain")
+    }
+}
+
+impl Graph {
+    pub fn new() -> Self {
+        Self::default()
+    }
+
+    pub fn node_count(&self) -> usize {
+        self.nodes.len()
+    }
+
+    // Vertices on the rings of the nodes, excluding headers
+    pub fn vertex_count(&self) -> usize {
+        self.vertices.len() - self.free.len() - 2 * self.nodes.len()
+    }
+
+    // Number of slots in the arena, ids of vertices added by a search start after them
+    pub(crate) fn arena_size(&self) -> usize {
+        self.vertices.len()
+    }
+
+    pub(crate) fn vertex_mut(&mut self, id: VertexId) -> &mut Vertex {
+        &mut self.vertices[id]
+    }
+
+    fn add_vertex(&mut self, vertex: Vertex) -> VertexId {
+        match self.free.pop() {
+            Some(id) => {
+                self.vertices[id] = vertex;
+                id
+            }
+            None => {
+                self.vertices.push(vertex);
+                self.vertices.len() - 1
+            }
+        }
+    }
+
+    // Ring without vertices, the header points to itself
+    fn add_ring(&mut self, origin: Point) -> VertexId {
+        let header = self.add_vertex(Vertex::new_head(origin));
+        self.vertices[header].next = Some(header);
+        self.vertices[header].prev = Some(header);
+        header
+    }
+
+    // Insert node at index with empty rings, shifting the nodes after it
+    pub(crate) fn insert_node(&mut self, index: usize, node: Node) {
+        let left = self.add_ring(node.origin);
+        let right = self.add_ring(node.origin);
+        self.nodes.insert(index, node);
+        self.rings.insert(index, (left, right));
+    }
+
+    pub(crate) fn push_node(&mut self, node: Node) -> usize {
+        let index = self.nodes.len();
+        self.insert_node(index, node);
+        index
+    }
+
+    // Remove nodes and free the vertices of their rings
+    // Returns the nodes and the freed vertices, edges to them must be dropped by the caller
+    pub(crate) fn remove_nodes(&mut self, range: Range<usize>) -> (Vec<Node>, HashSet<VertexId>) {
+        let mut removed = HashSet::new();
+        for node in range.clone() {
+            removed.extend(self.node_vertices(node));
+            let (left, right) = self.rings[node];
+            removed.insert(left);
+            removed.insert(right);
+        }
+        for &id in &removed {
+            self.vertices[id].connection.clear();
+            self.free.push(id);
+        }
+        self.rings.drain(range.clone());
+        (self.nodes.drain(range).collect(), removed)
+    }
+
+    // Insert vertex between current and next
+    fn link(&mut self, current: VertexId, mut vertex: Vertex, next: VertexId) -> VertexId {
+        vertex.prev = Some(current);
+        vertex.next = Some(next);
+        let id = self.add_vertex(vertex);
+        self.vertices[next].prev = Some(id);
+        self.vertices[current].next = Some(id);
+        id
+    }
+
+    // Find a vertex of node within threshold to the angle and return it
+    // if not found, create new one
+    pub(crate) fn get_vertex(&mut self, node: usize, angle: f32, threshold: f32) -> VertexId {
+        let (current, next) = self.traverse_rings(node, angle);
+
+        // Ensure current and next are different vertices
+        if current != next {
+            let mut temp_current = current;
+            let mut temp_next = next;
+            if self.vertices[current].kind == VertexKind::Header {
+                temp_current = self.prev(current);
+            } else if self.vertices[next].kind == VertexKind::Header {
+                temp_next = self.next(next);
+            }
+            let radius = self.nodes[node].radius;
+            let arc_a = arc_length(self.vertices[temp_current].angle, angle, radius);
+            let arc_b = arc_length(angle, self.vertices[temp_next].angle, radius);
+            let min_arc = if arc_a < arc_b { arc_a } else { arc_b };
+            if min_arc < threshold {
+                trace!(
+                    current,
+                    next,
+                    angle,
+                    arc = min_arc,
+                    "merged with existing vertex"
+                );
+
+                return if arc_a < arc_b {
+                    temp_current
+                } else {
+                    temp_next
+                };
+            }
+        }
+
+        let vertex = Vertex::new(&self.nodes[node], angle, vec![]);
+        let id = self.link(current, vertex, next);
+        trace!(
+            vertex = id,
+            prev = current,
+            next,
+            angle,
+            "inserted new vertex"
+        );
+        id
+    }
+
+    // Insert an existing vertex into a ring of node
+    pub(crate) fn insert_vertex(&mut self, node: usize, vertex: Vertex) -> VertexId {
+        let (current, next) = self.traverse_rings(node, vertex.angle);
+        self.link(current, vertex, next)
+    }
+}
@@ -27,7 +27,7 @@ fn vertex_direction(points: &[Point]) -> (bool, bool) {
 
 impl Tanstar {
     // Find origin (lower left corner) of a flyzone
-    pub fn find_origin(flyzones: &[Vec<Location>]) -> Location {
+    pub fn find_origin(flyzones: &[Flyzone]) -> Location {
         const MAX_RADIAN: f64 = 2f64 * ::std::f64::consts::PI;
         let mut min_lat = MAX_RADIAN;
         let mut min_lon = MAX_RADIAN;
@@ -37,11 +37,11 @@ impl Tanstar {
         assert!(!flyzones.is_empty(), "Require at least one flyzone");
         for flyzone in flyzones {
             assert!(
-                flyzone.len() > 2,
+                flyzone.boundary.len() > 2,
                 "Require at least 3 points to construct fly zone."
             );
 
-            for point in flyzone {
+            for point in &flyzone.boundary {
                 if point.lat() < min_lat {
                     min_lat = point.lat();
                 }
@@ -58,26 +58,88 @@ impl Tanstar {
             }
         }
 
-        println!(
-            "Found origin: {}, {}",
-            min_lat.to_degrees(),
-            lon.to_degrees()
+        debug!(
+            lat = min_lat.to_degrees(),
+            lon = lon.to_degrees(),
+            "found origin"
         );
         Location::from_radians(min_lat, lon, 0f32)
     }
 
-    // Convert flyzone into virtual nodes
-    pub fn virtualize_flyzone(&mut self, index: usize) {
-        let flyzone = &self.flyzones[index];
-        // convert flyzone to points
-        let mut flyzone_points = Vec::new();
-        for location in flyzone {
-            let point = Point::from((location, &self.origin));
-            flyzone_points.push(point);
+    // Determine if location is inside any of the flyzones
+    pub fn inside_flyzones(flyzones: &[Flyzone], origin: &Location, location: &Location) -> bool {
+        Self::containing_flyzones(flyzones, origin, location)
+            .next()
+            .is_some()
+    }
+
+    // Lowest floor and highest ceiling of the flyzones containing location, None outside them
+    pub fn flyzone_altitudes(
+        flyzones: &[Flyzone],
+        origin: &Location,
+        location: &Location,
+    ) -> Option<(f32, f32)> {
+        Self::containing_flyzones(flyzones, origin, location).fold(None, |band, flyzone| {
+            let (min, max) = band.unwrap_or((f32::INFINITY, f32::NEG_INFINITY));
+            Some((min.min(flyzone.min_alt), max.max(flyzone.max_alt)))
+        })
+    }
+
+    // Move the altitude of location within the limits of the flyzones containing it
+    pub fn clamp_altitude(flyzones: &[Flyzone], origin: &Location, location: &mut Location) {
+        if let Some((min, max)) = Self::flyzone_altitudes(flyzones, origin, location) {
+            location.alt = location.alt().max(min).min(max).into();
         }
+    }
+
+    fn containing_flyzones<'a>(
+        flyzones: &'a [Flyzone],
+        origin: &'a Location,
+        location: &Location,
+    ) -> impl Iterator<Item = &'a Flyzone> {
+        let point = Point::from((location, origin));
+        let inside = move |polygon: &[Location]| {
+            let polygon: Vec<Point> = polygon
+                .iter()
+                .map(|loc| Point::from((loc, origin)))
+                .collect();
+            inside_polygon(&point, &polygon)
+        };
+        flyzones.iter().filter(move |flyzone| {
+            inside(&flyzone.boundary) && !flyzone.keep_outs.iter().any(|k| inside(k))
+        })
+    }
+
+    // Convert flyzone boundary and keep-outs into virtual nodes
+    pub fn virtualize_flyzone(&self, graph: &mut Graph, index: usize) {
+        let boundary = self.polygon_points(&self.flyzones[index].boundary);
+        self.virtualize_polygon(graph, index, &boundary, false);
+        for keep_out in &self.flyzones[index].keep_outs {
+            let keep_out = self.polygon_points(keep_out);
+            self.virtualize_polygon(graph, index, &keep_out, true);
+        }
+    }
+
+    fn polygon_points(&self, polygon: &[Location]) -> Vec<Point> {
+        polygon
+            .iter()
+            .map(|location| Point::from((location, &self.origin)))
+            .collect()
+    }
+
+    // Place nodes in the corners of a polygon, the plane flies inside a boundary and outside a
+    // keep-out, so a keep-out is handled as a boundary of the opposite direction
+    fn virtualize_polygon(
+        &self,
+        graph: &mut Graph,
+        index: usize,
+        flyzone_points: &[Point],
+        keep_out: bool,
+    ) {
         // determine flyzone directions
-        let size = flyzone.len() as isize - 1;
-        let (clockwise, _) = vertex_direction(&flyzone_points);
+        let size = flyzone_points.len() as isize - 1;
+        let (clockwise, _) = vertex_direction(flyzone_points);
+        let clockwise = clockwise != keep_out;
         let (direction, mut iter): (isize, isize) = if clockwise { (1, 0) } else { (-1, size) };
 
         // edge conditions for flyzone
@@ -110,13 +172,18 @@ impl Tanstar {
             if !straight {
                 let d =
                     if (!iter_clockwise && direction == 1) || (iter_clockwise && direction == -1) {
-                        self.config.turning_radius
+                        self.config.ground_turning_radius()
                     } else {
-                        self.config.turning_radius / ((theta / 2f32).sin())
+                        self.config.ground_turning_radius() / ((theta / 2f32).sin())
                     };
 
                 if d > mag_a || d > mag_b {
-                    println!("small angle");
+                    trace!(
+                        flyzone = index,
+                        keep_out,
+                        corner = iter,
+                        "skipped small angle corner"
+                    );
                 } else {
                     // normal angle node
                     let dis = d;
@@ -126,8 +193,8 @@ impl Tanstar {
                         0f32,
                     );
                     //println!("center: {:?}", center);
-                    let virt_ob = Node::new(center, self.config.turning_radius, 0f32);
-                    self.nodes.push(Rc::new(RefCell::new(virt_ob)));
+                    let virt_ob = Node::new(center, self.config.ground_turning_radius(), 0f32);
+                    graph.push_node(virt_ob);
                 }
             }
             iter += direction;
@@ -135,53 +202,47 @@ impl Tanstar {
     }
 
     // determines vertices of node and flyzone intersection
+    pub fn insert_flyzone_sentinel(&self, graph: &mut Graph, node: usize) {
+        let sentinels = self.flyzone_sentinels(&graph.nodes[node]);
+        self.insert_sentinels(graph, node, sentinels);
+    }
+
+    // Insert sentinels at angles of node
+    pub fn insert_sentinels(&self, graph: &mut Graph, node: usize, angles: Vec<f32>) {
+        for angle in angles {
+            let vertex = Vertex::new_sentinel(&graph.nodes[node], angle);
+            let vertex = graph.insert_vertex(node, vertex);
+            trace!(node, vertex, angle, "inserted flyzone sentinel");
+        }
+    }
+
+    // Angles of the sentinels where node crosses the edges of the flyzones, a pair per edge
     #[allow(clippy::many_single_char_names)]
-    pub fn insert_flyzone_sentinel(&mut self, node: &mut Node) {
+    pub fn flyzone_sentinels(&self, node: &Node) -> Vec<f32> {
+        let mut sentinels = Vec::new();
         let center: Point = node.origin;
         let r: f32 = node.radius;
-        for flyzone in self.flyzones.iter() {
-            let size = flyzone.len();
-            // iterate node over all vertices
-            for i in 0..size {
-                let mut v1 = flyzone[i];
-                let mut v2 = flyzone[(i + 1) % size];
-                let (x, y, dist_squared, end) = intersect_distance(
-                    &Point::from((&v1, &self.origin)),
-                    &Point::from((&v2, &self.origin)),
-                    &center,
-                );
-                let dist = dist_squared.sqrt();
-                // println!("dist: {:?}",dist);
-                // check intersect is true
-                if dist > r {
-                    continue;
-                }
-                // determine both intersect angles in left and right ring
-                let theta = (dist / r).acos(); //check
-                let dx = x - center.x; //check
-                let dy = y - center.y; //check
-                let phi = dy.atan2(dx); //check
-                let a = phi + theta;
-                let b = phi - theta;
-                let vertex_a = Rc::new(RefCell::new(Vertex::new_sentinel(
-                    &mut self.num_vertices,
-                    node,
-                    a,
-                )));
-                let vertex_b = Rc::new(RefCell::new(Vertex::new_sentinel(
-                    &mut self.num_vertices,
-                    node,
-                    b,
-                )));
-
-                let a = Location::from((&vertex_a.borrow().location, &self.origin));
-                let b = Location::from((&vertex_b.borrow().location, &self.origin));
-                println!("flyzone/node vertices: {:?},{:?}", a.lat(), a.lon());
-                println!("flyzone/node vertices: {:?},{:?}", b.lat(), b.lon());
-                node.insert_vertex(vertex_a);
-                node.insert_vertex(vertex_b);
+        for i in self.index.edges_near(&center, r) {
+            let (v1, v2) = self.index.segment(i);
+            let (x, y, dist_squared, end) = intersect_distance(v1, v2, &center);
+            let dist = dist_squared.sqrt();
+            // println!("dist: {:?}",dist);
+            // check intersect is true
+            if dist > r {
+                continue;
             }
+            // determine both intersect angles in left and right ring
+            let theta = (dist / r).acos(); //check
+            let dx = x - center.x; //check
+            let dy = y - center.y; //check
+            let phi = dy.atan2(dx); //check
+            let a = phi + theta;
+            let b = phi - theta;
+            trace!(edge = i, a, b, "found flyzone sentinels");
+            sentinels.push(a);
+            sentinels.push(b);
         }
+        sentinels
     }
 }
 
@@ -0,0 +1,218 @@
+// geojson.rs
+// Export of the visibility graph as a GeoJSON FeatureCollection for debugging in QGIS or geojson.io
+
+use super::*;
+
+use serde_json::Value;
+
+// Number of segments approximating node circles
+const CIRCLE_SEGMENTS: usize = 36;
+
+fn position(location: &Location) -> Value {
+    json!([location.lon_degree(), location.lat_degree(), location.alt()])
+}
+
+fn point_position(point: &Point, origin: &Location) -> Value {
+    position(&Location::from((point, origin)))
+}
+
+fn feature(geometry: Value, properties: Value) -> Value {
+    json!({
+        "type": "Feature",
+        "geometry": geometry,
+        "properties": properties,
+    })
+}
+
+// Flyzone altitude limits are only included when bounded, JSON has no infinity
+fn flyzone_properties(index: usize, flyzone: &Flyzone) -> Value {
+    let mut properties = json!({ "kind": "flyzone", "index": index });
+    if flyzone.min_alt.is_finite() {
+        properties["min_alt"] = json!(flyzone.min_alt);
+    }
+    if flyzone.max_alt.is_finite() {
+        properties["max_alt"] = json!(flyzone.max_alt);
+    }
+    properties
+}
+
+// Closed ring approximating the circle of a node
+fn circle(node: &Node, origin: &Location) -> Value {
+    let ring: Vec<Value> = (0..=CIRCLE_SEGMENTS)
+        .map(|i| {
+            let angle = 2f32 * PI * i as f32 / CIRCLE_SEGMENTS as f32;
+            point_position(&Point::from((node, angle)), origin)
+        })
+        .collect();
+    json!({ "type": "Polygon", "coordinates": [ring] })
+}
+
+impl Tanstar {
+    // Graph as GeoJSON features: flyzone and prism polygons, buffered obstacle, prism corner and
+    // virtual node circles, vertices (including sentinels) and connection edges
+    // Every feature has a "kind" property to filter on
+    pub fn graph_geojson(&self) -> Value {
+        let mut features = Vec::new();
+
+        for (i, flyzone) in self.flyzones.iter().enumerate() {
+            // Keep-outs are the holes of the polygon
+            let rings: Vec<Vec<Value>> = flyzone
+                .polygons()
+                .map(|polygon| {
+                    polygon
+                        .iter()
+                        .chain(polygon.first())
+                        .map(position)
+                        .collect()
+                })
+                .collect();
+            features.push(feature(
+                json!({ "type": "Polygon", "coordinates": rings }),
+                flyzone_properties(i, flyzone),
+            ));
+        }
+
+        // Nodes are populated in obstacle order, one per static cylinder and one per prism
+        // corner, followed by virtual flyzone nodes
+        let mut kinds = Vec::new();
+        for (i, obstacle) in self.obstacles.iter().enumerate() {
+            if obstacle.is_moving() {
+                features.push(feature(
+                    json!({ "type": "Point", "coordinates": position(&obstacle.location) }),
+                    json!({
+                        "kind": "moving_obstacle",
+                        "index": i,
+                        "radius": obstacle.radius,
+                        "height": obstacle.height,
+                    }),
+                ));
+                continue;
+            }
+            match obstacle.shape {
+                Shape::Cylinder => kinds.push(("obstacle", i)),
+                Shape::Prism(ref vertices) => {
+                    let ring: Vec<Value> = vertices
+                        .iter()
+                        .chain(vertices.first())
+                        .map(position)
+                        .collect();
+                    features.push(feature(
+                        json!({ "type": "Polygon", "coordinates": [ring] }),
+                        json!({ "kind": "prism", "index": i, "height": obstacle.height }),
+                    ));
+                    kinds.extend(vertices.iter().map(|_| ("corner_node", i)));
+                }
+            }
+        }
+
+        let graph = &self.graph;
+        for (i, node) in graph.nodes.iter().enumerate() {
+            let properties = match kinds.get(i) {
+                Some(&(kind, obstacle)) => json!({
+                    "kind": kind,
+                    "index": i,
+                    "obstacle": obstacle,
+                    "radius": node.radius,
+                    "height": node.height,
+                }),
+                None => json!({ "kind": "virtual_node", "index": i, "radius": node.radius }),
+            };
+            features.push(feature(circle(node, &self.origin), properties));
+
+            for id in graph.node_vertices(i) {
+                let vertex = graph.vertex(id);
+                let from = point_position(&vertex.location, &self.origin);
+                features.push(feature(
+                    json!({ "type": "Point", "coordinates": from }),
+                    json!({
+                        "kind": if vertex.sentinel { "sentinel" } else { "vertex" },
+                        "index": id,
+                        "node": i,
+                        "angle": vertex.angle,
+                    }),
+                ));
+                for connection in &vertex.connection {
+                    let neighbor = graph.vertex(connection.neighbor);
+                    let to = point_position(&neighbor.location, &self.origin);
+                    features.push(feature(
+                        json!({ "type": "LineString", "coordinates": [from, to] }),
+                        json!({
+                            "kind": "connection",
+                            "from": id,
+                            "to": connection.neighbor,
+                            "distance": connection.distance,
+                            "threshold": connection.threshold,
+                        }),
+                    ));
+                }
+            }
+        }
+
+        json!({ "type": "FeatureCollection", "features": features })
+    }
+
+    // Graph features followed by the path flown from start through every waypoint
+    pub fn path_geojson<T>(&self, start: &Location, path: &LinkedList<Waypoint<T>>) -> Value {
+        let mut collection = self.graph_geojson();
+        let mut line = vec![position(start)];
+        line.extend(path.iter().map(|wp| position(&wp.location)));
+        let route = feature(
+            json!({ "type": "LineString", "coordinates": line }),
+            json!({ "kind": "path" }),
+        );
+        if let Some(features) = collection["features"].as_array_mut() {
+            features.push(route);
+        }
+        collection
+    }
+}
+
+#[cfg(test)]
+mod test {
+    use super::*;
+
+    fn count(collection: &Value, kind: &str) -> usize {
+        collection["features"]
+            .as_array()
+            .unwrap()
+            .iter()
+            .filter(|f| f["properties"]["kind"] == kind)
+            .count()
+    }
+
+    #[test]
+    fn graph_geojson_test() {
+        let origin = Location::from_degrees(30.32469, -97.60466, 0f32);
+        let flyzones = vec![vec![
+            Location::from_meters(0f32, 0f32, 0f32, &origin),
+            Location::from_meters(0f32, 400f32, 0f32, &origin),
+            Location::from_meters(400f32, 400f32, 0f32, &origin),
+            Location::from_meters(400f32, 0f32, 0f32, &origin),
+        ]];
+        let obstacles = vec![Obstacle::new(
+            Location::from_meters(200f32, 200f32, 0f32, &origin),
+            50f32,
+            20f32,
+        )];
+        let mut tanstar = Tanstar::create(2f32, flyzones, obstacles);
+
+        let graph = tanstar.graph_geojson();
+        assert_eq!(graph["type"], "FeatureCollection");
+        assert_eq!(count(&graph, "flyzone"), 1);
+        assert_eq!(count(&graph, "obstacle"), 1);
+        assert_eq!(count(&graph, "virtual_node"), 4);
+        assert!(count(&graph, "connection") > 0);
+        assert!(count(&graph, "sentinel") > 0);
+
+        let start = Location::from_meters(50f32, 50f32, 10f32, &origin);
+        let end = Location::from_meters(350f32, 350f32, 10f32, &origin);
+        let path = tanstar.adjust_path::<()>(start, end).unwrap();
+        let with_path = tanstar.path_geojson(&start, &path);
+        assert_eq!(count(&with_path, "path"), 1);
+        let line = with_path["features"].as_array().unwrap().last().unwrap();
+        assert_eq!(
+            line["geometry"]["coordinates"].as_array().unwrap().len(),
+            path.len() + 1
+        );
+    }
+}
@@ -0,0 +1,561 @@
+// index.rs
+// Uniform grid over the static obstacles and flyzone edges, segments are only checked against
+// the items in the cells they cross instead of every item
+// Tangents of the graph are binned by the direction and offset of their line instead, so each is
+// stored once however many cells it crosses
+
+use super::*;
+
+// Cells per side, capped to bound the memory of large fields
+const MAX_CELLS: usize = 256;
+// Items also cover the cells within this distance, so rounding on a cell border never misses one
+const MARGIN: f32 = 1f32;
+// Directions the tangent lines are binned by, over half a turn
+const ANGLES: usize = 64;
+
+#[derive(Clone, Debug, Default)]
+pub struct SpatialIndex {
+    min: (f32, f32), // Lower left corner of the grid
+    cell: f32,       // Side of a cell
+    columns: usize,
+    rows: usize,
+    // Obstacles and flyzone edges overlapping each cell, row after row
+    obstacles: Vec<Vec<usize>>,
+    edges: Vec<Vec<usize>>,
+    // Edges of the flyzone boundaries and keep-outs, in polygon order
+    segments: Vec<(Point, Point)>,
+    // Tangents by direction then offset of their line from the middle of the grid, a cell wide
+    // Shared by the clones of the planner like the graph they index
+    offsets: usize,
+    tangents: Arc<Vec<Vec<(VertexId, VertexId)>>>,
+}
+
+// Bounding box of an item, (min x, min y, max x, max y)
+type Bounds = (f32, f32, f32, f32);
+
+fn bounds(points: &[Point]) -> Bounds {
+    points.iter().fold(
+        (
+            f32::INFINITY,
+            f32::INFINITY,
+            f32::NEG_INFINITY,
+            f32::NEG_INFINITY,
+        ),
+        |(x0, y0, x1, y1), p| (x0.min(p.x), y0.min(p.y), x1.max(p.x), y1.max(p.y)),
+    )
+}
+
+impl SpatialIndex {
+    // Index the static obstacles, by their position in obstacles, and the flyzone edges
+    pub fn new(flyzones: &[Flyzone], obstacles: &[Obstacle], origin: &Location) -> Self {
+        Self::with_cells(flyzones, obstacles, origin, MAX_CELLS)
+    }
+
+    // Single cell holding every item, so queries scan them all as without an index
+    pub fn unindexed(flyzones: &[Flyzone], obstacles: &[Obstacle], origin: &Location) -> Self {
+        Self::with_cells(flyzones, obstacles, origin, 1)
+    }
+
+    // Index with at most max_cells cells per side
+    fn with_cells(
+        flyzones: &[Flyzone],
+        obstacles: &[Obstacle],
+        origin: &Location,
+        max_cells: usize,
+    ) -> Self {
+        let mut segments = Vec::new();
+        for polygon in flyzones.iter().flat_map(|flyzone| flyzone.polygons()) {
+            let points: Vec<Point> = polygon
+                .iter()
+                .map(|location| Point::from((location, origin)))
+                .collect();
+            for i in 0..points.len() {
+                segments.push((points[i], points[(i + 1) % points.len()]));
+            }
+        }
+        let obstacle_bounds: Vec<(usize, Bounds)> = obstacles
+            .iter()
+            .enumerate()
+            .filter(|(_, obstacle)| !obstacle.is_moving())
+            .map(|(i, obstacle)| (i, Self::obstacle_bounds(obstacle, origin)))
+            .collect();
+        let edge_bounds: Vec<Bounds> = segments.iter().map(|&(a, b)| bounds(&[a, b])).collect();
+
+        let extent = obstacle_bounds
+            .iter()
+            .map(|&(_, bounds)| bounds)
+            .chain(edge_bounds.iter().cloned())
+            .fold(None, |extent: Option<Bounds>, (x0, y0, x1, y1)| {
+                Some(match extent {
+                    Some((a0, b0, a1, b1)) => (a0.min(x0), b0.min(y0), a1.max(x1), b1.max(y1)),
+                    None => (x0, y0, x1, y1),
+                })
+            });
+        let (x0, y0, x1, y1) = match extent {
+            Some(extent) => extent,
+            None => return Self::default(),
+        };
+
+        // About one item per cell
+        let items = obstacle_bounds.len() + segments.len();
+        let side = ((items as f32).sqrt().ceil() as usize).clamp(1, max_cells);
+        let cell = ((x1 - x0).max(y1 - y0) / side as f32).max(MARGIN);
+        let columns = (((x1 - x0) / cell).ceil() as usize).clamp(1, max_cells);
+        let rows = (((y1 - y0) / cell).ceil() as usize).clamp(1, max_cells);
+        // Lines crossing the grid are at most half its diagonal from the middle
+        let offsets = ((columns.pow(2) + rows.pow(2)) as f32).sqrt().ceil() as usize;
+        let mut index = Self {
+            min: (x0, y0),
+            cell,
+            columns,
+            rows,
+            obstacles: vec![Vec::new(); columns * rows],
+            edges: vec![Vec::new(); columns * rows],
+            segments,
+            offsets,
+            tangents: Arc::new(vec![Vec::new(); ANGLES * offsets]),
+        };
+        for (i, bounds) in obstacle_bounds {
+            for cell in index.cells_within(bounds) {
+                index.obstacles[cell].push(i);
+            }
+        }
+        for (i, bounds) in edge_bounds.into_iter().enumerate() {
+            for cell in index.cells_within(bounds) {
+                index.edges[cell].push(i);
+            }
+        }
+        trace!(columns, rows, cell, "indexed obstacles and flyzone edges");
+        index
+    }
+
+    // Bounding box of a static obstacle
+    fn obstacle_bounds(obstacle: &Obstacle, origin: &Location) -> Bounds {
+        match obstacle.shape {
+            Shape::Cylinder => {
+                let c = Point::from((&obstacle.location, origin));
+                let r = obstacle.radius;
+                (c.x - r, c.y - r, c.x + r, c.y + r)
+            }
+            Shape::Prism(ref vertices) => {
+                let points: Vec<Point> = vertices
+                    .iter()
+                    .map(|location| Point::from((location, origin)))
+                    .collect();
+                bounds(&points)
+            }
+        }
+    }
+
+    // Insert the obstacle at index of obstacles, shifting the obstacles after it
+    pub fn insert_obstacle(&mut self, index: usize, obstacle: &Obstacle, origin: &Location) {
+        for cell in &mut self.obstacles {
+            for i in cell.iter_mut().filter(|i| **i >= index) {
+                *i += 1;
+            }
+        }
+        if obstacle.is_moving() {
+            return;
+        }
+        for cell in self.cells_within(Self::obstacle_bounds(obstacle, origin)) {
+            self.obstacles[cell].push(index);
+        }
+    }
+
+    // Remove the obstacle at index of obstacles, shifting the obstacles after it
+    pub fn remove_obstacle(&mut self, index: usize) {
+        for cell in &mut self.obstacles {
+            cell.retain(|&i| i != index);
+            for i in cell.iter_mut().filter(|i| **i > index) {
+                *i -= 1;
+            }
+        }
+    }
+
+    // Index every edge of the graph
+    pub fn insert_tangents(&mut self, graph: &Graph) {
+        for node in 0..graph.node_count() {
+            for u in graph.node_vertices(node) {
+                let a = graph.vertex(u).location;
+                for edge in &graph.vertex(u).connection {
+                    let b = graph.vertex(edge.neighbor).location;
+                    self.insert_tangent(u, &a, edge.neighbor, &b);
+                }
+            }
+        }
+    }
+
+    // Index the edge from vertex u at a to vertex v at b
+    pub fn insert_tangent(&mut self, u: VertexId, a: &Point, v: VertexId, b: &Point) {
+        if let Some(bin) = self.tangent_bin(a, b) {
+            Arc::make_mut(&mut self.tangents)[bin].push((u, v));
+        }
+    }
+
+    // Drop the edges from vertex u at a to vertex v at b
+    pub fn remove_tangent(&mut self, u: VertexId, a: &Point, v: VertexId, b: &Point) {
+        if let Some(bin) = self.tangent_bin(a, b) {
+            Arc::make_mut(&mut self.tangents)[bin].retain(|&edge| edge != (u, v));
+        }
+    }
+
+    // Edges of the graph possibly within radius of center, sorted by vertex
+    pub fn tangents_near(&self, center: &Point, radius: f32) -> Vec<(VertexId, VertexId)> {
+        if self.offsets == 0 {
+            return Vec::new();
+        }
+        let (x, y) = self.centered(center);
+        let step = PI / ANGLES as f32;
+        // Lines of a bin turn up to half a step from its middle direction, moving their offset
+        // from center by up to half a step per meter center lies away from the middle
+        let spread = radius + MARGIN + (x.powi(2) + y.powi(2)).sqrt() * step / 2f32;
+        let mut found = Vec::new();
+        for k in 0..ANGLES {
+            let angle = (k as f32 + 0.5f32) * step;
+            let offset = y * angle.cos() - x * angle.sin();
+            let (o0, o1) = (
+                self.offset_bin(offset - spread),
+                self.offset_bin(offset + spread),
+            );
+            for bin in k * self.offsets + o0..=k * self.offsets + o1 {
+                found.extend(self.tangents[bin].iter().cloned());
+            }
+        }
+        found.sort_unstable();
+        found.dedup();
+        found
+    }
+
+    // Edges of the graph possibly crossing a static obstacle
+    pub fn tangents_over(
+        &self,
+        obstacle: &Obstacle,
+        origin: &Location,
+    ) -> Vec<(VertexId, VertexId)> {
+        let (x0, y0, x1, y1) = Self::obstacle_bounds(obstacle, origin);
+        let center = Point::new((x0 + x1) / 2f32, (y0 + y1) / 2f32, 0f32);
+        let radius = ((x1 - x0).powi(2) + (y1 - y0).powi(2)).sqrt() / 2f32;
+        self.tangents_near(&center, radius)
+    }
+
+    // Bin of the line through a and b, by its direction over half a turn and its offset
+    fn tangent_bin(&self, a: &Point, b: &Point) -> Option<usize> {
+        if self.offsets == 0 {
+            return None;
+        }
+        let mut angle = (b.y - a.y).atan2(b.x - a.x);
+        if angle < 0f32 {
+            angle += PI;
+        }
+        let k = ((angle / PI * ANGLES as f32) as usize).min(ANGLES - 1);
+        let (x, y) = self.centered(a);
+        let offset = y * angle.cos() - x * angle.sin();
+        Some(k * self.offsets + self.offset_bin(offset))
+    }
+
+    // Coordinates of point from the middle of the grid
+    fn centered(&self, point: &Point) -> (f32, f32) {
+        (
+            point.x - self.min.0 - self.columns as f32 * self.cell / 2f32,
+            point.y - self.min.1 - self.rows as f32 * self.cell / 2f32,
+        )
+    }
+
+    // Bin of a line offset from the middle of the grid, clamped to the bins
+    fn offset_bin(&self, offset: f32) -> usize {
+        let min = -(self.offsets as f32) * self.cell / 2f32;
+        self.coordinate(offset, min, self.offsets)
+    }
+
+    // Edge of a flyzone polygon
+    pub fn segment(&self, edge: usize) -> (&Point, &Point) {
+        let (ref a, ref b) = self.segments[edge];
+        (a, b)
+    }
+
+    // Obstacles possibly crossed by segment ab, in obstacle order
+    pub fn obstacles_along(&self, a: &Point, b: &Point) -> Vec<usize> {
+        Self::collect(&self.obstacles, self.cells_along(a, b))
+    }
+
+    // Flyzone edges possibly crossed by segment ab, in polygon order
+    pub fn edges_along(&self, a: &Point, b: &Point) -> Vec<usize> {
+        Self::collect(&self.edges, self.cells_along(a, b))
+    }
+
+    // Flyzone edges possibly within radius of center, in polygon order
+    pub fn edges_near(&self, center: &Point, radius: f32) -> Vec<usize> {
+        let bounds = (
+            center.x - radius,
+            center.y - radius,
+            center.x + radius,
+            center.y + radius,
+        );
+        Self::collect(&self.edges, self.cells_within(bounds))
+    }
+
+    // Obstacles possibly containing point, in obstacle order
+    pub fn obstacles_at(&self, point: &Point) -> Vec<usize> {
+        let bounds = (point.x, point.y, point.x, point.y);
+        Self::collect(&self.obstacles, self.cells_within(bounds))
+    }
+
+    // Items of the cells, sorted without duplicates so checks run in the same order as a scan
+    fn collect(items: &[Vec<usize>], cells: Vec<usize>) -> Vec<usize> {
+        let mut found: Vec<usize> = cells
+            .into_iter()
+            .flat_map(|cell| items[cell].iter().cloned())
+            .collect();
+        found.sort_unstable();
+        found.dedup();
+        found
+    }
+
+    // Cell coordinate of x along an axis starting at min, clamped to the grid
+    fn coordinate(&self, x: f32, min: f32, count: usize) -> usize {
+        let i = ((x - min) / self.cell).floor();
+        if i > 0f32 {
+            (i as usize).min(count - 1)
+        } else {
+            0
+        }
+    }
+
+    // Cells overlapping the bounds widened by the margin
+    fn cells_within(&self, (x0, y0, x1, y1): Bounds) -> Vec<usize> {
+        if self.columns == 0 {
+            return Vec::new();
+        }
+        let (c0, c1) = (
+            self.coordinate(x0 - MARGIN, self.min.0, self.columns),
+            self.coordinate(x1 + MARGIN, self.min.0, self.columns),
+        );
+        let (r0, r1) = (
+            self.coordinate(y0 - MARGIN, self.min.1, self.rows),
+            self.coordinate(y1 + MARGIN, self.min.1, self.rows),
+        );
+        (r0..=r1)
+            .flat_map(|r| (c0..=c1).map(move |c| r * self.columns + c))
+            .collect()
+    }
+
+    // Cells crossed by segment ab, column by column
+    fn cells_along(&self, a: &Point, b: &Point) -> Vec<usize> {
+        if self.columns == 0 {
+            return Vec::new();
+        }
+        let (x0, x1) = (a.x.min(b.x), a.x.max(b.x));
+        let (y0, y1) = (a.y.min(b.y), a.y.max(b.y));
+        let slope = (b.y - a.y) / (b.x - a.x);
+        let mut cells = Vec::new();
+        let (c0, c1) = (
+            self.coordinate(x0, self.min.0, self.columns),
+            self.coordinate(x1, self.min.0, self.columns),
+        );
+        for c in c0..=c1 {
+            // Part of the segment within the column and the margin around it, steep segments
+            // are clamped to their own extent instead of trusting the slope
+            let left = (self.min.0 + c as f32 * self.cell - MARGIN).max(x0);
+            let right = (self.min.0 + (c + 1) as f32 * self.cell + MARGIN).min(x1);
+            let (ya, yb) = if slope.is_finite() {
+                (a.y + (left - a.x) * slope, a.y + (right - a.x) * slope)
+            } else {
+                (a.y, b.y)
+            };
+            let (ya, yb) = (ya.max(y0).min(y1), yb.max(y0).min(y1));
+            let (r0, r1) = (
+                self.coordinate(ya.min(yb), self.min.1, self.rows),
+                self.coordinate(ya.max(yb), self.min.1, self.rows),
+            );
+            cells.extend((r0..=r1).map(|r| r * self.columns + c));
+        }
+        cells
+    }
+}
+
+#[cfg(test)]
+mod test {
+    use super::*;
+
+    #[test]
+    fn candidates_test() {
+        let origin = Location::from_degrees(0f64, 0f64, 0f32);
+        let at = |x: f32, y: f32| Location::from_meters(x, y, 0f32, &origin);
+        let flyzones = vec![Flyzone::from(vec![
+            at(0f32, 0f32),
+            at(0f32, 400f32),
+            at(400f32, 400f32),
+            at(400f32, 0f32),
+        ])
+        .keep_out(vec![
+            at(300f32, 300f32),
+            at(300f32, 350f32),
+            at(350f32, 350f32),
+        ])];
+        let mut obstacles: Vec<Obstacle> = (0..25)
+            .map(|i| {
+                let (x, y) = (
+                    40f32 + 80f32 * (i % 5) as f32,
+                    40f32 + 80f32 * (i / 5) as f32,
+                );
+                Obstacle::new(Location::from_meters(x, y, 10f32, &origin), 15f32, 10f32)
+            })
+            .collect();
+        obstacles.push(Obstacle::prism(
+            vec![at(100f32, 200f32), at(100f32, 240f32), at(180f32, 240f32)],
+            20f32,
+        ));
+        let index = SpatialIndex::new(&flyzones, &obstacles, &origin);
+        assert!(index.columns > 1 && index.rows > 1);
+
+        // Fan of segments, including vertical, horizontal and out of the flyzone ones
+        let mut segments = Vec::new();
+        for k in 0..40 {
+            let angle = k as f32 * PI / 20f32;
+            let a = Point::new(200f32, 200f32, 0f32);
+            segments.push((
+                a,
+                Point::new(a.x + 300f32 * angle.cos(), a.y + 300f32 * angle.sin(), 0f32),
+            ));
+            let c = Point::new(-20f32 + 11f32 * k as f32, -20f32, 0f32);
+            segments.push((c, Point::new(c.x, 420f32, 0f32)));
+            segments.push((Point::new(-20f32, c.x, 0f32), Point::new(420f32, c.x, 0f32)));
+        }
+        for (a, b) in segments {
+            let edges = index.edges_along(&a, &b);
+            for (i, &(c, d)) in index.segments.iter().enumerate() {
+                if intersect(&a, &b, &c, &d) {
+                    assert!(edges.contains(&i));
+                }
+            }
+            let candidates = index.obstacles_along(&a, &b);
+            for (i, obstacle) in obstacles.iter().enumerate() {
+                let crossed = match obstacle.shape {
+                    Shape::Cylinder => perpendicular_intersect(&origin, &a, &b, obstacle)
+                        .0
+                        .is_some(),
+                    Shape::Prism(ref vertices) => {
+                        let points: Vec<Point> =
+                            vertices.iter().map(|l| Point::from((l, &origin))).collect();
+                        intersect_polygon(&a, &b, &points)
+                    }
+                };
+                if crossed {
+                    assert!(candidates.contains(&i));
+                }
+            }
+            assert!(candidates.windows(2).all(|pair| pair[0] < pair[1]));
+        }
+
+        // the prism is found from a point inside it, the far corner cylinder is not
+        let inside = index.obstacles_at(&Point::new(120f32, 230f32, 0f32));
+        assert!(inside.contains(&25));
+        assert!(!inside.contains(&24));
+        assert!(index
+            .edges_near(&Point::new(200f32, 200f32, 0f32), 20f32)
+            .is_empty());
+        assert_eq!(
+            index.edges_near(&Point::new(0f32, 200f32, 0f32), 20f32),
+            vec![0]
+        );
+
+        // a single cell holds every static obstacle and edge
+        let unindexed = SpatialIndex::unindexed(&flyzones, &obstacles, &origin);
+        assert_eq!(unindexed.columns * unindexed.rows, 1);
+        assert_eq!(
+            unindexed.obstacles_at(&Point::new(120f32, 230f32, 0f32)),
+            (0..26).collect::<Vec<usize>>()
+        );
+        assert_eq!(
+            unindexed
+                .edges_near(&Point::new(200f32, 200f32, 0f32), 20f32)
+                .len(),
+            7
+        );
+    }
+
+    #[test]
+    fn tangents_test() {
+        let origin = Location::from_degrees(0f64, 0f64, 0f32);
+        let at = |x: f32, y: f32| Location::from_meters(x, y, 0f32, &origin);
+        let flyzones = vec![Flyzone::from(vec![
+            at(0f32, 0f32),
+            at(0f32, 400f32),
+            at(400f32, 400f32),
+            at(400f32, 0f32),
+        ])];
+        let obstacles: Vec<Obstacle> = (0..16)
+            .map(|i| {
+                let (x, y) = (
+                    50f32 + 100f32 * (i % 4) as f32,
+                    50f32 + 100f32 * (i / 4) as f32,
+                );
+                Obstacle::new(Location::from_meters(x, y, 10f32, &origin), 15f32, 10f32)
+            })
+            .collect();
+        let mut index = SpatialIndex::new(&flyzones, &obstacles, &origin);
+
+        // tangents between points of a ring around the field, some of them outside it
+        let points: Vec<Point> = (0..24)
+            .map(|k| {
+                let angle = k as f32 * PI / 12f32;
+                Point::new(
+                    200f32 + 250f32 * angle.cos(),
+                    200f32 + 250f32 * angle.sin(),
+                    0f32,
+                )
+            })
+            .collect();
+        let mut tangents = Vec::new();
+        for u in 0..points.len() {
+            for v in 0..points.len() {
+                if u != v {
+                    index.insert_tangent(u, &points[u], v, &points[v]);
+                    tangents.push((u, v));
+                }
+            }
+        }
+        let all = index.tangents_near(&Point::new(200f32, 200f32, 0f32), 1000f32);
+        assert_eq!(all, tangents);
+        for i in 0..81 {
+            let center = Point::new(
+                -50f32 + 62.5f32 * (i % 9) as f32,
+                -50f32 + 62.5f32 * (i / 9) as f32,
+                0f32,
+            );
+            let near = index.tangents_near(&center, 20f32);
+            for &(u, v) in &tangents {
+                let (_, _, dist_squared, _) = intersect_distance(&points[u], &points[v], &center);
+                if dist_squared <= 400f32 {
+                    assert!(near.contains(&(u, v)));
+                }
+            }
+            assert!(near.len() < tangents.len());
+        }
+
+        index.remove_tangent(0, &points[0], 12, &points[12]);
+        let all = index.tangents_near(&Point::new(200f32, 200f32, 0f32), 1000f32);
+        assert_eq!(all.len(), tangents.len() - 1);
+        assert!(!all.contains(&(0, 12)));
+
+        // obstacles inserted and removed in place are indexed as if the index was rebuilt
+        let mut moved = obstacles.clone();
+        let added = Obstacle::new(at(180f32, 220f32), 30f32, 10f32);
+        moved.insert(3, added.clone());
+        index.insert_obstacle(3, &added, &origin);
+        moved.remove(10);
+        index.remove_obstacle(10);
+        let rebuilt = SpatialIndex::new(&flyzones, &moved, &origin);
+        for i in 0..81 {
+            let point = Point::new(
+                -50f32 + 62.5f32 * (i % 9) as f32,
+                -50f32 + 62.5f32 * (i / 9) as f32,
+                0f32,
+            );
+            assert_eq!(index.obstacles_at(&point), rebuilt.obstacles_at(&point));
+        }
+        assert!(index
+            .obstacles_at(&Point::new(200f32, 200f32, 0f32))
+            .contains(&3));
+    }
+}
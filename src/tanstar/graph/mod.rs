@@ -1,9 +1,15 @@
 // Visibility related code for modularity
 use super::*;
 
+mod arena;
 mod flyzones;
+#[cfg(feature = "serde")]
+mod geojson;
+mod index;
+mod prism;
 #[cfg(test)]
 mod test;
+mod update;
 
 pub mod connection;
 pub mod node;
@@ -11,13 +17,15 @@ pub mod point;
 pub mod util;
 pub mod vertex;
 
+pub use self::arena::{Graph, Rings};
 pub use self::connection::Connection;
+pub use self::index::SpatialIndex;
 pub use self::node::Node;
 pub use self::point::Point;
 pub use self::util::*;
-pub use self::vertex::Vertex;
+pub use self::vertex::{Vertex, VertexId, VertexKind};
 
-use obj::{Location, Obstacle};
+use obj::{Location, Obstacle, Shape};
 
 pub enum PathValidity {
     Valid,
@@ -36,91 +44,195 @@ impl From<PathValidity> for bool {
 
 type Path = Vec<(f32, f32, f32, f32)>;
 type Sentinel = Vec<(f32, f32)>;
+// Flyzone sentinels of a node, and the paths and sentinels to each node after it
+type Links = (Vec<f32>, Vec<(Path, Option<Sentinel>)>);
 
 impl Tanstar {
+    // Insert edge from u -> v and return both vertices
     fn insert_edge(
-        &mut self,
+        &self,
+        graph: &mut Graph,
         i: usize,
         j: usize,
         (alpha, beta, distance, threshold): (f32, f32, f32, f32),
-    ) {
-        // Insert edge from u -> v
-        let v = self.nodes[j].borrow().get_vertex(
-            &mut self.num_vertices,
-            beta,
-            self.config.vertex_merge_threshold,
-        );
-        let edge = Connection::new(v.clone(), distance, threshold);
-        let u = self.nodes[i].borrow().get_vertex(
-            &mut self.num_vertices,
-            alpha,
-            self.config.vertex_merge_threshold,
-        );
-        u.borrow_mut().connection.push(edge);
+    ) -> (VertexId, VertexId) {
+        let v = graph.get_vertex(j, beta, self.config.vertex_merge_threshold);
+        let edge = Connection::new(v, distance, threshold);
+        let u = graph.get_vertex(i, alpha, self.config.vertex_merge_threshold);
+        graph.vertex_mut(u).connection.push(edge);
+        (u, v)
     }
 
-    pub fn build_graph(&mut self) {
-        self.populate_nodes();
-        for i in 0..self.nodes.len() {
-            let node = self.nodes[i].clone();
-            self.insert_flyzone_sentinel(&mut node.borrow_mut());
-
-            for j in i + 1..self.nodes.len() {
-                let (paths, obs_sentinels) =
-                    self.find_path(&self.nodes[i].borrow(), &self.nodes[j].borrow());
-                println!("[{} {}]: path count -> {}", i, j, paths.len());
-
-                // Inserting edge
-                for mut path in paths {
-                    // Edge from i to j
-                    self.insert_edge(i, j, path);
-                    // Reciprocal edge from j to i
-                    let (beta, alpha) = (reverse_polarity(path.0), reverse_polarity(path.1));
-                    path.0 = alpha;
-                    path.1 = beta;
-                    self.insert_edge(j, i, path);
-                }
+    // Insert the tangent edges between nodes i and j in both directions, and the sentinels where
+    // the nodes overlap, and return the edges inserted
+    fn connect_nodes(&self, graph: &mut Graph, i: usize, j: usize) -> Vec<(VertexId, VertexId)> {
+        let (paths, obs_sentinels) = self.find_path(&graph.nodes[i], &graph.nodes[j]);
+        self.link_nodes(graph, i, j, paths, obs_sentinels)
+    }
+
+    // Insert tangents and sentinels found between nodes i and j by find_path
+    // Sentinels record the node they overlap, returns the edges inserted
+    fn link_nodes(
+        &self,
+        graph: &mut Graph,
+        i: usize,
+        j: usize,
+        paths: Path,
+        obs_sentinels: Option<Sentinel>,
+    ) -> Vec<(VertexId, VertexId)> {
+        trace!(i, j, paths = paths.len(), "found paths between nodes");
+
+        // Inserting edge
+        let mut edges = Vec::new();
+        for mut path in paths {
+            // Edge from i to j
+            edges.push(self.insert_edge(graph, i, j, path));
+            // Reciprocal edge from j to i
+            let (beta, alpha) = (reverse_polarity(path.0), reverse_polarity(path.1));
+            path.0 = alpha;
+            path.1 = beta;
+            edges.push(self.insert_edge(graph, j, i, path));
+        }
+
+        // Inserting sentinels
+        if let Some(obs_sentinels) = obs_sentinels {
+            trace!(i, j, "inserting obstacle sentinels");
+            let (owner_a, owner_b) = (graph.ring(j, true), graph.ring(i, true));
+            for (alpha_s, beta_s) in obs_sentinels {
+                let a = Vertex::new_overlap(&graph.nodes[i], alpha_s, owner_a);
+                let b = Vertex::new_overlap(&graph.nodes[j], beta_s, owner_b);
+                graph.insert_vertex(i, a);
+                graph.insert_vertex(j, b);
+            }
+        }
+        edges
+    }
+
+    // Flyzone sentinels of node i and its tangents with the nodes after it, None once out of time
+    // Only reads the geometry of the nodes, so nodes can be linked in parallel
+    fn node_links(&self, graph: &Graph, i: usize, start_time: SystemTime) -> Option<Links> {
+        if self.time_exceeded(start_time) {
+            return None;
+        }
+        let node = &graph.nodes[i];
+        let paths = graph.nodes[i + 1..]
+            .iter()
+            .map(|other| self.find_path(node, other))
+            .collect();
+        Some((self.flyzone_sentinels(node), paths))
+    }
+
+    // Build the visibility graph within max_process_time
+    // In anytime mode an unfinished graph is kept, otherwise the timeout is an error
+    // With the rayon feature the tangents of the nodes are found in parallel, then inserted in
+    // node order so the graph is the same as built sequentially
+    // The graph is built aside and replaces the shared one once done
+    pub fn build_graph(&mut self) -> Result<(), PathfinderError> {
+        *self = self.rebuilt(
+            self.config.clone(),
+            self.flyzones.clone(),
+            self.obstacles.clone(),
+        )?;
+        Ok(())
+    }
+
+    // Planner for the given inputs with its origin, index and graph built from them
+    // self is left untouched, so a failed build keeps the previous inputs and graph
+    pub(super) fn rebuilt(
+        &self,
+        config: TConfig,
+        flyzones: Vec<Flyzone>,
+        obstacles: Vec<Obstacle>,
+    ) -> Result<Self, PathfinderError> {
+        let start_time = SystemTime::now();
+        let mut candidate = Tanstar {
+            config,
+            flyzones,
+            obstacles,
+            initialized: self.initialized,
+            origin: self.origin,
+            graph: self.graph.clone(),
+            index: SpatialIndex::default(),
+            cost_model: self.cost_model.clone(),
+        };
+        let mut graph = candidate.populate_nodes();
+        let span = info_span!("build_graph", nodes = graph.node_count());
+        let _enter = span.enter();
+        candidate.link_graph(&mut graph, start_time)?;
+        candidate.index.insert_tangents(&graph);
+        debug!(vertices = graph.vertex_count(), "built graph");
+        candidate.graph = Arc::new(graph);
+        Ok(candidate)
+    }
+
+    // Link every pair of nodes of the populated graph, stopping early once out of time
+    fn link_graph(&self, graph: &mut Graph, start_time: SystemTime) -> Result<(), PathfinderError> {
+        #[cfg(feature = "rayon")]
+        let links: Vec<Option<Links>> = (0..graph.node_count())
+            .into_par_iter()
+            .map(|i| self.node_links(graph, i, start_time))
+            .collect();
+        #[cfg(not(feature = "rayon"))]
+        let links: Vec<Option<Links>> = (0..graph.node_count())
+            .map(|i| self.node_links(graph, i, start_time))
+            .collect();
 
-                // Inserting sentinels
-                if obs_sentinels.is_some() {
-                    println!("inserting sentinels");
-                    for (alpha_s, beta_s) in obs_sentinels.unwrap() {
-                        let mut a = Vertex::new_sentinel(
-                            &mut self.num_vertices,
-                            &self.nodes[i].borrow(),
-                            alpha_s,
-                        );
-                        //a.sentinel = true;
-                        let mut b = Vertex::new_sentinel(
-                            &mut self.num_vertices,
-                            &self.nodes[j].borrow(),
-                            beta_s,
-                        );
-                        //b.;
-                        let s_a = Rc::new(RefCell::new(a));
-                        let s_b = Rc::new(RefCell::new(b));
-                        self.nodes[i].borrow_mut().insert_vertex(s_a);
-                        self.nodes[j].borrow_mut().insert_vertex(s_b);
-                    }
+        for (i, links) in links.into_iter().enumerate() {
+            let (sentinels, paths) = match links {
+                Some(links) => links,
+                None if self.config.anytime => {
+                    warn!(node = i, "exceeded max process time, graph is incomplete");
+                    break;
                 }
+                None => return Err(PathfinderError::ProcessTimeExceeded),
+            };
+            self.insert_sentinels(graph, i, sentinels);
+
+            for (j, (paths, obs_sentinels)) in (i + 1..).zip(paths) {
+                self.link_nodes(graph, i, j, paths, obs_sentinels);
             }
         }
+        Ok(())
+    }
 
-        // output_graph(&self);
+    // Insert the nodes of the obstacle at index into the graph from position at, one per static
+    // cylinder and one per prism corner, and return how many were inserted
+    // Moving obstacles are checked during the search instead
+    fn insert_obstacle_nodes(&self, graph: &mut Graph, index: usize, at: usize) -> usize {
+        if self.obstacles[index].is_moving() {
+            return 0;
+        }
+        if let Shape::Prism(_) = self.obstacles[index].shape {
+            return self.virtualize_prism(graph, index, at);
+        }
+        let node = (
+            &self.obstacles[index],
+            &self.origin,
+            self.config.buffer_size,
+        )
+            .into();
+        graph.insert_node(at, node);
+        1
     }
 
-    fn populate_nodes(&mut self) {
-        self.nodes.clear();
+    fn populate_nodes(&mut self) -> Graph {
+        let mut graph = Graph::new();
         self.origin = Self::find_origin(&self.flyzones);
+        self.index = if self.config.spatial_index {
+            SpatialIndex::new(&self.flyzones, &self.obstacles, &self.origin)
+        } else {
+            SpatialIndex::unindexed(&self.flyzones, &self.obstacles, &self.origin)
+        };
         for i in 0..self.obstacles.len() {
-            let mut node = (&self.obstacles[i], &self.origin, self.config.buffer_size).into();
-            self.nodes.push(Rc::new(RefCell::new(node)));
+            let at = graph.node_count();
+            self.insert_obstacle_nodes(&mut graph, i, at);
         }
         if self.config.virtualize_flyzone {
             for i in 0..self.flyzones.len() {
-                self.virtualize_flyzone(i);
+                self.virtualize_flyzone(&mut graph, i);
             }
         }
+        graph
     }
 
     // Generate all valid possible path (tangent lines) between two nodes, and return the
@@ -143,16 +255,15 @@ impl Tanstar {
             (theta + 2f32 * PI, theta + PI)
         };
 
-        println!(
-            "x1:{}, y1:{}, r1:{}, x2:{}, y2:{}, r2:{}",
-            c1.x, c1.y, r1, c2.x, c2.y, r2
-        );
-
-        println!(
-            "theta: {}, theta1: {}, theta2: {}",
-            theta.to_degrees(),
-            theta1.to_degrees(),
-            theta2.to_degrees()
+        trace!(
+            x1 = c1.x,
+            y1 = c1.y,
+            r1,
+            x2 = c2.x,
+            y2 = c2.y,
+            r2,
+            theta = theta.to_degrees(),
+            "finding tangents"
         );
 
         // gamma1 and gamma2 are the angle between reference axis and the tangents
@@ -165,12 +276,6 @@ impl Tanstar {
             gamma2 = PI - gamma2;
         }
 
-        println!(
-            "gamma1: {}, gamma2: {}",
-            gamma1.to_degrees(),
-            gamma2.to_degrees()
-        );
-
         // Outer tangent always exists
         let mut candidates = vec![
             (
@@ -198,7 +303,7 @@ impl Tanstar {
                 ),
             ]);
         } else {
-            println!("obstacle sentinels detected");
+            trace!("obstacle sentinels detected");
             //determine angle locations of sentinels
             let theta_s = ((r1.powi(2) + dist.powi(2) - r2.powi(2)) / (2f32 * r1 * dist)).acos();
             let phi_s = ((r2.powi(2) + dist.powi(2) - r1.powi(2)) / (2f32 * r2 * dist)).acos();
@@ -221,22 +326,17 @@ impl Tanstar {
         for (i, j) in candidates {
             let p1 = Point::from((a, i));
             let p2 = Point::from((b, j));
-            println!("angles {} -> {}", i.to_degrees(), j.to_degrees());
-            println!("validating path {:?} -> {:?}", p1, p2);
-
             match self.valid_path(&p1, &p2) {
                 PathValidity::Valid => {
-                    println!("This path is Valid without Flyover.");
                     connections.push((i, j, p1.distance(&p2), 0f32));
                     point_connections.push((p1, p2));
                 }
                 PathValidity::Flyover(h_min) => {
-                    println!("This path is Valid with Flyover.");
                     connections.push((i, j, p1.distance(&p2), h_min));
                     point_connections.push((p1, p2));
                 }
                 _ => {
-                    println!("This Path is Invalid.");
+                    trace!(alpha = i, beta = j, "tangent invalid");
                 }
             }
         }
@@ -244,56 +344,44 @@ impl Tanstar {
         (connections, sentinels)
     }
 
+    // Whether segment ab crosses a static obstacle
+    fn blocks(&self, obstacle: &Obstacle, a: &Point, b: &Point) -> bool {
+        match obstacle.shape {
+            // catch the simple cases for now: if a or b are inside the radius of obstacle, invalid
+            // check if there are two points of intersect, for flyover cases
+            Shape::Cylinder => matches!(
+                perpendicular_intersect(&self.origin, a, b, obstacle),
+                (Some(_), Some(_))
+            ),
+            Shape::Prism(ref vertices) => intersect_polygon(a, b, &self.prism_points(vertices)),
+        }
+    }
+
     // check if a path is valid (not blocked by flightzone or obstacles)
-    fn valid_path(&self, a: &Point, b: &Point) -> PathValidity {
+    pub fn valid_path(&self, a: &Point, b: &Point) -> PathValidity {
         // let theta_o = (b.z - a.z).atan2(a.distance(b));
         // //check if angle of waypoints is valid
         // if theta_o > MAX_ANGLE_ASCENT {
         //     return PathValidity::Invalid;
         // }
 
-        println!("validating path: {:?}, {:?}", a, b);
-        // latitude is y, longitude is x
-        // flyzone is array connected by each index
-        // some messy code to link flyzone points, can definitely be better
-        for flyzone in &self.flyzones {
-            let mut tempzone = flyzone.clone();
-            let first = Point::from((&tempzone.remove(0), &self.origin));
-            let mut temp = first;
-            for location in tempzone {
-                //println!("origin: {:?}", &self.origin);
-                let point = Point::from((&location, &self.origin));
-                //println!("test intersect for {:?} {:?} {:?} {:?}", a, b, &temp, &point);
-                if intersect(a, b, &temp, &point) {
-                    println!("false due to flyzone");
-                    return PathValidity::Invalid;
-                }
-                temp = point;
-            }
-            //println!("test intersect for {:?} {:?} {:?} {:?}", a, b, &temp, &first);
-            if intersect(a, b, &temp, &first) {
-                println!("false due to flyzone");
+        // only the flyzone edges and obstacles near the segment can cross it
+        for edge in self.index.edges_along(a, b) {
+            let (c, d) = self.index.segment(edge);
+            if intersect(a, b, c, d) {
                 return PathValidity::Invalid;
             }
         }
 
         // test for obstacles
         let mut max_height = 0f32;
-        for obstacle in &self.obstacles {
-            // catch the simple cases for now: if a or b are inside the radius of obstacle, invalid
-            // check if there are two points of intersect, for flyover cases
-            if let (Some(_p1), Some(_p2)) = perpendicular_intersect(&self.origin, a, b, obstacle) {
-                println!(
-                    "found intersection at height {} with obstacle {:?}",
-                    obstacle.height, obstacle
-                );
-                if obstacle.height > max_height {
-                    max_height = obstacle.height;
-                }
+        for i in self.index.obstacles_along(a, b) {
+            let obstacle = &self.obstacles[i];
+            if self.blocks(obstacle, a, b) && obstacle.height > max_height {
+                max_height = obstacle.height;
                 // return PathValidity::Invalid; // Temporarily disable fly over
             }
         }
-        println!("path valid with threshold {}", max_height);
         PathValidity::Flyover(max_height)
     }
 }
@@ -0,0 +1,48 @@
+// prism.rs
+// Contains all functions relating to polygonal (prism) obstacles
+
+use super::*;
+
+// Corner nodes need a radius for tangents to exist, even without a buffer
+const MIN_CORNER_RADIUS: f32 = 1f32;
+
+impl Tanstar {
+    // Convert prism vertices into points relative to origin
+    pub fn prism_points(&self, vertices: &[Location]) -> Vec<Point> {
+        vertices
+            .iter()
+            .map(|location| Point::from((location, &self.origin)))
+            .collect()
+    }
+
+    // Insert the corners of a prism obstacle into the graph from position at as nodes of buffer
+    // radius, and return how many were inserted
+    // Sentinels on the polygon edges keep paths from hugging a corner through the prism
+    pub fn virtualize_prism(&self, graph: &mut Graph, index: usize, at: usize) -> usize {
+        let (points, height) = match self.obstacles[index].shape {
+            Shape::Prism(ref vertices) => {
+                (self.prism_points(vertices), self.obstacles[index].height)
+            }
+            Shape::Cylinder => return 0,
+        };
+        let radius = self.config.buffer_size.max(MIN_CORNER_RADIUS);
+        let n = points.len();
+        for i in 0..n {
+            let corner = points[i];
+            let node = Node::new(Point::new(corner.x, corner.y, height), radius, height);
+            graph.insert_node(at + i, node);
+            for neighbor in &[points[(i + n - 1) % n], points[(i + 1) % n]] {
+                let theta = (neighbor.y - corner.y).atan2(neighbor.x - corner.x);
+                for positive in &[true, false] {
+                    let sentinel = Vertex::new_sentinel(
+                        &graph.nodes[at + i],
+                        normalize_angle(*positive, theta),
+                    );
+                    graph.insert_vertex(at + i, sentinel);
+                }
+            }
+            trace!(obstacle = index, corner = i, "inserted prism corner node");
+        }
+        n
+    }
+}
@@ -161,8 +161,8 @@ fn obstacles_pathing() {
 
     let mut pathfinder = Tanstar::create(1f32, dummy_flyzones(), obstacles);
     let mut config = TConfig::default();
-    config.buffer_size = 0f32;
-    pathfinder.set_config(config);
+    config.buffer_size = 0.1f32;
+    pathfinder.set_config(config).unwrap();
 
     match pathfinder.valid_path(&a, &b) {
         PathValidity::Flyover(threshold) => assert_eq!(threshold, 20f32),
@@ -178,6 +178,22 @@ fn obstacles_pathing() {
     }
 }
 
+#[test]
+fn zero_buffer_rejected() {
+    let ob = obstacle_from_meters(20f32, 20f32, 20f32, 20f32);
+    let mut pathfinder = Tanstar::create(1f32, dummy_flyzones(), vec![ob]);
+    let mut config = TConfig::default();
+    config.buffer_size = 0f32;
+    assert_eq!(
+        pathfinder.set_config(config),
+        Err(PathfinderError::InvalidConfig {
+            field: "buffer_size"
+        })
+    );
+    // The planner keeps the buffer it was created with
+    assert_eq!(pathfinder.get_config().buffer_size, 1f32);
+}
+
 #[test]
 fn intersects_circle() {
     //Desmos Visual: https://www.desmos.com/calculator/fxknkpinao
@@ -385,6 +401,38 @@ fn generate_graph_test() {
     let pathfinder = Tanstar::create(5f32, flyzones, obstacles);
 }
 
+#[test]
+fn build_graph_order_test() {
+    // overlapping and flyzone crossing obstacles, a prism and virtual flyzone nodes
+    let square = [
+        (250f32, 250f32),
+        (250f32, 330f32),
+        (330f32, 330f32),
+        (330f32, 250f32),
+    ];
+    let mut obstacles: Vec<Obstacle> = (0..12)
+        .map(|i| {
+            let x = 30f32 + 30f32 * (i % 4) as f32;
+            let y = 40f32 + 60f32 * (i / 4) as f32;
+            obstacle_from_meters(x, y, 20f32, 10f32 * (i % 3) as f32 + 5f32)
+        })
+        .collect();
+    obstacles.push(prism_from_meters(&square, 50f32));
+    let pathfinder = Tanstar::create(5f32, dummy_flyzones(), obstacles);
+
+    // nodes linked one pair at a time, in order
+    let mut sequential = pathfinder.clone();
+    let mut graph = sequential.populate_nodes();
+    for i in 0..graph.node_count() {
+        sequential.insert_flyzone_sentinel(&mut graph, i);
+        for j in i + 1..graph.node_count() {
+            sequential.connect_nodes(&mut graph, i, j);
+        }
+    }
+    assert!(graph.node_count() > 16);
+    assert_eq!(format!("{:?}", graph), format!("{:?}", pathfinder.graph));
+}
+
 #[test]
 // https://www.geogebra.org/graphing/hbtydqcz
 fn same_radius_test() {
@@ -392,15 +440,13 @@ fn same_radius_test() {
 
     let n1 = Node::new(Point::new(30_f32, 30_f32, 0_f32), 1_f32, 0_f32);
     let n2 = Node::new(Point::new(20_f32, 30_f32, 0_f32), 1_f32, 0_f32);
-    let a1 = Rc::new(n1);
-    let b1 = Rc::new(n2);
     let expected = vec![
         (PI / 2_f32, PI / 2_f32, 10f32, 0f32),
         (-PI / 2_f32, -PI / 2_f32, 10f32, 0f32),
         (101.537 * PI / 180f32, -78.463 * PI / 180f32, 9.798, 0f32),
         (-101.537 * PI / 180f32, 78.463 * PI / 180f32, 9.798, 0f32),
     ];
-    assert_vec4_eqp(&pathfinder.find_path(&a1, &b1).0, &expected);
+    assert_vec4_eqp(&pathfinder.find_path(&n1, &n2).0, &expected);
 }
 
 #[test]
@@ -409,8 +455,6 @@ fn same_radius_offset_test() {
     let pathfinder = Tanstar::create(1f32, dummy_flyzones(), Vec::new());
     let n1 = Node::new(Point::new(20_f32, 20_f32, 0_f32), 5_f32, 0_f32);
     let n2 = Node::new(Point::new(30_f32, 30_f32, 0_f32), 5_f32, 0_f32);
-    let a1 = Rc::new(n1);
-    let b1 = Rc::new(n2);
 
     let expected = vec![
         (7_f32 * PI / 4_f32, 7_f32 * PI / 4_f32, 200f32.sqrt(), 0f32),
@@ -424,7 +468,7 @@ fn same_radius_offset_test() {
         (-3_f32 * PI / 2_f32, 3_f32 * PI / 2_f32, 10f32, 0f32),
     ];
 
-    assert_vec4_eqp(&pathfinder.find_path(&a1, &b1).0, &expected);
+    assert_vec4_eqp(&pathfinder.find_path(&n1, &n2).0, &expected);
 }
 
 #[test]
@@ -432,13 +476,11 @@ fn overlap_test() {
     let pathfinder = Tanstar::create(1f32, dummy_flyzones(), Vec::new());
     let n3 = Node::new(Point::new(15_f32, 10_f32, 0_f32), 5_f32, 0_f32);
     let n4 = Node::new(Point::new(20_f32, 10_f32, 0_f32), 4_f32, 0_f32);
-    let c = Rc::new(n3);
-    let d = Rc::new(n4);
     let expected = vec![
         (4.913799976f32, 4.913799976f32, 4.898979486f32, 0f32),
         (-4.913799976f32, -4.913799976f32, 4.898979486f32, 0f32),
     ];
-    assert_vec4_eqp(&pathfinder.find_path(&c, &d).0, &expected);
+    assert_vec4_eqp(&pathfinder.find_path(&n3, &n4).0, &expected);
 }
 
 #[test]
@@ -446,8 +488,6 @@ fn sentinel_test() {
     let pathfinder = Tanstar::create(1f32, dummy_flyzones(), Vec::new());
     let n3 = Node::new(Point::new(15_f32, 10_f32, 0_f32), 5_f32, 0_f32);
     let n4 = Node::new(Point::new(20_f32, 10_f32, 0_f32), 5_f32, 0_f32);
-    let c = Rc::new(n3);
-    let d = Rc::new(n4);
     let expected = vec![
         (PI / 3f32, 2f32 * PI / 3f32),
         (-PI / 3f32, 4f32 * PI / 3f32),
@@ -455,7 +495,7 @@ fn sentinel_test() {
         (5f32 * PI / 3f32, -4f32 * PI / 3f32),
     ];
     println!("{:?}", expected);
-    assert_vec2_eqp(&pathfinder.find_path(&c, &d).1.unwrap(), &expected);
+    assert_vec2_eqp(&pathfinder.find_path(&n3, &n4).1.unwrap(), &expected);
 }
 
 #[test]
@@ -463,15 +503,13 @@ fn different_radius_no_overlap_test() {
     let pathfinder = Tanstar::create(1f32, dummy_flyzones(), Vec::new());
     let n5 = Node::new(Point::new(20_f32, 10_f32, 0_f32), 2_f32, 0_f32);
     let n6 = Node::new(Point::new(12_f32, 10_f32, 0_f32), 1_f32, 0_f32);
-    let e = Rc::new(n5);
-    let f = Rc::new(n6);
     let expected = vec![
         (1.6961242, 1.6961241, 63f32.sqrt(), 0f32),
         (-1.6961241, -1.6961241, 63f32.sqrt(), 0f32),
         (1.9551932, -1.1863995, 55f32.sqrt(), 0f32),
         (-1.955193, 1.1863995, 55f32.sqrt(), 0f32),
     ];
-    assert_vec4_eqp(&pathfinder.find_path(&e, &f).0, &expected);
+    assert_vec4_eqp(&pathfinder.find_path(&n5, &n6).0, &expected);
 }
 
 /*
@@ -483,8 +521,6 @@ fn different_radius_no_overlap_all_flyover_test() {
     let pathfinder = Tanstar::create(1f32, dummy_flyzones(), vec![obs]);
     let n5 = Node::new(Point::new(20_f32, 10_f32, 30_f32), 2_f32, 0_f32);
     let n6 = Node::new(Point::new(12_f32, 10_f32, 30_f32), 1_f32, 0_f32);
-    let e = Rc::new(n5);
-    let f = Rc::new(n6);
     let expected = vec![
         (
             (1_f32 / 8_f32).acos(),
@@ -511,7 +547,7 @@ fn different_radius_no_overlap_all_flyover_test() {
             20f32,
         ),
     ];
-    assert_vec4_eqp(&pathfinder.find_path(&e, &f).0, &expected);
+    assert_vec4_eqp(&pathfinder.find_path(&n5, &n6).0, &expected);
 }
 */
 
@@ -523,8 +559,6 @@ fn different_radius_no_overlap_one_flyover_test() {
     let pathfinder = Tanstar::create(1f32, dummy_flyzones(), vec![obs]);
     let n5 = Node::new(Point::new(20_f32, 10_f32, 30_f32), 2_f32, 0_f32);
     let n6 = Node::new(Point::new(12_f32, 10_f32, 30_f32), 1_f32, 0_f32);
-    let e = Rc::new(n5);
-    let f = Rc::new(n6);
     let expected = vec![
         (
             (1_f32 / 8_f32).acos(),
@@ -551,10 +585,20 @@ fn different_radius_no_overlap_one_flyover_test() {
             0f32,
         ),
     ];
-    assert_vec4_eqp(&pathfinder.find_path(&e, &f).0, &expected);
+    assert_vec4_eqp(&pathfinder.find_path(&n5, &n6).0, &expected);
 }
 */
 
+#[test]
+fn dump_graph_test() {
+    let obstacles = vec![obstacle_from_meters(200f32, 200f32, 20f32, 20f32)];
+    let pathfinder = Tanstar::create(1f32, dummy_flyzones(), obstacles);
+    let dump = pathfinder.dump_graph();
+    let graph = pathfinder.graph();
+    assert!(dump.contains(&format!("node count: {}", graph.node_count())));
+    assert!(dump.contains(&format!("vertex count: {}", graph.vertex_count())));
+}
+
 #[test]
 fn virtualize_flyzone_square() {
     let origin = Location::from_degrees(0f64, 0f64, 0f32);
@@ -570,12 +614,12 @@ fn virtualize_flyzone_square() {
     let node_d = Point::new(5f32, 15f32, 0f32);
     let expected = vec![node_d, node_c, node_b, node_a];
     for i in 0..4 {
-        assert_point_eq(&pathfinder.nodes[i].borrow().origin, &expected[i]);
+        assert_point_eq(&pathfinder.graph.nodes[i].origin, &expected[i]);
     }
-    let test_flyzone = vec![vec![a, b, c, d]];
-    pathfinder.set_flyzone(test_flyzone);
+    let test_flyzone = vec![Flyzone::from(vec![a, b, c, d])];
+    pathfinder.set_flyzone(test_flyzone).unwrap();
     for i in 0..4 {
-        assert_point_eq(&pathfinder.nodes[i].borrow().origin, &expected[i]);
+        assert_point_eq(&pathfinder.graph.nodes[i].origin, &expected[i]);
     }
 }
 
@@ -629,12 +673,12 @@ fn virtualize_flyzone_plus() {
         node_a,
     ];
     for i in 0..11 {
-        assert_point_eq(&pathfinder.nodes[i].borrow().origin, &expected[i]);
+        assert_point_eq(&pathfinder.graph.nodes[i].origin, &expected[i]);
     }
-    let test_flyzone = vec![vec![a, b, c, d, e, f, g, h, i, j, k, l]];
-    pathfinder.set_flyzone(test_flyzone);
+    let test_flyzone = vec![Flyzone::from(vec![a, b, c, d, e, f, g, h, i, j, k, l])];
+    pathfinder.set_flyzone(test_flyzone).unwrap();
     for i in 0..4 {
-        assert_point_eq(&pathfinder.nodes[i].borrow().origin, &expected[i]);
+        assert_point_eq(&pathfinder.graph.nodes[i].origin, &expected[i]);
     }
 }
 
@@ -654,12 +698,12 @@ fn virtualize_flyzone_linear() {
     let node_d = Point::new(5f32, 15f32, 0f32);
     let expected = vec![node_d, node_c, node_b, node_a];
     for i in 0..4 {
-        assert_point_eq(&pathfinder.nodes[i].borrow().origin, &expected[i]);
+        assert_point_eq(&pathfinder.graph.nodes[i].origin, &expected[i]);
     }
-    let test_flyzone = vec![vec![a, b, c, d, e]];
-    pathfinder.set_flyzone(test_flyzone);
+    let test_flyzone = vec![Flyzone::from(vec![a, b, c, d, e])];
+    pathfinder.set_flyzone(test_flyzone).unwrap();
     for i in 0..4 {
-        assert_point_eq(&pathfinder.nodes[i].borrow().origin, &expected[i]);
+        assert_point_eq(&pathfinder.graph.nodes[i].origin, &expected[i]);
     }
 }
 
@@ -683,12 +727,47 @@ fn virtualize_flyzone_small_angle() {
     let node_e = Point::new(6.2927, 14.3550, 0f32);
     let expected = vec![node_f, node_e, node_d, node_c, node_b, node_a];
     for i in 0..6 {
-        assert_point_eq(&pathfinder.nodes[i].borrow().origin, &expected[i]);
+        assert_point_eq(&pathfinder.graph.nodes[i].origin, &expected[i]);
     }
-    let test_flyzone = vec![vec![a, b, c, d, e, f, g]];
-    pathfinder.set_flyzone(test_flyzone);
+    let test_flyzone = vec![Flyzone::from(vec![a, b, c, d, e, f, g])];
+    pathfinder.set_flyzone(test_flyzone).unwrap();
     for i in 0..6 {
-        assert_point_eq(&pathfinder.nodes[i].borrow().origin, &expected[i]);
+        assert_point_eq(&pathfinder.graph.nodes[i].origin, &expected[i]);
+    }
+}
+
+#[test]
+fn virtualize_keep_out() {
+    let origin = Location::from_degrees(0f64, 0f64, 0f32);
+    let corner = |x, y| Location::from((&Point::new(x, y, 10f32), &origin));
+    let flyzone = Flyzone::from(vec![
+        corner(0f32, 60f32),
+        corner(60f32, 60f32),
+        corner(60f32, 0f32),
+        corner(0f32, 0f32),
+    ])
+    .keep_out(vec![
+        corner(20f32, 20f32),
+        corner(40f32, 20f32),
+        corner(40f32, 40f32),
+        corner(20f32, 40f32),
+    ]);
+    let pathfinder = Tanstar::create(1f32, vec![flyzone], Vec::new());
+    // nodes of the keep-out sit inside it, touching its corners
+    let offset = (25f32 / 2f32).sqrt();
+    let expected = [
+        Point::new(5f32, 55f32, 0f32),
+        Point::new(55f32, 55f32, 0f32),
+        Point::new(55f32, 5f32, 0f32),
+        Point::new(5f32, 5f32, 0f32),
+        Point::new(20f32 + offset, 20f32 + offset, 0f32),
+        Point::new(40f32 - offset, 20f32 + offset, 0f32),
+        Point::new(40f32 - offset, 40f32 - offset, 0f32),
+        Point::new(20f32 + offset, 40f32 - offset, 0f32),
+    ];
+    assert_eq!(pathfinder.graph.node_count(), expected.len());
+    for (i, expected) in expected.iter().enumerate() {
+        assert_point_eq(&pathfinder.graph.nodes[i].origin, expected);
     }
 }
 
@@ -707,3 +786,103 @@ fn sentinel_vertex_test() {
     vertex.angle =
     let expected_vertices = []
 }*/
+
+fn prism_from_meters(points: &[(f32, f32)], height: f32) -> Obstacle {
+    let vertices = points
+        .iter()
+        .map(|&(x, y)| Location::from_meters(x, y, height, &dummy_origin()))
+        .collect();
+    Obstacle::prism(vertices, height)
+}
+
+#[test]
+fn prism_pathing() {
+    let square = [
+        (150f32, 150f32),
+        (150f32, 250f32),
+        (250f32, 250f32),
+        (250f32, 150f32),
+    ];
+    let pathfinder = Tanstar::create(
+        5f32,
+        dummy_flyzones(),
+        vec![prism_from_meters(&square, 50f32)],
+    );
+
+    // one node per corner, each with sentinels on both polygon edges and rings
+    let corners: Vec<_> = pathfinder
+        .graph
+        .nodes
+        .iter()
+        .filter(|node| node.height > 0f32)
+        .collect();
+    assert_eq!(corners.len(), 4);
+    for node in corners {
+        assert_eq!(node.radius, 5f32);
+    }
+
+    let a = Point::new(100f32, 200f32, 10f32);
+    let b = Point::new(300f32, 200f32, 10f32);
+    let c = Point::new(100f32, 300f32, 10f32);
+    let d = Point::new(200f32, 200f32, 10f32);
+    match pathfinder.valid_path(&a, &b) {
+        PathValidity::Flyover(threshold) => assert_eq!(threshold, 50f32),
+        _ => panic!(),
+    }
+    match pathfinder.valid_path(&a, &c) {
+        PathValidity::Flyover(threshold) => assert_eq!(threshold, 0f32),
+        _ => panic!(),
+    }
+    match pathfinder.valid_path(&a, &d) {
+        PathValidity::Flyover(threshold) => assert_eq!(threshold, 50f32),
+        _ => panic!(),
+    }
+}
+
+#[test]
+fn prism_adjust_path() {
+    let square = [
+        (150f32, 150f32),
+        (150f32, 250f32),
+        (250f32, 250f32),
+        (250f32, 150f32),
+    ];
+    let polygon: Vec<Point> = square
+        .iter()
+        .map(|&(x, y)| Point::new(x, y, 0f32))
+        .collect();
+    let mut pathfinder = Tanstar::create(
+        5f32,
+        dummy_flyzones(),
+        vec![prism_from_meters(&square, 50f32)],
+    );
+    let origin = pathfinder.origin;
+
+    let start = Location::from_meters(100f32, 200f32, 10f32, &origin);
+    let end = Location::from_meters(300f32, 200f32, 10f32, &origin);
+    let points = |start: Location, end: Location, path: LinkedList<Waypoint<()>>| {
+        let mut points = vec![Point::from((&start, &origin))];
+        points.extend(path.iter().map(|wp| Point::from((&wp.location, &origin))));
+        points.push(Point::from((&end, &origin)));
+        points
+    };
+    let length =
+        |points: &[Point]| -> f32 { points.windows(2).map(|p| p[0].distance(&p[1])).sum() };
+
+    let path = pathfinder.adjust_path::<()>(start, end).unwrap();
+    let low = points(start, end, path);
+    for pair in low.windows(2) {
+        assert!(!intersect_polygon(&pair[0], &pair[1], &polygon));
+    }
+    assert!(length(&low) > 200f32);
+
+    // high enough to fly over the prism
+    let start = Location::from_meters(100f32, 200f32, 60f32, &origin);
+    let end = Location::from_meters(300f32, 200f32, 60f32, &origin);
+    let path = pathfinder.adjust_path::<()>(start, end).unwrap();
+    let high = points(start, end, path);
+    assert!(high
+        .windows(2)
+        .any(|p| intersect_polygon(&p[0], &p[1], &polygon)));
+    assert!(length(&high) < length(&low));
+}
@@ -0,0 +1,279 @@
+// update.rs
+// Contains functions updating the graph for a single obstacle instead of rebuilding it
+
+use super::*;
+
+use std::mem;
+use std::ops::Range;
+
+impl Tanstar {
+    // Nodes of the obstacle at index, obstacle nodes are populated in obstacle order
+    fn node_range(&self, index: usize) -> Range<usize> {
+        let count = |obstacle: &Obstacle| match obstacle.shape {
+            _ if obstacle.is_moving() => 0,
+            Shape::Cylinder => 1,
+            Shape::Prism(ref vertices) => vertices.len(),
+        };
+        let start = self.obstacles[..index].iter().map(count).sum();
+        start..start + count(&self.obstacles[index])
+    }
+
+    // Take the graph out for an update, copying it if a clone of the planner still shares it
+    fn take_graph(&mut self) -> Graph {
+        let graph = mem::take(&mut self.graph);
+        Arc::try_unwrap(graph).unwrap_or_else(|graph| (*graph).clone())
+    }
+
+    // Raise the edges from u to v to the height of the obstacle if it blocks them
+    fn raise_edges(&self, graph: &mut Graph, (u, v): (VertexId, VertexId), obstacle: &Obstacle) {
+        let (a, b) = (graph.vertex(u).location, graph.vertex(v).location);
+        if !self.blocks(obstacle, &a, &b) {
+            return;
+        }
+        for edge in &mut graph.vertex_mut(u).connection {
+            if edge.neighbor == v && obstacle.height > edge.threshold {
+                edge.threshold = obstacle.height;
+            }
+        }
+    }
+
+    // Insert an obstacle at index into the graph
+    // Edges crossing it are raised to its height, and only the new nodes are connected
+    pub(crate) fn insert_obstacle(&mut self, index: usize, obstacle: Obstacle) {
+        let span = debug_span!("insert_obstacle", index);
+        let _enter = span.enter();
+        let mut graph = self.take_graph();
+        if !obstacle.is_moving() {
+            for edge in self.index.tangents_over(&obstacle, &self.origin) {
+                self.raise_edges(&mut graph, edge, &obstacle);
+            }
+        }
+
+        self.obstacles.insert(index, obstacle);
+        self.index
+            .insert_obstacle(index, &self.obstacles[index], &self.origin);
+        let range = self.node_range(index);
+        self.insert_obstacle_nodes(&mut graph, index, range.start);
+        let mut edges = Vec::new();
+        for i in range.clone() {
+            self.insert_flyzone_sentinel(&mut graph, i);
+            for j in 0..graph.node_count() {
+                // Pairs of new nodes are connected once
+                if !range.contains(&j) || j > i {
+                    edges.append(&mut self.connect_nodes(&mut graph, i, j));
+                }
+            }
+        }
+        for (u, v) in edges {
+            let (a, b) = (graph.vertex(u).location, graph.vertex(v).location);
+            self.index.insert_tangent(u, &a, v, &b);
+        }
+        debug!(
+            nodes = range.len(),
+            vertices = graph.vertex_count(),
+            "inserted obstacle"
+        );
+        self.graph = Arc::new(graph);
+    }
+
+    // Remove the obstacle at index from the graph
+    // Edges to its nodes are dropped, sentinels where they overlapped other nodes become plain
+    // vertices, and edges it raised are checked again against the remaining obstacles
+    pub(crate) fn remove_obstacle_at(&mut self, index: usize) -> Obstacle {
+        let span = debug_span!("remove_obstacle", index);
+        let _enter = span.enter();
+        let mut graph = self.take_graph();
+        let range = self.node_range(index);
+        let obstacle = self.obstacles.remove(index);
+        self.index.remove_obstacle(index);
+        // Freed vertices keep their location until reused, so their edges can still be found
+        let (removed, removed_vertices) = graph.remove_nodes(range);
+
+        // Edges of the removed nodes are tangent to their rings
+        let mut edges = Vec::new();
+        for node in &removed {
+            edges.append(&mut self.index.tangents_near(&node.origin, node.radius));
+        }
+        edges.sort_unstable();
+        edges.dedup();
+        for (u, v) in edges {
+            if !removed_vertices.contains(&u) && !removed_vertices.contains(&v) {
+                continue;
+            }
+            let (a, b) = (graph.vertex(u).location, graph.vertex(v).location);
+            self.index.remove_tangent(u, &a, v, &b);
+            if !removed_vertices.contains(&u) {
+                graph
+                    .vertex_mut(u)
+                    .connection
+                    .retain(|edge| edge.neighbor != v);
+            }
+        }
+
+        if !obstacle.is_moving() {
+            for (u, v) in self.index.tangents_over(&obstacle, &self.origin) {
+                let (a, b) = (graph.vertex(u).location, graph.vertex(v).location);
+                if !self.blocks(&obstacle, &a, &b) {
+                    continue;
+                }
+                let threshold = match self.valid_path(&a, &b) {
+                    PathValidity::Flyover(height) => height,
+                    _ => 0f32,
+                };
+                for edge in &mut graph.vertex_mut(u).connection {
+                    if edge.neighbor == v && edge.threshold > 0f32 {
+                        edge.threshold = threshold;
+                    }
+                }
+            }
+        }
+
+        // Sentinels the removed nodes left on the nodes they overlapped
+        for node in 0..graph.node_count() {
+            let other = &graph.nodes[node];
+            if !removed.iter().any(|removed| {
+                removed.origin.distance(&other.origin) <= removed.radius + other.radius
+            }) {
+                continue;
+            }
+            for id in graph.node_vertices(node) {
+                let vertex = graph.vertex_mut(id);
+                if matches!(vertex.owner, Some(owner) if removed_vertices.contains(&owner)) {
+                    vertex.sentinel = false;
+                    vertex.owner = None;
+                }
+            }
+        }
+        debug!(vertices = graph.vertex_count(), "removed obstacle");
+        self.graph = Arc::new(graph);
+        obstacle
+    }
+}
+
+#[cfg(test)]
+mod test {
+    use super::*;
+    use fixture::{square_flyzone, wall};
+
+    // Route of a leg across the square flyzone, compared between incremental and rebuilt graphs
+    fn assert_same_path(incremental: &mut Tanstar, obstacles: Vec<Obstacle>) {
+        let mut rebuilt = Tanstar::new();
+        rebuilt
+            .init(
+                TConfig::default(),
+                incremental.flyzones.clone(),
+                obstacles.clone(),
+            )
+            .unwrap();
+        assert_eq!(incremental.obstacles, obstacles);
+        assert_eq!(incremental.graph.node_count(), rebuilt.graph.node_count());
+        let origin = rebuilt.origin;
+        let start = Location::from_meters(50f32, 200f32, 10f32, &origin);
+        let end = Location::from_meters(350f32, 220f32, 10f32, &origin);
+        let a = incremental.adjust_path::<()>(start, end).unwrap();
+        let b = rebuilt.adjust_path::<()>(start, end).unwrap();
+        // waypoints in line with a tangent may be kept or merged, so compare the routes
+        let length = |path: LinkedList<Waypoint<()>>| {
+            let mut points = vec![Point::from((&start, &origin))];
+            points.extend(path.iter().map(|wp| Point::from((&wp.location, &origin))));
+            points.push(Point::from((&end, &origin)));
+            points.windows(2).map(|p| p[0].distance(&p[1])).sum::<f32>()
+        };
+        assert!((length(a) - length(b)).abs() < 0.1f32);
+
+        // every edge is indexed, and sentinels only remain where the nodes overlap
+        let edges = |tanstar: &Tanstar| {
+            let graph = &tanstar.graph;
+            let mut edges: Vec<(VertexId, VertexId)> = (0..graph.node_count())
+                .flat_map(|node| graph.node_vertices(node))
+                .flat_map(|u| {
+                    graph
+                        .vertex(u)
+                        .connection
+                        .iter()
+                        .map(move |e| (u, e.neighbor))
+                })
+                .collect();
+            edges.sort_unstable();
+            edges.dedup();
+            edges
+        };
+        let center = Point::new(200f32, 200f32, 0f32);
+        assert_eq!(
+            incremental.index.tangents_near(&center, 1000f32),
+            edges(incremental)
+        );
+        let overlaps = |tanstar: &Tanstar| {
+            let graph = &tanstar.graph;
+            (0..graph.node_count())
+                .flat_map(|node| graph.node_vertices(node))
+                .filter(|&id| graph.vertex(id).owner.is_some())
+                .count()
+        };
+        assert_eq!(overlaps(incremental), overlaps(&rebuilt));
+    }
+
+    #[test]
+    fn incremental_obstacles_test() {
+        let origin = Location::from_degrees(0f64, 0f64, 0f32);
+        let wall = wall(&origin, 150f32);
+        // overlapping the wall
+        let post = Obstacle::new(
+            Location::from_meters(260f32, 230f32, 50f32, &origin),
+            20f32,
+            150f32,
+        );
+        let prism = Obstacle::prism(
+            vec![
+                Location::from_meters(100f32, 150f32, 0f32, &origin),
+                Location::from_meters(130f32, 150f32, 0f32, &origin),
+                Location::from_meters(130f32, 250f32, 0f32, &origin),
+            ],
+            150f32,
+        );
+        let mut tanstar = Tanstar::new();
+        tanstar
+            .init(TConfig::default(), square_flyzone(&origin), Vec::new())
+            .unwrap();
+
+        tanstar.add_obstacle(wall.clone()).unwrap();
+        assert_same_path(&mut tanstar, vec![wall.clone()]);
+        tanstar.add_obstacle(post.clone()).unwrap();
+        tanstar.add_obstacle(prism.clone()).unwrap();
+        assert_same_path(
+            &mut tanstar,
+            vec![wall.clone(), post.clone(), prism.clone()],
+        );
+
+        assert_eq!(tanstar.remove_obstacle(0), Ok(wall.clone()));
+        assert_same_path(&mut tanstar, vec![post.clone(), prism.clone()]);
+
+        let moved = Obstacle::new(
+            Location::from_meters(220f32, 180f32, 50f32, &origin),
+            40f32,
+            150f32,
+        );
+        assert_eq!(tanstar.update_obstacle(0, moved.clone()), Ok(post));
+        assert_same_path(&mut tanstar, vec![moved.clone(), prism.clone()]);
+
+        // rejected updates leave the graph untouched
+        assert_eq!(
+            tanstar.remove_obstacle(2),
+            Err(PathfinderError::UnknownObstacle { obstacle: 2 })
+        );
+        let outside = Obstacle::new(
+            Location::from_meters(500f32, 200f32, 50f32, &origin),
+            20f32,
+            150f32,
+        );
+        assert_eq!(
+            tanstar.add_obstacle(outside.clone()),
+            Err(PathfinderError::ObstacleOutsideFlyzone { obstacle: 2 })
+        );
+        assert_eq!(
+            tanstar.update_obstacle(1, outside),
+            Err(PathfinderError::ObstacleOutsideFlyzone { obstacle: 1 })
+        );
+        assert_same_path(&mut tanstar, vec![moved, prism]);
+    }
+}
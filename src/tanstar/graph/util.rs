@@ -3,19 +3,34 @@
 
 use super::*;
 
+use std::fmt::Write;
+
 impl Tanstar {
     // Helper function to create and init tanstar object
-    pub fn create(
+    pub fn create<F: Into<Flyzone>>(
         buffer_size: f32,
-        flyzones: Vec<Vec<Location>>,
+        flyzones: Vec<F>,
         obstacles: Vec<Obstacle>,
     ) -> Self {
         let mut pathfinder = Self::new();
-        let mut config = TConfig::default();
-        config.buffer_size = buffer_size;
-        pathfinder.init(config, flyzones, obstacles);
+        let config = TConfig {
+            buffer_size,
+            ..Default::default()
+        };
+        if let Err(e) = pathfinder.init(
+            config,
+            flyzones.into_iter().map(Into::into).collect(),
+            obstacles,
+        ) {
+            panic!("{}", e);
+        }
         pathfinder
     }
+
+    // Opt-in dump of the vertices in the graph, see output_graph
+    pub fn dump_graph(&self) -> String {
+        output_graph(self)
+    }
 }
 
 // helper function to remap angle between range
@@ -117,6 +132,31 @@ pub fn intersect(a: &Point, b: &Point, c: &Point, d: &Point) -> bool {
     }
 }
 
+// determine if point is inside the polygon using ray casting
+// returns true if a ray from the point crosses the polygon edges an odd number of times
+pub fn inside_polygon(point: &Point, polygon: &[Point]) -> bool {
+    let mut inside = false;
+    let mut j = polygon.len() - 1;
+    for i in 0..polygon.len() {
+        let (a, b) = (&polygon[i], &polygon[j]);
+        if (a.y > point.y) != (b.y > point.y)
+            && point.x < (b.x - a.x) * (point.y - a.y) / (b.y - a.y) + a.x
+        {
+            inside = !inside;
+        }
+        j = i;
+    }
+    inside
+}
+
+// determine if segment ab crosses an edge of the polygon or lies inside of it
+pub fn intersect_polygon(a: &Point, b: &Point, polygon: &[Point]) -> bool {
+    let n = polygon.len();
+    let crosses = (0..n).any(|i| intersect(a, b, &polygon[i], &polygon[(i + 1) % n]));
+    let mid = Point::new((a.x + b.x) / 2f32, (a.y + b.y) / 2f32, 0f32);
+    crosses || inside_polygon(&mid, polygon)
+}
+
 // calculate distance of shortest distance from point c to a segment defined by a and b
 // returns x, y of intersection, distance SQUARED, and whether intersection is at endpoint
 #[allow(clippy::many_single_char_names)]
@@ -148,63 +188,33 @@ pub fn intersect_distance(a: &Point, b: &Point, c: &Point) -> (f32, f32, f32, bo
     (x, y, (x - c.x).powi(2) + (y - c.y).powi(2), endpoint)
 }
 
-fn output_ring(origin: &Location, mut current: Rc<RefCell<Vertex>>) {
-    let temp = match current.borrow().next {
-        Some(ref v) => v.clone(),
-        None => panic!("Next points to null"),
-    };
-    current = temp;
-    let mut count = 0;
-    loop {
-        let vertex = &current.clone();
-        if vertex.borrow().index != HEADER_VERTEX_INDEX {
-            count += 1;
-            let v_loc = Location::from((&vertex.borrow().location, origin));
-            println!("{}, {}", v_loc.lat_degree(), v_loc.lon_degree());
-        } else {
-            break;
-        }
-        current = match vertex.borrow().next {
-            Some(ref v) => v.clone(),
-            None => panic!("Next points to null"),
-        };
+fn output_ring(out: &mut String, origin: &Location, graph: &Graph, header: VertexId) {
+    for vertex in graph.ring_vertices(header) {
+        let v_loc = Location::from((&graph.vertex(vertex).location, origin));
+        let _ = writeln!(out, "{}, {}", v_loc.lat_degree(), v_loc.lon_degree());
     }
-    // println!("Node vertex count {}", count);
 }
 
-// Debug method to output vertices
-pub fn output_graph(finder: &Tanstar) {
-    println!("\n------------------------------");
-    println!("pathfinder graph");
-    println!("node count: {}", finder.nodes.len());
-    println!("vertex count: {}\n", finder.num_vertices);
-    println!("---- Node List ----");
-    // for node in &finder.nodes {
-    //     let loc = Location::from((&node.borrow().origin, &finder.origin));
-    //     println!("{}, {}", loc.lat_degree(), loc.lon_degree());
-    // }
-    //println!("\n---- Left Vertex List ----");
-
-    for node in &finder.nodes {
-        // let loc = Location::from((&node.borrow().origin, &finder.origin));
-        // println!("Node origin {:?}", node.borrow().origin);
-        if node.borrow().height > 0f32 {
-            output_ring(&finder.origin, node.borrow().left_ring.clone());
-        }
-    }
-
-    //println!("\n---- Right Vertex List ----");
-
-    for node in &finder.nodes {
-        // let loc = Location::from((&node.borrow().origin, &finder.origin));
-        // let loc = node.borrow().origin;
-        // println!("Node origin {:?}", node.borrow().origin);
-        if node.borrow().height > 0f32 {
-            output_ring(&finder.origin, node.borrow().right_ring.clone());
+// Debug method to output vertices of every obstacle node, left rings then right rings
+pub fn output_graph(finder: &Tanstar) -> String {
+    let graph = &finder.graph;
+    let mut out = String::new();
+    let _ = writeln!(out, "------------------------------");
+    let _ = writeln!(out, "pathfinder graph");
+    let _ = writeln!(out, "node count: {}", graph.node_count());
+    let _ = writeln!(out, "vertex count: {}", graph.vertex_count());
+    let _ = writeln!(out, "---- Node List ----");
+
+    for left in &[true, false] {
+        for (i, node) in graph.nodes.iter().enumerate() {
+            if node.height > 0f32 {
+                output_ring(&mut out, &finder.origin, graph, graph.ring(i, *left));
+            }
         }
     }
 
-    println!("------------------------------");
+    let _ = writeln!(out, "------------------------------");
+    out
 }
 
 // find the intersection of line ab with obstacle c, if they exist
@@ -220,11 +230,6 @@ pub fn perpendicular_intersect(
     // #TODO: endpoint not used, why is it here?
     let (x, y, distance, _endpoint) = intersect_distance(a, b, &Point::from((&c.location, origin)));
     if distance.sqrt() < c.radius as f32 {
-        println!(
-            "intersect with obstacle: dist {} r {}",
-            distance.sqrt(),
-            c.radius
-        );
         // immediately check if the endpoint is the shortest distance; can't fly over in this case
         // EXCEPTION: endpoint is inside obstacle but still generates a perpendicular.
         // if endpoint {
@@ -415,6 +420,57 @@ mod test {
         assert_eq!(intersect(&c, &d, &a, &b), false);
     }
 
+    #[test]
+    fn inside_polygon_test() {
+        let polygon = vec![
+            Point::new(0f32, 0f32, 0f32),
+            Point::new(0f32, 20f32, 0f32),
+            Point::new(10f32, 10f32, 0f32),
+            Point::new(20f32, 20f32, 0f32),
+            Point::new(20f32, 0f32, 0f32),
+        ];
+        assert!(inside_polygon(&Point::new(5f32, 5f32, 0f32), &polygon));
+        assert!(inside_polygon(&Point::new(15f32, 12f32, 0f32), &polygon));
+        assert!(!inside_polygon(&Point::new(10f32, 15f32, 0f32), &polygon));
+        assert!(!inside_polygon(&Point::new(25f32, 5f32, 0f32), &polygon));
+    }
+
+    #[test]
+    fn intersect_polygon_test() {
+        let polygon = vec![
+            Point::new(10f32, 10f32, 0f32),
+            Point::new(10f32, 20f32, 0f32),
+            Point::new(20f32, 20f32, 0f32),
+            Point::new(20f32, 10f32, 0f32),
+        ];
+        let p = |x, y| Point::new(x, y, 0f32);
+        assert!(intersect_polygon(
+            &p(0f32, 15f32),
+            &p(30f32, 15f32),
+            &polygon
+        ));
+        assert!(intersect_polygon(
+            &p(0f32, 15f32),
+            &p(15f32, 15f32),
+            &polygon
+        ));
+        assert!(intersect_polygon(
+            &p(12f32, 12f32),
+            &p(18f32, 18f32),
+            &polygon
+        ));
+        assert!(!intersect_polygon(
+            &p(0f32, 25f32),
+            &p(30f32, 25f32),
+            &polygon
+        ));
+        assert!(!intersect_polygon(
+            &p(0f32, 0f32),
+            &p(0f32, 30f32),
+            &polygon
+        ));
+    }
+
     #[test]
     fn special_intersect() {
         let a = Point::new(0f32, 0f32, 10f32);
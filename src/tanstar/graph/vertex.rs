@@ -1,77 +1,48 @@
 use super::*;
 
-use std::cmp::Ordering;
 use std::fmt;
-use std::hash::Hash;
-use std::hash::Hasher;
 
-#[derive(Debug)]
-pub struct Vertex {
-    pub index: i32,                          // Index to identify vertex
-    pub radius: f32,                         // Radius of the node vertex is attached to
-    pub location: Point,                     // Location of the vertex
-    pub angle: f32,                          // Angle with respect to the node
-    pub g_cost: f32,                         //
-    pub f_cost: f32,                         //
-    pub parent: Option<Rc<RefCell<Vertex>>>, // Parent of vertex
-    pub connection: Vec<Connection>,         // Edge connecting to another node
-    pub prev: Option<Rc<RefCell<Vertex>>>,   // Previous neighbor vertex in the same node
-    pub next: Option<Rc<RefCell<Vertex>>>,   // Neighbor vertex in the same node
-    pub sentinel: bool,                      // Sentinel property marks end of path hugging
-}
-
-impl Hash for Vertex {
-    fn hash<H: Hasher>(&self, state: &mut H) {
-        self.index.hash(state);
-    }
-}
-
-impl Eq for Vertex {}
+// Index of a vertex in the arena of the graph, or in the search for vertices added to a leg
+pub type VertexId = usize;
 
-impl PartialEq for Vertex {
-    fn eq(&self, other: &Self) -> bool {
-        self.index == other.index
-    }
+#[derive(Clone, Copy, Debug, PartialEq)]
+pub enum VertexKind {
+    Ring,   // Vertex on the ring of a node
+    Header, // Head of a ring, marks where the ring wraps around
+    Start,  // Location the leg starts from
+    End,    // Location the leg ends at
 }
 
-impl Ord for Vertex {
-    fn cmp(&self, other: &Self) -> Ordering {
-        // Order flipped for max heap
-        if self.f_cost < other.f_cost {
-            Ordering::Greater
-        } else if self.f_cost > other.f_cost {
-            Ordering::Less
-        } else {
-            Ordering::Equal
-        }
-    }
-}
-
-impl PartialOrd for Vertex {
-    fn partial_cmp(&self, other: &Self) -> Option<Ordering> {
-        Some(self.cmp(other))
-    }
+#[derive(Clone, Debug)]
+pub struct Vertex {
+    pub kind: VertexKind,
+    pub radius: f32,                 // Radius of the node vertex is attached to
+    pub location: Point,             // Location of the vertex
+    pub angle: f32,                  // Angle with respect to the node
+    pub connection: Vec<Connection>, // Edge connecting to another node
+    pub prev: Option<VertexId>,      // Previous neighbor vertex in the same node
+    pub next: Option<VertexId>,      // Neighbor vertex in the same node
+    pub sentinel: bool,              // Sentinel property marks end of path hugging
+    pub owner: Option<VertexId>,     // Left ring header of the node overlap sentinels come from
 }
 
 impl fmt::Display for Vertex {
     fn fmt(&self, f: &mut fmt::Formatter) -> fmt::Result {
         write!(
             f,
-            "(index={}, angle={}, connection={} next={} g_cost={} f_cost={})",
-            self.index,
+            "(kind={:?}, angle={}, connection={} next={:?})",
+            self.kind,
             self.angle,
             self.connection.len(),
-            self.next.is_some(),
-            self.g_cost,
-            self.f_cost,
+            self.next,
         )
     }
 }
 
 impl Vertex {
-    pub fn new(num_vertex: &mut i32, node: &Node, angle: f32, connection: Vec<Connection>) -> Self {
+    pub fn new(node: &Node, angle: f32, connection: Vec<Connection>) -> Self {
         Self::base_vertex(
-            num_vertex,
+            VertexKind::Ring,
             node.radius,
             angle,
             Point::from((node, angle)),
@@ -80,9 +51,9 @@ impl Vertex {
         )
     }
 
-    pub fn new_sentinel(num_vertex: &mut i32, node: &Node, angle: f32) -> Self {
+    pub fn new_sentinel(node: &Node, angle: f32) -> Self {
         Self::base_vertex(
-            num_vertex,
+            VertexKind::Ring,
             node.radius,
             angle,
             Point::from((node, angle)),
@@ -91,42 +62,50 @@ impl Vertex {
         )
     }
 
-    pub fn new_head(num_vertex: &mut i32, origin: Point) -> Self {
-        Self::base_vertex(num_vertex, 0f32, 0f32, origin, vec![], false)
+    // Sentinel where node overlaps the node of the ring header owner
+    pub fn new_overlap(node: &Node, angle: f32, owner: VertexId) -> Self {
+        Self {
+            owner: Some(owner),
+            ..Self::new_sentinel(node, angle)
+        }
+    }
+
+    pub fn new_start(node: &Node, angle: f32) -> Self {
+        Self {
+            kind: VertexKind::Start,
+            ..Self::new(node, angle, vec![])
+        }
+    }
+
+    pub fn new_end(node: &Node, angle: f32) -> Self {
+        Self {
+            kind: VertexKind::End,
+            ..Self::new(node, angle, vec![])
+        }
+    }
+
+    pub fn new_head(origin: Point) -> Self {
+        Self::base_vertex(VertexKind::Header, 0f32, 0f32, origin, vec![], false)
     }
 
     fn base_vertex(
-        num_vertex: &mut i32,
+        kind: VertexKind,
         radius: f32,
         angle: f32,
         location: Point,
         connection: Vec<Connection>,
         sentinel: bool,
     ) -> Self {
-        if *num_vertex >= 0 {
-            *num_vertex += 1;
-        }
-
         Self {
-            index: *num_vertex,
+            kind,
             radius,
             angle,
             location,
-            f_cost: -1f32,
-            g_cost: -1f32,
-            parent: None,
             connection,
             prev: None,
             next: None,
             sentinel,
-        }
-    }
-
-    pub fn get_neighbor_weight(&self) -> f32 {
-        if let Some(ref neighbor) = self.next {
-            return arc_length(self.angle, neighbor.borrow().angle, self.radius);
-        } else {
-            panic!("broken chain");
+            owner: None,
         }
     }
 }
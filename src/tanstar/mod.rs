@@ -1,48 +1,62 @@
 // mod.rs
 // contains main functionality of the library
+#[cfg(feature = "rayon")]
+use super::algorithm::Leg;
 use super::obj::*;
 use super::Algorithm;
+use super::{PathError, PathfinderError};
 
 pub mod config;
 
+mod cost;
+mod dubins;
 mod graph;
 mod queue;
+mod search;
+mod track;
 
 pub use self::config::*;
+pub use self::cost::*;
+pub use self::graph::{Graph, Point};
+// Geometry shared with the grid based planners
+pub(crate) use self::graph::{
+    inside_polygon, intersect, intersect_distance, intersect_polygon, perpendicular_intersect,
+};
 
 use self::graph::*;
 use self::queue::Queue;
-use std::cell::RefCell;
+use self::search::Search;
+use self::track::Track;
+#[cfg(feature = "rayon")]
+use rayon::prelude::*;
 use std::collections::{BinaryHeap, HashSet, LinkedList};
 use std::f32::consts::PI;
-use std::rc::Rc;
+#[cfg(feature = "rayon")]
+use std::sync::atomic::{AtomicUsize, Ordering};
+use std::sync::Arc;
 use std::time::SystemTime;
+use tracing::Level;
 
 // const EQUATORIAL_RADIUS: f64 = 63781370.0;
 // const POLAR_RADIUS: f64 = 6356752.0;
 const RADIUS: f64 = 6_371_000.0;
 
 // Plane properties
-// const MAX_ANGLE: f32 = PI / 6f32;
-// const MAX_ANGLE_ASCENT: f32 = PI / 3f32;
-// const MAX_ANGLE_DESCENT: f32 = -PI / 3f32;
-
-const START_VERTEX_INDEX: i32 = -1;
-const END_VERTEX_INDEX: i32 = -2;
-const HEADER_VERTEX_INDEX: i32 = -3;
 
+// Clones share the built graph, which is only copied when one of them updates its obstacles
 #[allow(non_snake_case)]
+#[derive(Clone)]
 pub struct Tanstar {
     // Configuration options
     config: TConfig,
-    flyzones: Vec<Vec<Location>>,
+    flyzones: Vec<Flyzone>,
     obstacles: Vec<Obstacle>,
     // private
     initialized: bool,
-    start_time: SystemTime,
     origin: Location, // Reference point defining each node
-    nodes: Vec<Rc<RefCell<Node>>>,
-    num_vertices: i32,
+    graph: Arc<Graph>,
+    index: SpatialIndex, // Static obstacles and flyzone edges by area
+    cost_model: Option<Arc<dyn CostModel>>, // Overrides the cost function of the config
 }
 
 impl Default for Tanstar {
@@ -54,10 +68,10 @@ impl Default for Tanstar {
             obstacles: Vec::new(),
             // private
             initialized: false,
-            start_time: SystemTime::now(),
             origin: Location::from_degrees(0f64, 0f64, 0f32),
-            nodes: Vec::new(),
-            num_vertices: 0i32,
+            graph: Arc::new(Graph::new()),
+            index: SpatialIndex::default(),
+            cost_model: None,
         }
     }
 }
@@ -67,35 +81,144 @@ impl Tanstar {
         Tanstar::default()
     }
 
+    // Graph built from the flyzones and obstacles, shared with the clones of the planner
+    pub fn graph(&self) -> Arc<Graph> {
+        self.graph.clone()
+    }
+
     // determine if flyzone intersects itself (correct order)
-    // inputs (flyzones, origin), outputs true if invalid
+    // inputs (flyzones, origin), outputs (flyzone, edge, edge) of the first intersection found
+    fn invalid_flyzone(flyzones: &[Flyzone], origin: &Location) -> Option<(usize, usize, usize)> {
+        flyzones.iter().enumerate().find_map(|(index, flyzone)| {
+            Self::self_intersection(&flyzone.boundary, origin).map(|(ab, cd)| (index, ab, cd))
+        })
+    }
+
+    // First pair of non-adjacent edges of the polygon that cross each other
     #[allow(clippy::many_single_char_names)]
-    fn invalid_flyzone(flyzones: &[Vec<Location>], origin: &Location) -> bool {
-        for flyzone in flyzones {
-            let mut vertices = Vec::new();
-            for loc in flyzone {
-                let point = Point::from((loc, origin));
-                vertices.push(point);
+    fn self_intersection(polygon: &[Location], origin: &Location) -> Option<(usize, usize)> {
+        let mut vertices = Vec::new();
+        for loc in polygon {
+            let point = Point::from((loc, origin));
+            vertices.push(point);
+        }
+        let n = vertices.len();
+        // compares any side of flyzone, ab, with any non-adjacent side, cd
+        for ab in 0..n - 2 {
+            let a = vertices[ab];
+            let b = vertices[ab + 1];
+            for i in 2..n - 1 {
+                let cd = ab + i;
+                let c = vertices[cd];
+                let d = vertices[(cd + 1) % n];
+                if intersect(&a, &b, &c, &d) {
+                    return Some((ab, cd));
+                }
+                if cd + 1 == n {
+                    break;
+                }
             }
-            let n = vertices.len();
-            // compares any side of flyzone, ab, with any non-adjacent side, cd
-            for ab in 0..n - 2 {
-                let a = vertices[ab];
-                let b = vertices[ab + 1];
-                for i in 2..n - 1 {
-                    let cd = ab + i;
-                    let c = vertices[cd];
-                    let d = vertices[(cd + 1) % n];
-                    if intersect(&a, &b, &c, &d) {
-                        return true;
-                    }
-                    if cd + 1 == n {
-                        break;
-                    }
+        }
+        None
+    }
+
+    // Check flyzones and their keep-outs are non empty polygons that do not intersect themselves,
+    // with a floor no higher than their ceiling
+    pub(crate) fn validate_flyzones(flyzones: &[Flyzone]) -> Result<(), PathfinderError> {
+        if flyzones.is_empty() {
+            return Err(PathfinderError::EmptyFlyzone);
+        }
+        for (i, flyzone) in flyzones.iter().enumerate() {
+            if flyzone.boundary.len() < 3 {
+                return Err(PathfinderError::TooFewVertices {
+                    flyzone: i,
+                    count: flyzone.boundary.len(),
+                });
+            }
+            if flyzone.min_alt.is_nan()
+                || flyzone.max_alt.is_nan()
+                || flyzone.min_alt > flyzone.max_alt
+            {
+                return Err(PathfinderError::InvalidAltitudes { flyzone: i });
+            }
+        }
+        let origin = Self::find_origin(flyzones);
+        if let Some((flyzone, a, b)) = Self::invalid_flyzone(flyzones, &origin) {
+            return Err(PathfinderError::SelfIntersection {
+                flyzone,
+                edges: (a, b),
+            });
+        }
+        for (i, flyzone) in flyzones.iter().enumerate() {
+            for (k, keep_out) in flyzone.keep_outs.iter().enumerate() {
+                if keep_out.len() < 3 {
+                    return Err(PathfinderError::TooFewKeepOutVertices {
+                        flyzone: i,
+                        keep_out: k,
+                        count: keep_out.len(),
+                    });
+                }
+                if let Some(edges) = Self::self_intersection(keep_out, &origin) {
+                    return Err(PathfinderError::KeepOutSelfIntersection {
+                        flyzone: i,
+                        keep_out: k,
+                        edges,
+                    });
+                }
+            }
+        }
+        Ok(())
+    }
+
+    // Check every obstacle is located inside the (already validated) flyzones
+    // Every vertex of a prism must be inside, and a prism needs at least 3 vertices
+    pub(crate) fn validate_obstacles(
+        flyzones: &[Flyzone],
+        obstacles: &[Obstacle],
+    ) -> Result<(), PathfinderError> {
+        let origin = Self::find_origin(flyzones);
+        for (i, obstacle) in obstacles.iter().enumerate() {
+            Self::validate_obstacle(flyzones, &origin, i, obstacle)?;
+        }
+        Ok(())
+    }
+
+    // Check a single obstacle, reported at index
+    pub(crate) fn validate_obstacle(
+        flyzones: &[Flyzone],
+        origin: &Location,
+        index: usize,
+        obstacle: &Obstacle,
+    ) -> Result<(), PathfinderError> {
+        // Positions between two locations of a trajectory are interpolated by time
+        if let Motion::Trajectory(ref trajectory) = obstacle.motion {
+            let increasing = trajectory.windows(2).all(|pair| pair[0].0 < pair[1].0);
+            if trajectory.len() < 2 || !increasing {
+                return Err(PathfinderError::InvalidTrajectory { obstacle: index });
+            }
+        }
+        // Moving obstacles may enter and leave the flyzones
+        if obstacle.is_moving() {
+            return Ok(());
+        }
+        let inside = match obstacle.shape {
+            Shape::Cylinder => Self::inside_flyzones(flyzones, origin, &obstacle.location),
+            Shape::Prism(ref vertices) => {
+                if vertices.len() < 3 {
+                    return Err(PathfinderError::TooFewObstacleVertices {
+                        obstacle: index,
+                        count: vertices.len(),
+                    });
                 }
+                vertices
+                    .iter()
+                    .all(|v| Self::inside_flyzones(flyzones, origin, v))
             }
+        };
+        if !inside {
+            return Err(PathfinderError::ObstacleOutsideFlyzone { obstacle: index });
         }
-        false
+        Ok(())
     }
 }
 
@@ -105,292 +228,854 @@ impl Algorithm for Tanstar {
     fn init(
         &mut self,
         config: Self::Config,
-        flyzones: Vec<Vec<Location>>,
+        flyzones: Vec<Flyzone>,
         obstacles: Vec<Obstacle>,
-    ) {
-        // Flyzone validation
-        assert!(!flyzones.is_empty());
-        for flyzone in &flyzones {
-            assert!(flyzone.len() >= 3);
-        }
-        assert!(!Self::invalid_flyzone(
-            &flyzones,
-            &Self::find_origin(&flyzones)
-        ));
+    ) -> Result<(), PathfinderError> {
+        config.validate()?;
+        Self::validate_flyzones(&flyzones)?;
+        Self::validate_obstacles(&flyzones, &obstacles)?;
 
-        self.config = config;
-        self.flyzones = flyzones;
-        self.obstacles = obstacles;
-        self.build_graph();
-        self.initialized = true;
+        // Keep the planner as it was if the graph can not be built
+        let mut built = self.rebuilt(config, flyzones, obstacles)?;
+        built.initialized = true;
+        *self = built;
+        Ok(())
     }
 
-    // Find best path using the a* algorithm
-    // Return path if found and none if any error occured or no path found
     fn adjust_path<T>(
         &mut self,
         start: Location,
         end: Location,
-    ) -> Option<LinkedList<Waypoint<T>>> {
-        let mut path = None;
-        let mut open_set = Queue::new(); // candidate vertices
-        let mut close_set: HashSet<i32> = HashSet::new(); // set of vertex already visited
-
-        let start_node = Rc::new(RefCell::new(Node::from((
-            &start,
-            &self.origin,
-            self.config.turning_radius,
-        ))));
-        let end_node = Rc::new(RefCell::new(Node::from((
-            &end,
-            &self.origin,
-            self.config.turning_radius,
-        ))));
+    ) -> Result<LinkedList<Waypoint<T>>, PathError> {
+        self.adjust_path_at(start, end, 0f32).map(|(path, _)| path)
+    }
+
+    fn adjust_path_at<T>(
+        &mut self,
+        start: Location,
+        end: Location,
+        departure: f32,
+    ) -> Result<(LinkedList<Waypoint<T>>, f32), PathError> {
+        self.adjust_leg(start, None, end, departure)
+    }
+
+    // Start on the turning circles of the plane if its heading is known
+    fn adjust_path_from<T>(
+        &mut self,
+        plane: &Plane,
+        end: Location,
+        departure: f32,
+    ) -> Result<(LinkedList<Waypoint<T>>, f32), PathError> {
+        self.adjust_leg(plane.location, Self::heading(plane), end, departure)
+    }
+
+    // Smooth the path into Dubins curves if enabled in the config
+    fn smooth_path<T>(
+        &self,
+        plane: &Plane,
+        path: LinkedList<Waypoint<T>>,
+    ) -> LinkedList<Waypoint<T>> {
+        if self.config.dubins && !path.is_empty() {
+            self.smooth_dubins(plane, path)
+        } else {
+            path
+        }
+    }
+
+    // Legs only depend on each other through the arrival times moving obstacles are checked at,
+    // without them the legs are searched in parallel on the rayon thread pool
+    // Legs after a failed one are skipped and dropped, the mission stops at the first failure as
+    // when the legs are adjusted one after the other
+    #[cfg(feature = "rayon")]
+    fn adjust_legs(&self, plane: &Plane, goals: &[Location]) -> Option<Vec<Leg>> {
+        if self.obstacles.iter().any(Obstacle::is_moving) {
+            return None;
+        }
+        let heading = Self::heading(plane);
+        let failed = AtomicUsize::new(goals.len());
+        let mut legs: Vec<Option<Leg>> = (0..goals.len())
+            .into_par_iter()
+            .map(|i| {
+                if i > failed.load(Ordering::Relaxed) {
+                    return None;
+                }
+                let span = info_span!("adjust_path", waypoint = i);
+                let _enter = span.enter();
+                let leg = if i == 0 {
+                    self.adjust_leg(plane.location, heading, goals[0], 0f32)
+                } else {
+                    self.adjust_leg(goals[i - 1], None, goals[i], 0f32)
+                };
+                if leg.is_err() {
+                    failed.fetch_min(i, Ordering::Relaxed);
+                }
+                Some(leg)
+            })
+            .collect();
+        // Every leg up to the first failure was adjusted, it only skips the ones after it
+        legs.truncate(failed.into_inner() + 1);
+        Some(
+            legs.into_iter()
+                .map(|leg| leg.expect("leg skipped"))
+                .collect(),
+        )
+    }
+
+    fn in_flyzone(&self, location: &Location) -> bool {
+        Self::inside_flyzones(&self.flyzones, &self.origin, location)
+    }
+
+    fn altitude_band(&self, location: &Location) -> Option<(f32, f32)> {
+        Self::flyzone_altitudes(&self.flyzones, &self.origin, location)
+    }
+
+    fn get_config(&self) -> &Self::Config {
+        &self.config
+    }
+
+    fn get_flyzone(&mut self) -> &Vec<Flyzone> {
+        &self.flyzones
+    }
+
+    fn get_obstacles(&self) -> &Vec<Obstacle> {
+        &self.obstacles
+    }
+
+    fn set_config(&mut self, config: Self::Config) -> Result<(), PathfinderError> {
+        config.validate()?;
+        *self = self.rebuilt(config, self.flyzones.clone(), self.obstacles.clone())?;
+        Ok(())
+    }
+
+    fn set_flyzone(&mut self, flyzone: Vec<Flyzone>) -> Result<(), PathfinderError> {
+        Self::validate_flyzones(&flyzone)?;
+        Self::validate_obstacles(&flyzone, &self.obstacles)?;
+        *self = self.rebuilt(self.config.clone(), flyzone, self.obstacles.clone())?;
+        Ok(())
+    }
+
+    fn set_obstacles(&mut self, obstacles: Vec<Obstacle>) -> Result<(), PathfinderError> {
+        Self::validate_obstacles(&self.flyzones, &obstacles)?;
+        *self = self.rebuilt(self.config.clone(), self.flyzones.clone(), obstacles)?;
+        Ok(())
+    }
+
+    fn add_obstacle(&mut self, obstacle: Obstacle) -> Result<(), PathfinderError> {
+        let index = self.obstacles.len();
+        Self::validate_obstacle(&self.flyzones, &self.origin, index, &obstacle)?;
+        self.insert_obstacle(index, obstacle);
+        Ok(())
+    }
+
+    fn remove_obstacle(&mut self, index: usize) -> Result<Obstacle, PathfinderError> {
+        if index >= self.obstacles.len() {
+            return Err(PathfinderError::UnknownObstacle { obstacle: index });
+        }
+        Ok(self.remove_obstacle_at(index))
+    }
+
+    fn update_obstacle(
+        &mut self,
+        index: usize,
+        obstacle: Obstacle,
+    ) -> Result<Obstacle, PathfinderError> {
+        if index >= self.obstacles.len() {
+            return Err(PathfinderError::UnknownObstacle { obstacle: index });
+        }
+        Self::validate_obstacle(&self.flyzones, &self.origin, index, &obstacle)?;
+        let replaced = self.remove_obstacle_at(index);
+        self.insert_obstacle(index, obstacle);
+        Ok(replaced)
+    }
+}
+
+impl Tanstar {
+    // Find best path using the a* algorithm
+    // Waypoints are kept within the altitude limits of their flyzone
+    // With a heading, the plane leaves the start turning on one of its turning circles
+    // Moving obstacles are checked at the time the plane flies each edge, flying at cruise speed
+    // The graph is only read, vertices and costs of the leg are kept in a search of its own
+    // Return path and arrival time if found and the reason of failure otherwise
+    fn adjust_leg<T>(
+        &self,
+        start: Location,
+        heading: Option<f32>,
+        end: Location,
+        departure: f32,
+    ) -> Result<(LinkedList<Waypoint<T>>, f32), PathError> {
+        if let Some(obstacle) = self.inside_obstacle(&start) {
+            return Err(PathError::StartInsideObstacle { obstacle });
+        }
+        match self.altitude_band(&end) {
+            None => return Err(PathError::GoalOutsideFlyzone),
+            Some((min, max)) if end.alt() < min || end.alt() > max => {
+                return Err(PathError::GoalAltitudeOutsideFlyzone)
+            }
+            Some(_) => (),
+        }
+        if let Some(obstacle) = self.inside_obstacle(&end) {
+            return Err(PathError::GoalInsideObstacle { obstacle });
+        }
+
+        let start_time = SystemTime::now();
+        let mut path = Err(PathError::OpenSetExhausted);
+        let mut search = Search::new(&self.graph);
+
+        // Nodes the plane starts on, with the angle of the start on turning circles
+        let starts = match heading {
+            Some(heading) => self.turning_circles(&Point::from((&start, &self.origin)), heading),
+            None => vec![(
+                Node::from((&start, &self.origin, self.config.ground_turning_radius())),
+                None,
+            )],
+        };
+        let end_node = Node::from((&end, &self.origin, self.config.ground_turning_radius()));
 
         let end_point = Point::from((&end, &self.origin));
+        let start_point = Point::from((&start, &self.origin));
+        let line = (&start_point, &end_point);
         let min_height = if start.alt() > end.alt() {
             end.alt()
         } else {
             start.alt()
         };
-        println!("Height threshold {}", min_height);
+        let climb = end.alt() - start.alt();
+        debug!(min_height, climb, departure, "searching path");
+
+        // Moving obstacles low enough to hit, and the time the plane reaches a vertex
+        let tracks: Vec<Track> = self
+            .obstacles
+            .iter()
+            .filter_map(|obstacle| Track::new(obstacle, &self.origin, self.config.buffer_size))
+            .filter(|track| min_height < track.height)
+            .collect();
+        let blocked = |a: &Point, b: &Point, time: f32, duration: f32| {
+            let t0 = departure + time;
+            tracks
+                .iter()
+                .any(|track| track.collides(a, b, t0, t0 + duration))
+        };
 
-        let temp_vertices = self.add_temp_vertices(
-            &start_node.borrow(),
-            &end_node.borrow(),
+        self.add_temp_vertices(
+            &mut search,
+            start_time,
+            &starts,
+            &end_node,
             min_height,
-            &end_point,
-            &mut open_set,
+            line,
+            &blocked,
         );
 
-        output_graph(&self);
-        println!("temporary vertices");
-        for vert in &temp_vertices {
-            let v_loc: Location = (&vert.borrow().location, &self.origin).into();
-            println!("{}, {}", v_loc.lat_degree(), v_loc.lon_degree());
+        if enabled!(Level::TRACE) {
+            trace!("graph dump\n{}", self.dump_graph());
         }
+        trace!(count = search.temp_count(), "inserted temporary vertices");
 
         //A* algorithm - find shortest path from plane to destination
-        while let Some(cur) = open_set.pop() {
-            assert!(cur.borrow().index != HEADER_VERTEX_INDEX);
-            println!("current vertex {}", cur.borrow());
-            if cur.borrow().index == END_VERTEX_INDEX {
-                path = Some(self.generate_waypoint::<T>(cur, start.alt.into(), end.alt.into()));
+        loop {
+            if self.time_exceeded(start_time) {
+                path = match Self::best_candidate(&search) {
+                    Some(end_vertex) if self.config.anytime => {
+                        warn!("exceeded max process time, returning best path found");
+                        self.climb_loops(
+                            &mut search,
+                            end_vertex,
+                            &start_point,
+                            heading,
+                            climb,
+                            min_height,
+                        )
+                        .map(|()| {
+                            let arrival = departure + search.reached(end_vertex).time;
+                            (
+                                self.generate_waypoint::<T>(
+                                    &search,
+                                    end_vertex,
+                                    start.alt.into(),
+                                    end.alt.into(),
+                                ),
+                                arrival,
+                            )
+                        })
+                    }
+                    _ => Err(PathError::ProcessTimeExceeded),
+                };
+                break;
+            }
+            let cur = match search.open_set.pop() {
+                Some(cur) => cur,
+                None => break,
+            };
+            // Stale entry of a vertex already expanded at a lower cost
+            if search.close_set.contains(&cur) {
+                continue;
+            }
+            assert!(search.vertex(cur).kind != VertexKind::Header);
+            let (g_cost, time) = {
+                let label = search.reached(cur);
+                (label.g_cost, label.time)
+            };
+            trace!(vertex = cur, g_cost, "expanding");
+            if search.vertex(cur).kind == VertexKind::End {
+                path = self
+                    .climb_loops(&mut search, cur, &start_point, heading, climb, min_height)
+                    .map(|()| {
+                        let arrival = departure + search.reached(cur).time;
+                        (
+                            self.generate_waypoint::<T>(
+                                &search,
+                                cur,
+                                start.alt.into(),
+                                end.alt.into(),
+                            ),
+                            arrival,
+                        )
+                    });
                 break;
             }
-            close_set.insert(cur.borrow().index);
-
-            let cur_vertex = cur.borrow();
-            let state = &mut (&mut open_set, &close_set, &cur, &end_point);
-            let g_cost = cur_vertex.g_cost;
-            for connection in &cur_vertex.connection {
-                // println!(
-                //     "Adding connection {} to queue",
-                //     connection.neighbor.borrow().index
-                // );
+            search.close_set.insert(cur);
+
+            let model = self.cost_model();
+            let (location, angle, radius) = {
+                let vertex = search.vertex(cur);
+                (vertex.location, vertex.angle, vertex.radius)
+            };
+            let connections = search.vertex(cur).connection.clone();
+            for connection in connections {
                 // Only add vertex if height meets threshold requirement
                 if min_height > connection.threshold {
-                    // println!("Met threshold requirement of {}", connection.threshold);
-                    let mut next = connection.neighbor.clone();
-                    let dist = connection.distance;
-                    Self::update_vertex(state, g_cost, next, dist);
+                    let next = connection.neighbor;
+                    let (a, b) = (location, search.vertex(next).location);
+                    let (dist, threshold) = (connection.distance, connection.threshold);
+                    let cost = model.tangent(&self.config, &a, &b, dist, threshold)
+                        + self.direct_cost(line, &a, &b, dist);
+                    let duration = flight_time(&self.config, &a, &b, dist);
+                    if blocked(&a, &b, time, duration) {
+                        continue;
+                    }
+                    self.update_vertex(&mut search, cur, next, &end_point, cost, duration);
                 }
             }
 
-            let mut weight = cur_vertex.get_neighbor_weight();
-            if let Some(ref next_vertex) = cur_vertex.next {
-                // println!("Adding neighbor {} to queue", next_vertex.borrow().index);
-                let mut next = next_vertex.clone();
-                // If next is header, skip to header neighbor
-                if next.borrow().index == HEADER_VERTEX_INDEX {
-                    weight += next.borrow().get_neighbor_weight();
-                    next = match next_vertex.borrow().next {
-                        Some(ref true_next) => true_next.clone(),
-                        None => panic!("broken chain"),
-                    }
-                }
+            let mut weight = search.neighbor_weight(cur);
+            let mut next = search.next(cur);
+            // If next is header, skip to header neighbor
+            if search.vertex(next).kind == VertexKind::Header {
+                weight += search.neighbor_weight(next);
+                next = search.next(next);
+            }
 
-                Self::update_vertex(state, g_cost, next, weight);
+            // Approximate the arc by its chord for moving obstacles
+            let turn = Self::turn(angle, weight, radius);
+            let (a, b) = (location, search.vertex(next).location);
+            let cost = model.arc(&self.config, angle, radius, turn)
+                + self.direct_cost(line, &a, &b, weight);
+            let duration = arc_flight_time(&self.config, angle, radius, turn);
+            if !blocked(&a, &b, time, duration) {
+                self.update_vertex(&mut search, cur, next, &end_point, cost, duration);
             }
         }
 
-        Node::prune_vertices(temp_vertices);
         path
     }
 
-    fn get_config(&self) -> &Self::Config {
-        &self.config
-    }
-
-    fn get_flyzone(&mut self) -> &Vec<Vec<Location>> {
-        &self.flyzones
-    }
-
-    fn get_obstacles(&self) -> &Vec<Obstacle> {
-        &self.obstacles
+    // Whether more than max_process_time has passed since start_time
+    fn time_exceeded(&self, start_time: SystemTime) -> bool {
+        match start_time.elapsed() {
+            Ok(elapsed) => elapsed > self.config.max_process_time,
+            Err(_) => false,
+        }
     }
 
-    fn set_config(&mut self, config: Self::Config) {
-        self.config = config;
-        self.build_graph();
+    // Find the cheapest end vertex that has already been reached by the search
+    fn best_candidate(search: &Search) -> Option<VertexId> {
+        let mut best: Option<(VertexId, f32)> = None;
+        for &end_vertex in &search.ends {
+            let g_cost = match search.label(end_vertex) {
+                Some(label) => label.g_cost,
+                None => continue,
+            };
+            let better = match best {
+                Some((_, b)) => g_cost < b,
+                None => true,
+            };
+            if better {
+                best = Some((end_vertex, g_cost));
+            }
+        }
+        best.map(|(end_vertex, _)| end_vertex)
     }
 
-    fn set_flyzone(&mut self, flyzone: Vec<Vec<Location>>) {
-        self.flyzones = flyzone;
-        self.build_graph();
+    // Find the first obstacle containing the location below its height
+    fn inside_obstacle(&self, location: &Location) -> Option<usize> {
+        let point = Point::from((location, &self.origin));
+        self.index.obstacles_at(&point).into_iter().find(|&i| {
+            let obstacle = &self.obstacles[i];
+            let inside = match obstacle.shape {
+                Shape::Cylinder => {
+                    let center = Point::from((&obstacle.location, &self.origin));
+                    point.distance(&center) < obstacle.radius
+                }
+                Shape::Prism(ref vertices) => inside_polygon(&point, &self.prism_points(vertices)),
+            };
+            location.alt() < obstacle.height && inside
+        })
     }
 
-    fn set_obstacles(&mut self, obstacles: Vec<Obstacle>) {
-        self.obstacles = obstacles;
-        self.build_graph();
-    }
-}
-
-impl Tanstar {
     // Helper function to add temp vertices connecting start and end
+    #[allow(clippy::too_many_arguments)]
     fn add_temp_vertices(
-        &mut self,
-        start_node: &Node,
+        &self,
+        search: &mut Search,
+        start_time: SystemTime,
+        starts: &[(Node, Option<f32>)],
         end_node: &Node,
         min_height: f32,
-        end_point: &Point,
-        open_set: &mut Queue,
-    ) -> LinkedList<Rc<RefCell<Vertex>>> {
-        let mut temp_vertices = LinkedList::new();
-        let start_vertex = Rc::new(RefCell::new(Vertex::new(
-            &mut START_VERTEX_INDEX,
-            &start_node,
-            0f32,
-            vec![],
-        )));
+        line: (&Point, &Point),
+        blocked: &dyn Fn(&Point, &Point, f32, f32) -> bool,
+    ) {
+        let start_vertex =
+            search.add_vertex(Vertex::new_start(&starts[0].0, starts[0].1.unwrap_or(0f32)));
+        search.set_label(start_vertex, 0f32, 0f32, None);
 
         //Prepare graph for A*
-        println!("\n[ Inserting temp vertices ]");
-        for i in 0..self.nodes.len() {
-            let temp_node = &self.nodes[i];
-            let (temp_paths, _) = self.find_path(&start_node, &temp_node.borrow());
-            println!("[start {}]: path count -> {}", i, temp_paths.len());
-
-            for (_, b, dist, threshold) in temp_paths {
-                if min_height < threshold {
-                    continue;
-                }
+        for (i, temp_node) in self.graph.nodes.iter().enumerate() {
+            // Stop inserting, the search loop reports the timeout
+            if self.time_exceeded(start_time) {
+                break;
+            }
+            for (start_node, start_angle) in starts {
+                let (temp_paths, _) = self.find_path(start_node, temp_node);
+                trace!(node = i, paths = temp_paths.len(), "paths from start");
+
+                for (a, b, dist, threshold) in temp_paths {
+                    if min_height < threshold {
+                        continue;
+                    }
 
-                println!("Inserting start vertex {}", self.num_vertices);
-                let mut vertex =
-                    Vertex::new(&mut self.num_vertices, &temp_node.borrow(), b, vec![]);
-                vertex.parent = Some(start_vertex.clone());
-                vertex.g_cost = dist;
-                vertex.f_cost = dist + vertex.location.distance(end_point);
-                let vertex_p = Rc::new(RefCell::new(vertex));
-                temp_node.borrow_mut().insert_vertex(vertex_p.clone());
-                open_set.push(vertex_p.clone());
-                temp_vertices.push_front(vertex_p.clone());
+                    // Turn on the circle up to the tangent, in the direction of the circle
+                    let (parent, turn_cost, turn_time) = match *start_angle {
+                        Some(angle) => {
+                            if (a >= 0f32) != (angle >= 0f32)
+                                || !self.valid_arc(start_node, angle, a, min_height)
+                            {
+                                continue;
+                            }
+                            self.turn_vertices(search, start_vertex, start_node, angle, a)
+                        }
+                        None => (start_vertex, 0f32, 0f32),
+                    };
+                    let vertex = Vertex::new(temp_node, b, vec![]);
+                    let from = match *start_angle {
+                        Some(_) => search.vertex(parent).location,
+                        None => start_node.origin,
+                    };
+                    let model = self.cost_model();
+                    let cost =
+                        model.tangent(&self.config, &from, &vertex.location, dist, threshold)
+                            + self.direct_cost(line, &from, &vertex.location, dist);
+                    let duration = flight_time(&self.config, &from, &vertex.location, dist);
+                    if !cost.is_finite()
+                        || !duration.is_finite()
+                        || blocked(&from, &vertex.location, turn_time, duration)
+                    {
+                        continue;
+                    }
+                    let g_cost = turn_cost + cost;
+                    let f_cost = g_cost + model.heuristic(&self.config, &vertex.location, line.1);
+                    let id = search.insert_vertex(i, vertex);
+                    search.set_label(id, g_cost, turn_time + duration, Some(parent));
+                    search.open_set.push(id, f_cost);
+                }
             }
 
-            let (temp_paths, _) = self.find_path(&temp_node.borrow(), &end_node);
-            println!("[end {}]: path count -> {}", i, temp_paths.len());
+            let (temp_paths, _) = self.find_path(temp_node, end_node);
+            trace!(node = i, paths = temp_paths.len(), "paths to end");
 
             for (a, b, dist, threshold) in temp_paths {
-                println!("Inserting end vertex {}", self.num_vertices);
-                let end_vertex = Rc::new(RefCell::new(Vertex::new(
-                    &mut END_VERTEX_INDEX,
-                    &end_node,
-                    b,
-                    vec![],
-                )));
-                let connection = Connection::new(end_vertex.clone(), dist, threshold);
-                let vertex = Rc::new(RefCell::new(Vertex::new(
-                    &mut self.num_vertices,
-                    &temp_node.borrow(),
-                    a,
-                    vec![connection],
-                )));
-                temp_node.borrow_mut().insert_vertex(vertex.clone());
-                temp_vertices.push_front(vertex.clone());
+                let end_vertex = search.add_vertex(Vertex::new_end(end_node, b));
+                search.ends.push(end_vertex);
+                let connection = Connection::new(end_vertex, dist, threshold);
+                search.insert_vertex(i, Vertex::new(temp_node, a, vec![connection]));
+            }
+        }
+    }
+
+    // Plane heading in radians counter clockwise from east, None if the yaw is not provided
+    pub(crate) fn heading(plane: &Plane) -> Option<f32> {
+        if plane.yaw >= 0f32 {
+            Some((90f32 - plane.yaw).to_radians())
+        } else {
+            None
+        }
+    }
+
+    // Left and right turning circles tangent to the heading at start, with the angle of start on
+    // each; the left circle is flown counter clockwise (positive angles), the right one clockwise
+    fn turning_circles(&self, start: &Point, heading: f32) -> Vec<(Node, Option<f32>)> {
+        let radius = self.config.ground_turning_radius();
+        let (dx, dy) = (radius * heading.sin(), radius * heading.cos());
+        let left = Point::new(start.x - dx, start.y + dy, start.z);
+        let right = Point::new(start.x + dx, start.y - dy, start.z);
+        vec![
+            (
+                Node::new(left, radius, 0f32),
+                Some(normalize_angle(true, heading - PI / 2f32)),
+            ),
+            (
+                Node::new(right, radius, 0f32),
+                Some(normalize_angle(false, heading + PI / 2f32)),
+            ),
+        ]
+    }
+
+    // Whether the arc of node between two angles stays inside the flyzones and above obstacles
+    fn valid_arc(&self, node: &Node, from: f32, to: f32, min_height: f32) -> bool {
+        let turn = arc_length(from, to, node.radius) / node.radius;
+        let direction = if from >= 0f32 { 1f32 } else { -1f32 };
+        let count = (turn / (PI / 8f32)).ceil().max(1f32) as usize;
+        let mut prev = Point::from((node, from));
+        (1..=count).all(|k| {
+            let point = Point::from((node, from + direction * turn * k as f32 / count as f32));
+            let valid = match self.valid_path(&prev, &point) {
+                PathValidity::Invalid => false,
+                PathValidity::Flyover(height) => min_height > height,
+                PathValidity::Valid => true,
+            };
+            prev = point;
+            valid
+        })
+    }
+
+    // Vertices along the turning circle from the start to the departure angle, one every quarter
+    // turn so the waypoints follow the circle, turns shorter than the merge threshold are skipped
+    // Turning away from the heading costs heading_modifier_weight per degree on top of the arc
+    // Returns the last vertex, the cost and the time to reach it
+    fn turn_vertices(
+        &self,
+        search: &mut Search,
+        start_vertex: VertexId,
+        node: &Node,
+        from: f32,
+        to: f32,
+    ) -> (VertexId, f32, f32) {
+        let arc = arc_length(from, to, node.radius);
+        let turn = Self::turn(from, arc, node.radius);
+        let cost = self.cost_model().arc(&self.config, from, node.radius, turn)
+            + self.config.heading_modifier_weight * turn.abs().to_degrees();
+        let time = arc_flight_time(&self.config, from, node.radius, turn);
+        if arc < self.config.vertex_merge_threshold {
+            return (start_vertex, cost, time);
+        }
+        let count = (turn.abs() / (PI / 2f32)).ceil().max(1f32) as usize;
+        let mut parent = start_vertex;
+        for k in 1..=count {
+            let fraction = k as f32 / count as f32;
+            let angle = from + turn * fraction;
+            let vertex = search.add_vertex(Vertex::new(node, angle, vec![]));
+            search.set_label(vertex, cost * fraction, time * fraction, Some(parent));
+            parent = vertex;
+        }
+        (parent, cost, time)
+    }
+
+    // Lengthen the path with loops when the altitude change is too steep for the climb or descent
+    // angle, around a ring of the path as wide as the turning radius, or a turning circle at the
+    // start towards the heading or the first vertex
+    // Loops are inserted where the fewest are needed, delaying the rest of the path; moving
+    // obstacles are not checked along them
+    fn climb_loops(
+        &self,
+        search: &mut Search,
+        end_vertex: VertexId,
+        start: &Point,
+        heading: Option<f32>,
+        climb: f32,
+        min_height: f32,
+    ) -> Result<(), PathError> {
+        let rate = if climb > 0f32 {
+            self.config.climb_rate()
+        } else {
+            self.config.descent_rate()
+        };
+        let extra = climb.abs() / rate - search.reached(end_vertex).time;
+        if extra <= 0f32 {
+            return Ok(());
+        }
+
+        // Vertices from the start vertex to the end vertex
+        let mut path = vec![end_vertex];
+        while let Some(parent) = search.reached(path[path.len() - 1]).parent {
+            path.push(parent);
+        }
+        path.reverse();
+
+        let radius = self.config.ground_turning_radius();
+        let mut hosts: Vec<(usize, Node, f32)> = path
+            .iter()
+            .map(|&id| search.vertex(id))
+            .enumerate()
+            .filter(|(_, vertex)| vertex.kind == VertexKind::Ring && vertex.radius >= radius - 1e-3)
+            .map(|(i, vertex)| {
+                let center = Point::new(
+                    vertex.location.x - vertex.radius * vertex.angle.cos(),
+                    vertex.location.y - vertex.radius * vertex.angle.sin(),
+                    vertex.location.z,
+                );
+                (i, Node::new(center, vertex.radius, 0f32), vertex.angle)
+            })
+            .collect();
+        if path.len() > 1 {
+            let next = search.vertex(path[1]).location;
+            let heading = heading.unwrap_or_else(|| (next.y - start.y).atan2(next.x - start.x));
+            for (node, angle) in self.turning_circles(start, heading) {
+                hosts.push((0, node, angle.unwrap_or(0f32)));
+            }
+        }
+
+        // Fewest loops of any valid host, half a loop at a time as full turns have no length
+        let mut best: Option<(usize, Node, f32, usize, f32)> = None;
+        for (index, node, angle) in hosts {
+            let direction = if angle >= 0f32 { 1f32 } else { -1f32 };
+            let half = normalize_angle(angle >= 0f32, angle + PI);
+            if !self.valid_arc(&node, angle, half, min_height)
+                || !self.valid_arc(&node, half, angle, min_height)
+            {
+                continue;
+            }
+            let time = arc_flight_time(&self.config, angle, node.radius, direction * 2f32 * PI);
+            let loops = (extra / time).ceil().max(1f32) as usize;
+            let total = loops as f32 * time;
+            let better = match best {
+                Some(ref b) => total < b.4,
+                None => true,
+            };
+            if better {
+                best = Some((index, node, angle, loops, total));
             }
         }
+        let (index, node, angle, loops, total) = match best {
+            Some(best) => best,
+            None => return Err(PathError::ClimbTooSteep),
+        };
+        debug!(vertex = index, loops, "inserting climb loops");
 
-        temp_vertices
+        let direction = if angle >= 0f32 { 1f32 } else { -1f32 };
+        let (base_time, g_cost) = {
+            let host = search.reached(path[index]);
+            (host.time, host.g_cost)
+        };
+        let count = 4 * loops;
+        let mut parent = path[index];
+        for k in 1..=count {
+            let fraction = k as f32 / count as f32;
+            let turn = direction * 2f32 * PI * loops as f32 * fraction;
+            let vertex = search.add_vertex(Vertex::new(&node, angle + turn, vec![]));
+            search.set_label(vertex, g_cost, base_time + total * fraction, Some(parent));
+            parent = vertex;
+        }
+        if let Some(label) = search.label_mut(path[index + 1]) {
+            label.parent = Some(parent);
+        }
+        for &id in &path[index + 1..] {
+            if let Some(label) = search.label_mut(id) {
+                label.time += total;
+            }
+        }
+        Ok(())
     }
 
     fn update_vertex(
-        (open_set, close_set, cur, end_point): &mut (
-            &mut Queue,
-            &HashSet<i32>,
-            &Rc<RefCell<Vertex>>,
-            &Point,
-        ),
-        cur_g_cost: f32,
-        next: Rc<RefCell<Vertex>>,
-        dist: f32,
+        &self,
+        search: &mut Search,
+        cur: VertexId,
+        next: VertexId,
+        end_point: &Point,
+        cost: f32,
+        duration: f32,
     ) {
-        // Handle edge case when node only has one vertex
-        if next.borrow().index == cur.borrow().index {
+        // Handle edge case when node only has one vertex, or the wind forbids the edge
+        if next == cur || !cost.is_finite() || !duration.is_finite() {
             return;
         }
-        let new_g_cost = cur_g_cost + dist;
-        // println!("add vertex to queue {}", next.borrow());
+        let (g_cost, time) = {
+            let label = search.reached(cur);
+            (label.g_cost, label.time)
+        };
+        let new_g_cost = g_cost + cost;
+        if search.close_set.contains(&next)    //vertex is already explored
+            || search.vertex(next).sentinel     //vertex is a sentinel
+            || matches!(search.label(next), Some(label) if new_g_cost >= label.g_cost)
         {
-            if next.borrow().sentinel {
-                println!("SENTINEL ENCOUNTERED");
-            }
-            if close_set.contains(&next.borrow().index)    //vertex is already explored
-                || next.borrow().sentinel                   //vertex is a sentinel
-                || (open_set.contains(&next) && new_g_cost >= next.borrow().g_cost)
-            {
-                //vertex has been visited and the current cost is better
-                return;
-            }
-            let mut next_mut = next.borrow_mut();
-            let new_f_cost = new_g_cost + next_mut.location.distance(end_point);
-            next_mut.g_cost = new_g_cost;
-            next_mut.f_cost = new_f_cost;
-            next_mut.parent = Some(cur.clone());
+            //vertex has been visited and the current cost is better
+            return;
         }
-        open_set.push(next.clone());
+        let heuristic =
+            self.cost_model()
+                .heuristic(&self.config, &search.vertex(next).location, end_point);
+        search.set_label(next, new_g_cost, time + duration, Some(cur));
+        search.open_set.push(next, new_g_cost + heuristic);
     }
 
     fn generate_waypoint<T>(
         &self,
-        end_vertex: Rc<RefCell<Vertex>>,
+        search: &Search,
+        end_vertex: VertexId,
         start_alt: f32,
         end_alt: f32,
     ) -> LinkedList<Waypoint<T>> {
         let mut waypoint_list = LinkedList::new();
         let mut cur_vertex = end_vertex;
-        println!(
-            "Generating waypoints from alt {} to alt {}",
-            start_alt, end_alt
-        );
-        let slope = (end_alt - start_alt) / cur_vertex.borrow().g_cost;
+        debug!(start_alt, end_alt, "generating waypoints");
+        // Climb or descend at a constant rate over the flight time
+        let slope = (end_alt - start_alt) / search.reached(cur_vertex).time;
         loop {
             // Skip appending end vertex to waypoint_list
-            let parent = match cur_vertex.borrow().parent {
-                Some(ref cur_parent) => cur_parent.clone(),
+            let parent = match search.reached(cur_vertex).parent {
+                Some(cur_parent) => cur_parent,
                 None => panic!("Missing a parent without reaching start point"),
             };
 
             // Skip appending start vertex to waypoint list
-            if parent.borrow().index == START_VERTEX_INDEX {
+            if search.vertex(parent).kind == VertexKind::Start {
                 break;
             }
 
             cur_vertex = parent;
-            let mut loc = Location::from((&cur_vertex.borrow().location, &self.origin));
-            println!("weight: {}", cur_vertex.borrow().g_cost);
-            loc.alt = (start_alt + cur_vertex.borrow().g_cost * slope).into();
-            println!("{}", loc);
-            let radius = cur_vertex.borrow().radius;
-            waypoint_list.push_front(Waypoint::new(loc, radius));
+            let vertex = search.vertex(cur_vertex);
+            let mut loc = Location::from((&vertex.location, &self.origin));
+            loc.alt = (start_alt + search.reached(cur_vertex).time * slope).into();
+            Self::clamp_altitude(&self.flyzones, &self.origin, &mut loc);
+            trace!(vertex = cur_vertex, location = %loc, "waypoint");
+            waypoint_list.push_front(Waypoint::new(loc, vertex.radius));
         }
         waypoint_list
     }
 }
-
 #[cfg(test)]
 mod test {
     use super::*;
+    use fixture::{leg, square_flyzone, wall};
+    use std::time::Duration;
+
+    fn timeout_config(anytime: bool) -> TConfig {
+        TConfig {
+            max_process_time: Duration::from_secs(0),
+            anytime,
+            ..Default::default()
+        }
+    }
+
+    #[test]
+    fn build_graph_timeout_test() {
+        let origin = Location::from_degrees(0f64, 0f64, 0f32);
+        let obstacles = vec![Obstacle::new(
+            Location::from_meters(200f32, 200f32, 20f32, &origin),
+            20f32,
+            20f32,
+        )];
+
+        let mut tanstar = Tanstar::new();
+        let result = tanstar.init(
+            timeout_config(false),
+            square_flyzone(&origin),
+            obstacles.clone(),
+        );
+        assert_eq!(result, Err(PathfinderError::ProcessTimeExceeded));
+
+        let mut tanstar = Tanstar::new();
+        let result = tanstar.init(timeout_config(true), square_flyzone(&origin), obstacles);
+        assert_eq!(result, Ok(()));
+        let path = tanstar.adjust_path::<()>(
+            Location::from_meters(50f32, 50f32, 10f32, &origin),
+            Location::from_meters(350f32, 350f32, 10f32, &origin),
+        );
+        assert_eq!(path.err(), Some(PathError::ProcessTimeExceeded));
+    }
+
+    #[test]
+    fn failed_build_test() {
+        let origin = Location::from_degrees(0f64, 0f64, 0f32);
+        let obstacles = vec![Obstacle::new(
+            Location::from_meters(200f32, 200f32, 20f32, &origin),
+            20f32,
+            20f32,
+        )];
+        let mut tanstar = Tanstar::new();
+        let result = tanstar.init(timeout_config(false), square_flyzone(&origin), Vec::new());
+        assert_eq!(result, Err(PathfinderError::ProcessTimeExceeded));
+        assert!(!tanstar.initialized);
+        assert!(tanstar.flyzones.is_empty());
+
+        tanstar
+            .init(TConfig::default(), square_flyzone(&origin), Vec::new())
+            .unwrap();
+        let graph = tanstar.graph();
+        let result = tanstar.set_config(timeout_config(false));
+        assert_eq!(result, Err(PathfinderError::ProcessTimeExceeded));
+        assert_eq!(tanstar.config, TConfig::default());
+        assert!(Arc::ptr_eq(&graph, &tanstar.graph));
+
+        tanstar.config.max_process_time = Duration::from_secs(0);
+        let result = tanstar.set_obstacles(obstacles);
+        assert_eq!(result, Err(PathfinderError::ProcessTimeExceeded));
+        assert!(tanstar.obstacles.is_empty());
+        assert!(tanstar
+            .index
+            .obstacles_at(&Point::new(200f32, 200f32, 0f32))
+            .is_empty());
+        assert!(Arc::ptr_eq(&graph, &tanstar.graph));
+    }
+
+    // Distance cost that stalls when the search reaches the goal, so it runs out of time
+    // with a path found
+    struct SlowGoal(Duration);
+
+    impl CostModel for SlowGoal {
+        fn tangent(&self, config: &TConfig, a: &Point, b: &Point, length: f32, h: f32) -> f32 {
+            DistanceCost.tangent(config, a, b, length, h)
+        }
+
+        fn arc(&self, config: &TConfig, angle: f32, radius: f32, turn: f32) -> f32 {
+            DistanceCost.arc(config, angle, radius, turn)
+        }
+
+        fn heuristic(&self, config: &TConfig, a: &Point, goal: &Point) -> f32 {
+            if a.distance(goal) <= config.ground_turning_radius() + 1f32 {
+                std::thread::sleep(self.0);
+            }
+            DistanceCost.heuristic(config, a, goal)
+        }
+    }
+
+    #[test]
+    fn anytime_search_test() {
+        let origin = Location::from_degrees(0f64, 0f64, 0f32);
+        let mut tanstar = Tanstar::new();
+        tanstar
+            .init(
+                TConfig::default(),
+                square_flyzone(&origin),
+                vec![wall(&origin, 50f32)],
+            )
+            .unwrap();
+        tanstar.set_cost_model(Box::new(SlowGoal(Duration::from_millis(100))));
+        tanstar.config.max_process_time = Duration::from_millis(50);
+        let start = Location::from_meters(50f32, 50f32, 10f32, &origin);
+        let end = Location::from_meters(350f32, 350f32, 10f32, &origin);
+        let path = tanstar.adjust_path::<()>(start, end);
+        assert_eq!(path.err(), Some(PathError::ProcessTimeExceeded));
+
+        // the goal reached before running out of time is returned
+        tanstar.config.anytime = true;
+        let path = tanstar.adjust_path::<()>(start, end).unwrap();
+        assert!(!path.is_empty());
+        let center = Point::new(200f32, 200f32, 0f32);
+        for waypoint in &path {
+            let point = Point::from((&waypoint.location, &tanstar.origin));
+            assert!(point.distance(&center) > 50f32);
+        }
+    }
 
     #[test]
     fn flyzones_intersection_test() {
@@ -399,7 +1084,293 @@ mod test {
         let b = (&Point::new(20f32, 0f32, 10f32), &origin).into();
         let c = (&Point::new(20f32, 20f32, 10f32), &origin).into();
         let d = (&Point::new(0f32, 20f32, 10f32), &origin).into();
-        let test_flyzone = vec![vec![a, b, d, c]];
-        assert!(Tanstar::invalid_flyzone(&test_flyzone, &origin));
+        let test_flyzone = vec![Flyzone::from(vec![a, b, d, c])];
+        assert_eq!(
+            Tanstar::invalid_flyzone(&test_flyzone, &origin),
+            Some((0, 1, 3))
+        );
+    }
+
+    #[test]
+    fn config_validation_test() {
+        let origin = Location::from_degrees(0f64, 0f64, 0f32);
+        let mut tanstar = Tanstar::new();
+        let config = TConfig {
+            turning_radius: 0f32,
+            ..Default::default()
+        };
+        assert_eq!(
+            tanstar.init(config, square_flyzone(&origin), vec![]),
+            Err(PathfinderError::InvalidConfig {
+                field: "turning_radius"
+            })
+        );
+        tanstar
+            .init(TConfig::default(), square_flyzone(&origin), vec![])
+            .unwrap();
+        for (config, field) in [
+            (
+                TConfig {
+                    buffer_size: f32::NAN,
+                    ..Default::default()
+                },
+                "buffer_size",
+            ),
+            (
+                TConfig {
+                    vertex_merge_threshold: -1f32,
+                    ..Default::default()
+                },
+                "vertex_merge_threshold",
+            ),
+            (
+                TConfig {
+                    direct_path_modifier_weight: f32::INFINITY,
+                    ..Default::default()
+                },
+                "direct_path_modifier_weight",
+            ),
+        ] {
+            assert_eq!(
+                tanstar.set_config(config),
+                Err(PathfinderError::InvalidConfig { field })
+            );
+        }
+        // Buffer must be positive
+        let config = TConfig {
+            buffer_size: 0f32,
+            ..Default::default()
+        };
+        assert_eq!(
+            tanstar.set_config(config),
+            Err(PathfinderError::InvalidConfig {
+                field: "buffer_size"
+            })
+        );
+    }
+
+    #[test]
+    fn climb_test() {
+        let origin = Location::from_degrees(0f64, 0f64, 0f32);
+        let mut tanstar = Tanstar::new();
+        tanstar
+            .init(
+                TConfig::default(),
+                square_flyzone(&origin),
+                vec![wall(&origin, 150f32)],
+            )
+            .unwrap();
+        let origin = tanstar.origin;
+        let start = Location::from_meters(50f32, 200f32, 10f32, &origin);
+        let end = Location::from_meters(350f32, 200f32, 110f32, &origin);
+        let (free, arrival) = tanstar.adjust_path_at::<()>(start, end, 0f32).unwrap();
+        assert!(arrival < 100f32 / tanstar.config.climb_rate() * 2f32);
+
+        // climbing 100 meters at 5 degrees takes about a minute, looping around the wall
+        let mut config = tanstar.config.clone();
+        config.max_climb_angle = 5f32;
+        tanstar.set_config(config).unwrap();
+        let (path, looped) = tanstar.adjust_path_at::<()>(start, end, 0f32).unwrap();
+        assert!(looped >= 100f32 / tanstar.config.climb_rate() - 1e-3);
+        assert!(path.len() >= free.len() + 4);
+        let mut alt = start.alt();
+        for wp in &path {
+            assert!(wp.location.alt() >= alt && wp.location.alt() <= end.alt());
+            assert!(tanstar.in_flyzone(&wp.location));
+            alt = wp.location.alt();
+        }
+        // descending is still steep enough
+        let (_, descent) = tanstar.adjust_path_at::<()>(end, start, 0f32).unwrap();
+        assert!((descent - arrival).abs() < 1f32);
+
+        // no room to loop
+        let mut config = tanstar.config.clone();
+        config.turning_radius = 150f32;
+        tanstar.set_config(config).unwrap();
+        assert_eq!(
+            tanstar.adjust_path_at::<()>(start, end, 0f32),
+            Err(PathError::ClimbTooSteep)
+        );
+    }
+
+    #[test]
+    fn flyzone_altitudes_test() {
+        let origin = Location::from_degrees(0f64, 0f64, 0f32);
+        let mut flyzones = square_flyzone(&origin);
+        flyzones[0].min_alt = 20f32;
+        flyzones[0].max_alt = 100f32;
+        let mut tanstar = Tanstar::new();
+        tanstar
+            .init(TConfig::default(), flyzones, vec![wall(&origin, 150f32)])
+            .unwrap();
+        let origin = tanstar.origin;
+        assert_eq!(
+            tanstar.altitude_band(&Location::from_meters(50f32, 50f32, 0f32, &origin)),
+            Some((20f32, 100f32))
+        );
+        assert_eq!(
+            tanstar.altitude_band(&Location::from_meters(500f32, 50f32, 0f32, &origin)),
+            None
+        );
+
+        // plane below the floor climbs into the band
+        let start = Location::from_meters(50f32, 200f32, 10f32, &origin);
+        let end = Location::from_meters(350f32, 200f32, 40f32, &origin);
+        let path = tanstar.adjust_path::<()>(start, end).unwrap();
+        assert!(!path.is_empty());
+        assert!(path
+            .iter()
+            .all(|wp| wp.location.alt() >= 20f32 && wp.location.alt() <= 40f32));
+
+        let above = Location::from_meters(350f32, 200f32, 120f32, &origin);
+        assert_eq!(
+            tanstar.adjust_path::<()>(start, above),
+            Err(PathError::GoalAltitudeOutsideFlyzone)
+        );
+    }
+
+    #[test]
+    fn keep_out_test() {
+        let origin = Location::from_degrees(0f64, 0f64, 0f32);
+        let keep_out = vec![
+            Location::from_meters(150f32, 100f32, 0f32, &origin),
+            Location::from_meters(150f32, 300f32, 0f32, &origin),
+            Location::from_meters(250f32, 300f32, 0f32, &origin),
+            Location::from_meters(250f32, 100f32, 0f32, &origin),
+        ];
+        let flyzone = square_flyzone(&origin).remove(0).keep_out(keep_out.clone());
+        let mut tanstar = Tanstar::new();
+        tanstar
+            .init(TConfig::default(), vec![flyzone.clone()], Vec::new())
+            .unwrap();
+        let origin = tanstar.origin;
+        assert!(!tanstar.in_flyzone(&Location::from_meters(200f32, 200f32, 10f32, &origin)));
+        assert!(tanstar.in_flyzone(&Location::from_meters(50f32, 200f32, 10f32, &origin)));
+
+        // path goes around the keep-out instead of through it
+        let (start, end) = leg(&origin);
+        let path = tanstar.adjust_path::<()>(start, end).unwrap();
+        let points: Vec<Point> = path
+            .iter()
+            .map(|wp| Point::from((&wp.location, &origin)))
+            .collect();
+        assert!(points.iter().any(|p| p.y < 100f32 || p.y > 300f32));
+        assert!(points
+            .iter()
+            .all(|p| p.x < 150f32 || p.x > 250f32 || p.y < 100f32 + 1f32 || p.y > 300f32 - 1f32));
+
+        assert_eq!(
+            tanstar.adjust_path::<()>(start, Location::from_meters(200f32, 200f32, 10f32, &origin)),
+            Err(PathError::GoalOutsideFlyzone)
+        );
+
+        // obstacle inside the keep-out and degenerate keep-outs are rejected
+        let obstacle = Obstacle::new(
+            Location::from_meters(200f32, 200f32, 0f32, &origin),
+            10f32,
+            20f32,
+        );
+        assert_eq!(
+            tanstar.set_obstacles(vec![obstacle]),
+            Err(PathfinderError::ObstacleOutsideFlyzone { obstacle: 0 })
+        );
+        let flyzone = flyzone.keep_out(keep_out[..2].to_vec());
+        assert_eq!(
+            tanstar.set_flyzone(vec![flyzone]),
+            Err(PathfinderError::TooFewKeepOutVertices {
+                flyzone: 0,
+                keep_out: 1,
+                count: 2
+            })
+        );
+        let crossed = vec![keep_out[0], keep_out[2], keep_out[1], keep_out[3]];
+        let flyzone = square_flyzone(&origin).remove(0).keep_out(crossed);
+        assert_eq!(
+            tanstar.set_flyzone(vec![flyzone]),
+            Err(PathfinderError::KeepOutSelfIntersection {
+                flyzone: 0,
+                keep_out: 0,
+                edges: (0, 2)
+            })
+        );
+    }
+
+    #[test]
+    fn send_sync_test() {
+        fn assert_send_sync<T: Send + Sync>() {}
+        assert_send_sync::<Tanstar>();
+        assert_send_sync::<Graph>();
+        assert_send_sync::<::Pathfinder<Tanstar>>();
+    }
+
+    #[cfg(feature = "rayon")]
+    #[test]
+    fn adjust_legs_failure_test() {
+        let origin = Location::from_degrees(0f64, 0f64, 0f32);
+        let mut tanstar = Tanstar::new();
+        tanstar
+            .init(
+                TConfig::default(),
+                square_flyzone(&origin),
+                vec![wall(&origin, 150f32)],
+            )
+            .unwrap();
+        let origin = tanstar.origin;
+        let at = |x: f32, y: f32| Location::from_meters(x, y, 10f32, &origin);
+        let plane = Plane::new(at(50f32, 50f32));
+
+        // the second goal is inside the wall, the legs after it are not returned
+        let goals = [
+            at(50f32, 350f32),
+            at(200f32, 200f32),
+            at(350f32, 350f32),
+            at(350f32, 50f32),
+        ];
+        let legs = tanstar.adjust_legs(&plane, &goals).unwrap();
+        assert_eq!(legs.len(), 2);
+        assert!(legs[0].is_ok());
+        assert_eq!(
+            legs[1].as_ref().unwrap_err(),
+            &PathError::GoalInsideObstacle { obstacle: 0 }
+        );
+    }
+
+    #[test]
+    fn shared_graph_test() {
+        use std::thread;
+
+        let origin = Location::from_degrees(0f64, 0f64, 0f32);
+        let mut tanstar = Tanstar::new();
+        tanstar
+            .init(
+                TConfig::default(),
+                square_flyzone(&origin),
+                vec![wall(&origin, 150f32)],
+            )
+            .unwrap();
+        let origin = tanstar.origin;
+        let (start, end) = leg(&origin);
+        let vertices = tanstar.graph().vertex_count();
+
+        // clones plan from other threads on the same graph, leaving it untouched
+        let handles: Vec<_> = (0..4)
+            .map(|_| {
+                let mut clone = tanstar.clone();
+                thread::spawn(move || clone.adjust_path::<()>(start, end).unwrap())
+            })
+            .collect();
+        let path = tanstar.adjust_path::<()>(start, end).unwrap();
+        for handle in handles {
+            assert_eq!(handle.join().unwrap(), path);
+        }
+        assert_eq!(tanstar.graph().vertex_count(), vertices);
+
+        // updating a clone copies the graph instead of changing the shared one
+        let mut clone = tanstar.clone();
+        assert!(Arc::ptr_eq(&clone.graph(), &tanstar.graph()));
+        clone.remove_obstacle(0).unwrap();
+        assert!(!Arc::ptr_eq(&clone.graph(), &tanstar.graph()));
+        assert_eq!(tanstar.graph().vertex_count(), vertices);
+        assert_eq!(clone.graph().node_count(), tanstar.graph().node_count() - 1);
     }
 }
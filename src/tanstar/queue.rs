@@ -1,31 +1,60 @@
 use super::*;
 
-// Simple wrapper around heap and set for efficient data retrival
+use std::cmp::Ordering;
+
+// Vertex waiting in the queue with its estimated total cost when it was pushed
+struct Entry {
+    f_cost: f32,
+    id: VertexId,
+}
+
+impl Eq for Entry {}
+
+impl PartialEq for Entry {
+    fn eq(&self, other: &Self) -> bool {
+        self.cmp(other) == Ordering::Equal
+    }
+}
+
+impl Ord for Entry {
+    fn cmp(&self, other: &Self) -> Ordering {
+        // Order flipped for max heap
+        if self.f_cost < other.f_cost {
+            Ordering::Greater
+        } else if self.f_cost > other.f_cost {
+            Ordering::Less
+        } else {
+            Ordering::Equal
+        }
+    }
+}
+
+impl PartialOrd for Entry {
+    fn partial_cmp(&self, other: &Self) -> Option<Ordering> {
+        Some(self.cmp(other))
+    }
+}
+
+// Simple wrapper around heap for efficient data retrival
+// A vertex pushed again with a lower cost is popped first, stale entries are skipped by the search
 pub struct Queue {
-    heap: BinaryHeap<Rc<RefCell<Vertex>>>, // Efficiently get min
-    set: HashSet<i32>,                     // Efficiently check of existence
+    heap: BinaryHeap<Entry>, // Efficiently get min
 }
 
 impl Queue {
     pub fn new() -> Self {
         Queue {
             heap: BinaryHeap::new(),
-            set: HashSet::new(),
         }
     }
 
     // Insert to queue
-    pub fn push(&mut self, vertex: Rc<RefCell<Vertex>>) {
-        self.set.insert(vertex.borrow().index);
-        self.heap.push(vertex);
+    pub fn push(&mut self, id: VertexId, f_cost: f32) {
+        self.heap.push(Entry { f_cost, id });
     }
 
     // Return min from queue
-    pub fn pop(&mut self) -> Option<Rc<RefCell<Vertex>>> {
-        self.heap.pop()
-    }
-
-    pub fn contains(&self, vertex: &Rc<RefCell<Vertex>>) -> bool {
-        self.set.contains(&vertex.borrow().index)
+    pub fn pop(&mut self) -> Option<VertexId> {
+        self.heap.pop().map(|entry| entry.id)
     }
 }
@@ -0,0 +1,135 @@
+// search.rs
+// State of a single query, kept apart from the shared graph so queries only read it
+
+use super::*;
+
+use std::collections::HashMap;
+
+// Best known way to reach a vertex
+#[derive(Clone, Debug)]
+pub struct Label {
+    pub g_cost: f32,              // Cost from the start
+    pub time: f32,                // Flight time from the start
+    pub parent: Option<VertexId>, // Previous vertex on the path
+}
+
+// Vertices added to the graph for one leg and the costs found by the search
+// Vertices of the search get ids after the arena of the graph, and the rings they are inserted
+// into are relinked here without changing the graph
+pub struct Search<'a> {
+    graph: &'a Graph,
+    base: VertexId,
+    vertices: Vec<Vertex>,
+    next: HashMap<VertexId, VertexId>, // Links of graph vertices changed by inserted vertices
+    prev: HashMap<VertexId, VertexId>,
+    labels: HashMap<VertexId, Label>,
+    pub ends: Vec<VertexId>, // End vertices the temporary vertices connect to
+    pub open_set: Queue,     // candidate vertices
+    pub close_set: HashSet<VertexId>, // set of vertex already visited
+}
+
+impl<'a> Rings for Search<'a> {
+    fn vertex(&self, id: VertexId) -> &Vertex {
+        if id < self.base {
+            self.graph.vertex(id)
+        } else {
+            &self.vertices[id - self.base]
+        }
+    }
+
+    fn ring(&self, node: usize, left: bool) -> VertexId {
+        self.graph.ring(node, left)
+    }
+
+    fn next(&self, id: VertexId) -> VertexId {
+        match self.next.get(&id) {
+            Some(&next) => next,
+            None => self.vertex(id).next.expect("broken chain"),
+        }
+    }
+
+    fn prev(&self, id: VertexId) -> VertexId {
+        match self.prev.get(&id) {
+            Some(&prev) => prev,
+            None => self.vertex(id).prev.expect("broken chain"),
+        }
+    }
+}
+
+impl<'a> Search<'a> {
+    pub fn new(graph: &'a Graph) -> Self {
+        Self {
+            graph,
+            base: graph.arena_size(),
+            vertices: Vec::new(),
+            next: HashMap::new(),
+            prev: HashMap::new(),
+            labels: HashMap::new(),
+            ends: Vec::new(),
+            open_set: Queue::new(),
+            close_set: HashSet::new(),
+        }
+    }
+
+    // Number of vertices added by the search
+    pub fn temp_count(&self) -> usize {
+        self.vertices.len()
+    }
+
+    // Add a vertex outside of any ring
+    pub fn add_vertex(&mut self, vertex: Vertex) -> VertexId {
+        self.vertices.push(vertex);
+        self.base + self.vertices.len() - 1
+    }
+
+    // Insert a vertex into a ring of node
+    pub fn insert_vertex(&mut self, node: usize, mut vertex: Vertex) -> VertexId {
+        let (current, next) = self.traverse_rings(node, vertex.angle);
+        vertex.prev = Some(current);
+        vertex.next = Some(next);
+        let id = self.add_vertex(vertex);
+        self.set_next(current, id);
+        self.set_prev(next, id);
+        id
+    }
+
+    fn set_next(&mut self, id: VertexId, next: VertexId) {
+        if id < self.base {
+            self.next.insert(id, next);
+        } else {
+            self.vertices[id - self.base].next = Some(next);
+        }
+    }
+
+    fn set_prev(&mut self, id: VertexId, prev: VertexId) {
+        if id < self.base {
+            self.prev.insert(id, prev);
+        } else {
+            self.vertices[id - self.base].prev = Some(prev);
+        }
+    }
+
+    pub fn label(&self, id: VertexId) -> Option<&Label> {
+        self.labels.get(&id)
+    }
+
+    pub fn label_mut(&mut self, id: VertexId) -> Option<&mut Label> {
+        self.labels.get_mut(&id)
+    }
+
+    pub fn set_label(&mut self, id: VertexId, g_cost: f32, time: f32, parent: Option<VertexId>) {
+        self.labels.insert(
+            id,
+            Label {
+                g_cost,
+                time,
+                parent,
+            },
+        );
+    }
+
+    // Label of a vertex reached by the search
+    pub fn reached(&self, id: VertexId) -> &Label {
+        self.labels.get(&id).expect("vertex not reached")
+    }
+}
@@ -0,0 +1,255 @@
+// track.rs
+// Time-parameterized position of moving obstacles
+
+use super::graph::Point;
+use super::*;
+
+// Moving obstacle as piecewise linear motion relative to the origin
+pub struct Track {
+    knots: Vec<(f32, Point)>, // (seconds, position), a single knot moves with velocity
+    velocity: (f32, f32),     // east and north, in meters per second
+    radius: f32,
+    pub height: f32,
+}
+
+impl Track {
+    // Track of a moving obstacle, None if the obstacle is static
+    pub fn new(obstacle: &Obstacle, origin: &Location, buffer: f32) -> Option<Self> {
+        let (knots, velocity) = match obstacle.motion {
+            Motion::Static => return None,
+            Motion::Velocity { east, north } => (
+                vec![(0f32, Point::from((&obstacle.location, origin)))],
+                (east, north),
+            ),
+            Motion::Trajectory(ref trajectory) if !trajectory.is_empty() => (
+                trajectory
+                    .iter()
+                    .map(|(t, location)| (*t, Point::from((location, origin))))
+                    .collect(),
+                (0f32, 0f32),
+            ),
+            Motion::Trajectory(_) => (
+                vec![(0f32, Point::from((&obstacle.location, origin)))],
+                (0f32, 0f32),
+            ),
+        };
+        Some(Self {
+            knots,
+            velocity,
+            radius: obstacle.radius + buffer,
+            height: obstacle.height,
+        })
+    }
+
+    // Position of the obstacle at time t
+    pub fn position(&self, t: f32) -> Point {
+        let (t0, p0) = self.knots[0];
+        if self.knots.len() == 1 {
+            return Point::new(
+                p0.x + self.velocity.0 * (t - t0),
+                p0.y + self.velocity.1 * (t - t0),
+                p0.z,
+            );
+        }
+        if t <= t0 {
+            return p0;
+        }
+        for pair in self.knots.windows(2) {
+            let ((ta, a), (tb, b)) = (pair[0], pair[1]);
+            if t <= tb {
+                return lerp(&a, &b, (t - ta) / (tb - ta));
+            }
+        }
+        self.knots[self.knots.len() - 1].1
+    }
+
+    // Whether a plane flying from a at time t0 to b at time t1 comes within the obstacle radius
+    pub fn collides(&self, a: &Point, b: &Point, t0: f32, t1: f32) -> bool {
+        // Split the flight at trajectory knots so both motions are linear in every piece
+        let mut times = vec![t0];
+        times.extend(
+            self.knots
+                .iter()
+                .map(|(t, _)| *t)
+                .filter(|t| *t > t0 && *t < t1),
+        );
+        times.push(t1);
+
+        let duration = t1 - t0;
+        let plane = |t: f32| {
+            if duration > 0f32 {
+                lerp(a, b, (t - t0) / duration)
+            } else {
+                *a
+            }
+        };
+        times.windows(2).any(|w| {
+            let (pa, pb) = (plane(w[0]), plane(w[1]));
+            let (oa, ob) = (self.position(w[0]), self.position(w[1]));
+            // Closest approach of the relative motion to the obstacle
+            let r0 = (pa.x - oa.x, pa.y - oa.y);
+            let r1 = (pb.x - ob.x, pb.y - ob.y);
+            let d = (r1.0 - r0.0, r1.1 - r0.1);
+            let len = d.0 * d.0 + d.1 * d.1;
+            let s = if len > 0f32 {
+                (-(r0.0 * d.0 + r0.1 * d.1) / len).clamp(0f32, 1f32)
+            } else {
+                0f32
+            };
+            let closest = (r0.0 + s * d.0, r0.1 + s * d.1);
+            (closest.0 * closest.0 + closest.1 * closest.1).sqrt() < self.radius
+        })
+    }
+}
+
+fn lerp(a: &Point, b: &Point, s: f32) -> Point {
+    Point::new(
+        a.x + (b.x - a.x) * s,
+        a.y + (b.y - a.y) * s,
+        a.z + (b.z - a.z) * s,
+    )
+}
+
+#[cfg(test)]
+mod test {
+    use super::*;
+    use fixture::{leg, square_flyzone, wall};
+
+    fn origin() -> Location {
+        Location::from_degrees(30.32469, -97.60466, 0f32)
+    }
+
+    fn obstacle(x: f32, y: f32) -> Obstacle {
+        Obstacle::new(Location::from_meters(x, y, 50f32, &origin()), 10f32, 50f32)
+    }
+
+    #[test]
+    fn velocity_track_test() {
+        let track = Track::new(
+            &obstacle(100f32, 0f32).velocity(0f32, 10f32),
+            &origin(),
+            0f32,
+        )
+        .unwrap();
+        let p = track.position(5f32);
+        assert!((p.x - 100f32).abs() < 0.01 && (p.y - 50f32).abs() < 0.01);
+
+        // crossing x = 100 along y = 50 at t = 5 meets the obstacle, at t = 0 it does not
+        let a = Point::new(50f32, 50f32, 0f32);
+        let b = Point::new(150f32, 50f32, 0f32);
+        assert!(track.collides(&a, &b, 0f32, 10f32));
+        assert!(!track.collides(&a, &b, 10f32, 20f32));
+        assert!(Track::new(&obstacle(0f32, 0f32), &origin(), 0f32).is_none());
+    }
+
+    #[test]
+    fn trajectory_track_test() {
+        let trajectory = vec![
+            (10f32, Location::from_meters(0f32, 0f32, 50f32, &origin())),
+            (20f32, Location::from_meters(100f32, 0f32, 50f32, &origin())),
+            (
+                30f32,
+                Location::from_meters(100f32, 100f32, 50f32, &origin()),
+            ),
+        ];
+        let track = Track::new(
+            &obstacle(0f32, 0f32).trajectory(trajectory),
+            &origin(),
+            5f32,
+        )
+        .unwrap();
+        assert!(track.position(0f32).x.abs() < 0.01);
+        assert!((track.position(15f32).x - 50f32).abs() < 0.01);
+        assert!((track.position(25f32).y - 50f32).abs() < 0.01);
+        assert!((track.position(40f32).y - 100f32).abs() < 0.01);
+
+        // obstacle passes (100, 50) at t = 25
+        let a = Point::new(100f32, 0f32, 0f32);
+        let b = Point::new(100f32, 100f32, 0f32);
+        assert!(track.collides(&b, &a, 20f32, 30f32));
+        assert!(!track.collides(&b, &a, 0f32, 5f32));
+    }
+
+    #[test]
+    fn invalid_trajectory_test() {
+        let flyzones = vec![Flyzone::from(vec![
+            Location::from_meters(-200f32, -200f32, 0f32, &origin()),
+            Location::from_meters(-200f32, 200f32, 0f32, &origin()),
+            Location::from_meters(200f32, 200f32, 0f32, &origin()),
+            Location::from_meters(200f32, -200f32, 0f32, &origin()),
+        ])];
+        let at = |t: f32, x: f32| (t, Location::from_meters(x, 0f32, 50f32, &origin()));
+        for trajectory in [
+            vec![],
+            vec![at(0f32, 0f32)],
+            vec![at(0f32, 0f32), at(10f32, 50f32), at(10f32, 100f32)],
+            vec![at(10f32, 0f32), at(0f32, 50f32)],
+            vec![at(0f32, 0f32), at(f32::NAN, 50f32)],
+        ] {
+            let obstacles = vec![
+                obstacle(0f32, 0f32),
+                obstacle(0f32, 0f32).trajectory(trajectory),
+            ];
+            assert_eq!(
+                Tanstar::validate_obstacles(&flyzones, &obstacles),
+                Err(PathfinderError::InvalidTrajectory { obstacle: 1 })
+            );
+        }
+        let obstacles =
+            vec![obstacle(0f32, 0f32).trajectory(vec![at(0f32, 0f32), at(10f32, 50f32)])];
+        assert!(Tanstar::validate_obstacles(&flyzones, &obstacles).is_ok());
+    }
+
+    #[test]
+    fn moving_obstacle_test() {
+        let origin = Location::from_degrees(0f64, 0f64, 0f32);
+        let wall = wall(&origin, 50f32);
+        let mut tanstar = Tanstar::new();
+        tanstar
+            .init(
+                TConfig::default(),
+                square_flyzone(&origin),
+                vec![wall.clone()],
+            )
+            .unwrap();
+        let origin = tanstar.origin;
+        let speed = tanstar.config.cruise_speed;
+        let (start, end) = leg(&origin);
+        let points = |path: &LinkedList<Waypoint<()>>| {
+            let mut points = vec![Point::from((&start, &origin))];
+            points.extend(path.iter().map(|wp| Point::from((&wp.location, &origin))));
+            points.push(Point::from((&end, &origin)));
+            points
+        };
+
+        let (baseline, arrival) = tanstar.adjust_path_at::<()>(start, end, 0f32).unwrap();
+        let route = points(&baseline);
+        let length: f32 = route.windows(2).map(|p| p[0].distance(&p[1])).sum();
+        assert!(arrival >= length / speed * 0.99);
+
+        // balloon drifting north reaches the first waypoint when the plane does
+        let target = route[1];
+        let t = route[0].distance(&target) / speed;
+        let balloon = Obstacle::new(
+            Location::from_meters(target.x, target.y - 10f32 * t, 50f32, &origin),
+            20f32,
+            50f32,
+        )
+        .velocity(0f32, 10f32);
+        tanstar.set_obstacles(vec![wall, balloon.clone()]).unwrap();
+
+        let (path, _) = tanstar.adjust_path_at::<()>(start, end, 0f32).unwrap();
+        assert_ne!(path, baseline);
+        let track = Track::new(&balloon, &origin, tanstar.config.buffer_size).unwrap();
+        let mut time = 0f32;
+        for pair in points(&path).windows(2) {
+            let dt = pair[0].distance(&pair[1]) / speed;
+            assert!(!track.collides(&pair[0], &pair[1], time, time + dt));
+            time += dt;
+        }
+
+        // long after the balloon has passed, the baseline is flown again
+        let (late, _) = tanstar.adjust_path_at::<()>(start, end, 1000f32).unwrap();
+        assert_eq!(late, baseline);
+    }
+}
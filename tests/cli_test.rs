@@ -0,0 +1,123 @@
+#![cfg(feature = "serde")]
+extern crate pathfinder;
+extern crate serde_json;
+
+use pathfinder::*;
+use serde_json::Value;
+use std::env;
+use std::fs;
+use std::process::{Command, Output};
+
+fn run(args: &[&str]) -> Output {
+    Command::new(env!("CARGO_BIN_EXE_pathfinder"))
+        .args(args)
+        .output()
+        .unwrap()
+}
+
+#[test]
+fn cli_json() {
+    let output = run(&["tests/data/mission_3.json"]);
+    assert_eq!(output.status.code(), Some(0));
+    let route: Vec<Value> = serde_json::from_slice(&output.stdout).unwrap();
+    assert!(route.len() >= 3);
+    assert_eq!(route.last().unwrap()["lat"], 30.289718627929688);
+}
+
+#[test]
+fn cli_csv() {
+    let output = run(&["tests/data/mission_0.toml", "--format", "csv"]);
+    assert_eq!(output.status.code(), Some(0));
+    let csv = String::from_utf8(output.stdout).unwrap();
+    let mut lines = csv.lines();
+    assert_eq!(lines.next(), Some("lat,lon,alt,radius"));
+    assert!(lines.all(|line| line.split(',').count() == 4));
+}
+
+#[test]
+fn cli_geojson() {
+    let path = env::temp_dir().join("pathfinder_cli_route.geojson");
+    let graph = env::temp_dir().join("pathfinder_cli_graph.geojson");
+    let output = run(&[
+        "tests/data/mission_3.json",
+        "--buffer-size",
+        "5",
+        "--output",
+        path.to_str().unwrap(),
+        "--graph",
+        graph.to_str().unwrap(),
+    ]);
+    assert_eq!(output.status.code(), Some(0));
+    let geojson: Value = serde_json::from_str(&fs::read_to_string(&path).unwrap()).unwrap();
+    fs::remove_file(&path).unwrap();
+    assert_eq!(geojson["type"], "FeatureCollection");
+    assert_eq!(geojson["features"][0]["geometry"]["type"], "LineString");
+
+    let graph_geojson: Value = serde_json::from_str(&fs::read_to_string(&graph).unwrap()).unwrap();
+    fs::remove_file(&graph).unwrap();
+    let features = graph_geojson["features"].as_array().unwrap();
+    assert!(features
+        .iter()
+        .any(|f| f["properties"]["kind"] == "connection"));
+    assert_eq!(features.last().unwrap()["properties"]["kind"], "path");
+}
+
+#[test]
+fn cli_partial() {
+    // last waypoint is inside the obstacle
+    let mut mission = Mission::<()>::load("tests/data/mission_3.json").unwrap();
+    let obstacle = mission.obstacles[0].location;
+    mission.waypoints.push(Waypoint::from_degrees(
+        obstacle.lat_degree(),
+        obstacle.lon_degree(),
+        50f32,
+        10f32,
+    ));
+    let path = env::temp_dir().join("pathfinder_cli_partial.json");
+    mission.save(&path).unwrap();
+
+    let output = run(&[path.to_str().unwrap()]);
+    fs::remove_file(&path).unwrap();
+    assert_eq!(output.status.code(), Some(1));
+    let route: Vec<Value> = serde_json::from_slice(&output.stdout).unwrap();
+    assert!(route.len() >= 3);
+}
+
+#[test]
+fn cli_invalid() {
+    assert_eq!(run(&[]).status.code(), Some(2));
+    assert_eq!(run(&["--format", "kml", "x.json"]).status.code(), Some(2));
+    assert_eq!(run(&["tests/data/missing.json"]).status.code(), Some(2));
+    assert_eq!(
+        run(&["tests/data/mission_3.json", "--turning-radius", "five"])
+            .status
+            .code(),
+        Some(2)
+    );
+}
+
+#[test]
+fn cli_config() {
+    let output = run(&["tests/data/mission_3.json", "--config", "pathfinder.toml"]);
+    assert_eq!(output.status.code(), Some(0));
+
+    let env = |name: &str, value: &str| {
+        Command::new(env!("CARGO_BIN_EXE_pathfinder"))
+            .args(["tests/data/mission_3.json"])
+            .env(name, value)
+            .output()
+            .unwrap()
+    };
+    assert_eq!(
+        env("PATHFINDER_TURNING_RADIUS", "-5").status.code(),
+        Some(2)
+    );
+    assert_eq!(env("PATHFINDER_BUFFER_SIZE", "wide").status.code(), Some(2));
+    assert_eq!(env("PATHFINDER_BUFFER_SIZE", "2").status.code(), Some(0));
+    assert_eq!(
+        run(&["tests/data/mission_3.json", "--config", "missing.toml"])
+            .status
+            .code(),
+        Some(2)
+    );
+}
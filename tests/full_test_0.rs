@@ -36,6 +36,8 @@ fn test0() {
     let mut pathfinder = Pathfinder::new(Tanstar::new(), TConfig::default(), flyzone, obstacles);
     // let plane = Plane::from_degrees(30.32298, -97.60310, 100.0).yaw(170f32);
     let plane = Plane::from_degrees(30.322280883789063, -97.60298156738281, 100f32).yaw(170f32);
-    let result = pathfinder.get_adjust_path(plane.clone(), waypoints.clone());
+    let result = pathfinder
+        .get_adjust_path(plane.clone(), waypoints.clone())
+        .unwrap();
     output_result(waypoints, result, plane);
 }
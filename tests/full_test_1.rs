@@ -30,6 +30,8 @@ fn test1() {
     let flyzone = vec![flyzone];
     let mut pathfinder = Pathfinder::new(Tanstar::new(), TConfig::default(), flyzone, obstacles);
     let plane = Plane::from_degrees(30.32491, -97.60159, 10.0);
-    let result = pathfinder.get_adjust_path(plane.clone(), waypoints.clone());
+    let result = pathfinder
+        .get_adjust_path(plane.clone(), waypoints.clone())
+        .unwrap();
     output_result(waypoints, result, plane);
 }
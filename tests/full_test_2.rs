@@ -25,6 +25,8 @@ fn test2() {
 
     let mut pathfinder = Pathfinder::new(Tanstar::new(), TConfig::default(), flyzone, obstacles);
     let plane = Plane::from_degrees(30.288105, -97.73533, 10.0);
-    let result = pathfinder.get_adjust_path(plane.clone(), waypoints.clone());
+    let result = pathfinder
+        .get_adjust_path(plane.clone(), waypoints.clone())
+        .unwrap();
     output_result(waypoints, result, plane);
 }
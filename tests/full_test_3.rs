@@ -28,7 +28,9 @@ fn test3() {
 
     let plane = Plane::from_degrees(30.2881757, -97.7354343, 10.0);
     let mut pathfinder = Pathfinder::new(Tanstar::new(), TConfig::default(), flyzone, obstacles);
-    let result = pathfinder.get_adjust_path(plane.clone(), waypoints.clone());
+    let result = pathfinder
+        .get_adjust_path(plane.clone(), waypoints.clone())
+        .unwrap();
     output_result(waypoints, result, plane);
 }
 
@@ -64,6 +66,8 @@ fn test3_with_data() {
 
     let mut pathfinder = Pathfinder::new(Tanstar::new(), TConfig::default(), flyzone, obstacles);
     let plane = Plane::from_degrees(30.2881757, -97.7354343, 10.0);
-    let result = pathfinder.get_adjust_path(plane.clone(), waypoints.clone());
+    let result = pathfinder
+        .get_adjust_path(plane.clone(), waypoints.clone())
+        .unwrap();
     output_result(waypoints, result, plane);
 }
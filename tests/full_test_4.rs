@@ -28,6 +28,8 @@ fn test4() {
 
     let mut pathfinder = Pathfinder::new(Tanstar::new(), TConfig::default(), flyzone, obstacles);
     let plane = Plane::from_degrees(38.15059, -76.43147, 10.0);
-    let result = pathfinder.get_adjust_path(plane.clone(), waypoints.clone());
+    let result = pathfinder
+        .get_adjust_path(plane.clone(), waypoints.clone())
+        .unwrap();
     output_result(waypoints, result, plane);
 }
@@ -40,6 +40,8 @@ fn test6() {
     let plane = Plane::from_degrees(30.32222, -97.60060, 100.0).yaw(170f32);
     // let plane = Plane::from_degrees(30.32551, -97.60331, 100.0).yaw(170f32);
     // let plane = Plane::from_degrees(30.32298, -97.60310, 100.0).yaw(170f32);
-    let result = pathfinder.get_adjust_path(plane.clone(), waypoints.clone());
+    let result = pathfinder
+        .get_adjust_path(plane.clone(), waypoints.clone())
+        .unwrap();
     output_result(waypoints, result, plane);
 }
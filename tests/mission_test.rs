@@ -0,0 +1,74 @@
+#![cfg(feature = "serde")]
+extern crate pathfinder;
+
+use pathfinder::*;
+use std::env;
+use std::fs;
+
+mod util;
+use util::*;
+
+fn plan(file: &str) {
+    let mission = Mission::<()>::load(format!("tests/data/{}", file)).unwrap();
+    let mut pathfinder = mission.pathfinder().unwrap();
+    let waypoints = vec_to_list(mission.waypoints.clone());
+    let result = pathfinder
+        .get_adjust_path(mission.plane, waypoints.clone())
+        .unwrap();
+    assert!(result.len() >= waypoints.len());
+    output_result(waypoints, result, mission.plane);
+}
+
+#[test]
+fn mission_json() {
+    plan("mission_3.json");
+}
+
+#[test]
+fn mission_toml() {
+    plan("mission_0.toml");
+}
+
+// Scenarios of the full tests, one file each
+#[test]
+fn mission_1() {
+    plan("mission_1.json");
+}
+
+#[test]
+fn mission_2() {
+    plan("mission_2.json");
+}
+
+#[test]
+fn mission_4() {
+    plan("mission_4.json");
+}
+
+#[test]
+fn mission_5() {
+    plan("mission_5.toml");
+}
+
+#[test]
+fn mission_6() {
+    plan("mission_6.toml");
+}
+
+#[test]
+fn mission_7() {
+    plan("mission_7.toml");
+}
+
+#[test]
+fn mission_save_load() {
+    let mission = Mission::<()>::load("tests/data/mission_3.json").unwrap();
+    let path = env::temp_dir().join("pathfinder_mission_save_load.toml");
+    mission.save(&path).unwrap();
+    let loaded = Mission::<()>::load(&path).unwrap();
+    fs::remove_file(&path).unwrap();
+    assert_eq!(loaded.flyzones.len(), mission.flyzones.len());
+    assert_eq!(loaded.obstacles.len(), mission.obstacles.len());
+    assert_eq!(loaded.waypoints.len(), mission.waypoints.len());
+    assert_eq!(loaded.config, mission.config);
+}